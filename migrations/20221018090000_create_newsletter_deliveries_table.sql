@@ -0,0 +1,9 @@
+-- Add migration script here
+
+CREATE TABLE newsletter_deliveries
+(
+    subscriber_id uuid        NOT NULL
+        REFERENCES subscriptions (id),
+    title         TEXT        NOT NULL,
+    delivered_at  timestamptz NOT NULL
+);
@@ -0,0 +1,37 @@
+-- Add migration script here
+
+-- Emails that only differ by case belong to the same person. Merge each group of
+-- duplicates into one row: the confirmed one if any, otherwise the oldest.
+CREATE TEMPORARY TABLE duplicate_subscriptions AS
+SELECT id,
+       first_value(id) OVER (
+           PARTITION BY lower(email)
+           ORDER BY (status = 'confirmed') DESC, subscribed_at, id
+       ) AS survivor_id
+FROM subscriptions;
+
+DELETE FROM duplicate_subscriptions WHERE id = survivor_id;
+
+UPDATE subscription_tokens
+SET subscriber_id = duplicate_subscriptions.survivor_id
+FROM duplicate_subscriptions
+WHERE subscription_tokens.subscriber_id = duplicate_subscriptions.id;
+
+UPDATE newsletter_deliveries
+SET subscriber_id = duplicate_subscriptions.survivor_id
+FROM duplicate_subscriptions
+WHERE newsletter_deliveries.subscriber_id = duplicate_subscriptions.id;
+
+DELETE FROM subscriptions
+USING duplicate_subscriptions
+WHERE subscriptions.id = duplicate_subscriptions.id;
+
+DROP TABLE duplicate_subscriptions;
+
+-- Match the normalisation applied by `SubscriberEmail::parse`: the domain is lower-cased
+UPDATE subscriptions
+SET email = substring(email from '^(.*)@') || '@' || lower(substring(email from '@([^@]*)$'))
+WHERE email <> substring(email from '^(.*)@') || '@' || lower(substring(email from '@([^@]*)$'));
+
+ALTER TABLE subscriptions DROP CONSTRAINT subscriptions_email_key;
+CREATE UNIQUE INDEX subscriptions_email_lower_key ON subscriptions (lower(email));
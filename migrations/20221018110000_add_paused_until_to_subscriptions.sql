@@ -0,0 +1,3 @@
+-- Add migration script here
+
+ALTER TABLE subscriptions ADD COLUMN paused_until timestamptz NULL;
@@ -0,0 +1,11 @@
+-- Add migration script here
+
+ALTER TABLE subscriptions ADD COLUMN attributes JSONB NOT NULL DEFAULT '{}';
+
+CREATE TABLE subscriber_attribute_definitions
+(
+    key        TEXT        NOT NULL,
+    value_type TEXT        NOT NULL CHECK (value_type IN ('string', 'number', 'boolean')),
+    created_at timestamptz NOT NULL,
+    PRIMARY KEY (key)
+);
@@ -0,0 +1,11 @@
+-- Add migration script here
+CREATE TABLE subscriber_imports(
+    id uuid PRIMARY KEY,
+    imported_by uuid NOT NULL REFERENCES users (user_id),
+    imported_at timestamptz NOT NULL,
+    -- How the imported subscribers were brought in: 'confirmed' or 'send_confirmation'
+    mode TEXT NOT NULL,
+    -- Where consent was obtained for subscribers imported as already confirmed
+    consent_note TEXT NULL
+);
+ALTER TABLE subscriptions ADD COLUMN import_id uuid NULL REFERENCES subscriber_imports (id);
@@ -0,0 +1,9 @@
+-- Add migration script here
+CREATE TABLE archived_pending_subscriptions(
+    id uuid PRIMARY KEY,
+    email TEXT NOT NULL,
+    name TEXT NOT NULL,
+    subscribed_at timestamptz NOT NULL,
+    attributes JSONB NOT NULL,
+    archived_at timestamptz NOT NULL
+);
@@ -0,0 +1,18 @@
+-- Add migration script here
+ALTER TABLE subscriptions ADD CONSTRAINT subscriptions_status_check
+    CHECK (status IN ('pending_confirmation', 'confirmed', 'unsubscribed', 'bounced', 'complained'));
+
+CREATE TABLE subscription_status_changes(
+    subscriber_id uuid NOT NULL REFERENCES subscriptions (id),
+    -- NULL for the status a subscription was created with
+    from_status TEXT NULL,
+    to_status TEXT NOT NULL,
+    changed_at timestamptz NOT NULL
+);
+CREATE INDEX subscription_status_changes_subscriber_id_idx
+    ON subscription_status_changes (subscriber_id, changed_at);
+
+-- Earlier changes were not recorded, so the history of existing subscriptions
+-- starts with their current status.
+INSERT INTO subscription_status_changes (subscriber_id, from_status, to_status, changed_at)
+SELECT id, NULL, status, subscribed_at FROM subscriptions;
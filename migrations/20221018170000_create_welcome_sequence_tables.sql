@@ -0,0 +1,24 @@
+-- Add migration script here
+CREATE TABLE welcome_sequence_steps(
+    id uuid NOT NULL,
+    PRIMARY KEY (id),
+    -- Days after confirmation at which the step is sent
+    delay_days INT NOT NULL CHECK (delay_days >= 0),
+    subject TEXT NOT NULL,
+    html_content TEXT NOT NULL,
+    text_content TEXT NOT NULL,
+    created_at timestamptz NOT NULL
+);
+
+CREATE TABLE welcome_sequence_enrollments(
+    subscriber_id uuid NOT NULL REFERENCES subscriptions (id),
+    PRIMARY KEY (subscriber_id),
+    enrolled_at timestamptz NOT NULL
+);
+
+CREATE TABLE welcome_sequence_deliveries(
+    subscriber_id uuid NOT NULL REFERENCES subscriptions (id),
+    step_id uuid NOT NULL REFERENCES welcome_sequence_steps (id),
+    PRIMARY KEY (subscriber_id, step_id),
+    sent_at timestamptz NOT NULL
+);
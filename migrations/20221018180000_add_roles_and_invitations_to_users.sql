@@ -0,0 +1,19 @@
+-- Add migration script here
+-- Existing users, i.e. the seeded admin, keep full access as owners.
+ALTER TABLE users ADD COLUMN role TEXT NOT NULL DEFAULT 'owner'
+    CHECK (role IN ('owner', 'editor', 'viewer'));
+ALTER TABLE users ALTER COLUMN role DROP DEFAULT;
+ALTER TABLE users ADD COLUMN active BOOLEAN NOT NULL DEFAULT TRUE;
+-- NULL for users created before invitations existed
+ALTER TABLE users ADD COLUMN email TEXT NULL UNIQUE;
+
+CREATE TABLE user_invitations(
+    id uuid NOT NULL,
+    PRIMARY KEY (id),
+    email TEXT NOT NULL,
+    role TEXT NOT NULL CHECK (role IN ('owner', 'editor', 'viewer')),
+    invited_by uuid NOT NULL REFERENCES users (user_id),
+    invited_at timestamptz NOT NULL,
+    -- Set once the invitation has been used, so that each link works only once
+    accepted_at timestamptz NULL
+);
@@ -0,0 +1,18 @@
+-- Add migration script here
+CREATE TABLE user_totp(
+    user_id uuid NOT NULL REFERENCES users (user_id),
+    PRIMARY KEY (user_id),
+    -- Base32, as shown to authenticator apps
+    secret TEXT NOT NULL,
+    -- NULL until the user has entered a code from their authenticator app
+    confirmed_at timestamptz NULL,
+    -- The last time step a code was accepted for, so that each code works only once
+    last_used_step BIGINT NULL
+);
+
+CREATE TABLE user_recovery_codes(
+    user_id uuid NOT NULL REFERENCES users (user_id),
+    code_hash TEXT NOT NULL,
+    PRIMARY KEY (user_id, code_hash),
+    used_at timestamptz NULL
+);
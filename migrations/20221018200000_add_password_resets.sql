@@ -0,0 +1,12 @@
+-- Add migration script here
+CREATE TABLE password_resets(
+    id uuid NOT NULL,
+    PRIMARY KEY (id),
+    user_id uuid NOT NULL REFERENCES users (user_id),
+    requested_at timestamptz NOT NULL,
+    -- Set when the reset link is used, or when another link for the same user is
+    used_at timestamptz NULL
+);
+
+-- Sessions that logged in before this moment are no longer valid.
+ALTER TABLE users ADD COLUMN sessions_revoked_at timestamptz NULL;
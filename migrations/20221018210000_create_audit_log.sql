@@ -0,0 +1,16 @@
+-- Add migration script here
+CREATE TABLE audit_log(
+    id uuid NOT NULL,
+    PRIMARY KEY (id),
+    occurred_at timestamptz NOT NULL,
+    -- NULL when nobody was logged in, e.g. for a failed login
+    actor_id uuid NULL REFERENCES users (user_id),
+    action TEXT NOT NULL,
+    -- What the action was about, e.g. the username of a failed login or the title
+    -- of a newsletter issue
+    target TEXT NULL,
+    client_ip TEXT NOT NULL,
+    user_agent TEXT NULL
+);
+
+CREATE INDEX audit_log_occurred_at_idx ON audit_log (occurred_at);
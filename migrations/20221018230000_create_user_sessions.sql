@@ -0,0 +1,15 @@
+-- Add migration script here
+CREATE TABLE user_sessions(
+    id uuid NOT NULL,
+    PRIMARY KEY (id),
+    user_id uuid NOT NULL REFERENCES users (user_id),
+    -- Where the session was last used from
+    client_ip TEXT NOT NULL,
+    user_agent TEXT NULL,
+    created_at timestamptz NOT NULL,
+    last_seen_at timestamptz NOT NULL,
+    -- Set when the session logs out or is revoked from another one
+    revoked_at timestamptz NULL
+);
+
+CREATE INDEX user_sessions_user_id_idx ON user_sessions (user_id);
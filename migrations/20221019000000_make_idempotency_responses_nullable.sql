@@ -0,0 +1,5 @@
+-- A row is inserted before the request is processed, to hold the key against
+-- concurrent retries, and the response is filled in once it is ready.
+ALTER TABLE idempotency ALTER COLUMN response_status_code DROP NOT NULL;
+ALTER TABLE idempotency ALTER COLUMN response_body DROP NOT NULL;
+ALTER TABLE idempotency ALTER COLUMN response_headers DROP NOT NULL;
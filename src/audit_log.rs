@@ -0,0 +1,133 @@
+use actix_web::{FromRequest, HttpRequest};
+use actix_web::dev::Payload;
+use actix_web::http::header::USER_AGENT;
+use anyhow::Context;
+use chrono::Utc;
+use std::future::{ready, Ready};
+use uuid::Uuid;
+
+use crate::startup::DbConnectionKind;
+use crate::utils::client_ip;
+
+// Longer user agents are cut off, so that a client cannot fill the log with junk.
+const MAXIMUM_USER_AGENT_LENGTH: usize = 512;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AuditAction {
+    Login,
+    LoginFailed,
+    Logout,
+    PasswordChanged,
+    NewsletterPublished,
+    ApiTokenCreated,
+    ApiTokenRevoked,
+    SessionRevoked,
+}
+
+impl AuditAction {
+    pub const ALL: [AuditAction; 8] = [
+        Self::Login,
+        Self::LoginFailed,
+        Self::Logout,
+        Self::PasswordChanged,
+        Self::NewsletterPublished,
+        Self::ApiTokenCreated,
+        Self::ApiTokenRevoked,
+        Self::SessionRevoked,
+    ];
+
+    pub fn parse(s: &str) -> Result<Self, String> {
+        Self::ALL
+            .into_iter()
+            .find(|action| action.as_str() == s)
+            .ok_or_else(|| format!("{} is not an audited action", s))
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Login => "login",
+            Self::LoginFailed => "login_failed",
+            Self::Logout => "logout",
+            Self::PasswordChanged => "password_changed",
+            Self::NewsletterPublished => "newsletter_published",
+            Self::ApiTokenCreated => "api_token_created",
+            Self::ApiTokenRevoked => "api_token_revoked",
+            Self::SessionRevoked => "session_revoked",
+        }
+    }
+}
+
+impl std::fmt::Display for AuditAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// Where a request came from, as recorded in the audit log.
+#[derive(Debug, Clone)]
+pub struct RequestOrigin {
+    pub client_ip: String,
+    pub user_agent: Option<String>,
+}
+
+impl FromRequest for RequestOrigin {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let user_agent = req
+            .headers()
+            .get(USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.chars().take(MAXIMUM_USER_AGENT_LENGTH).collect());
+        ready(Ok(RequestOrigin {
+            client_ip: client_ip(req),
+            user_agent,
+        }))
+    }
+}
+
+#[tracing::instrument(name = "Recording an audit event", skip(database, target, origin))]
+pub async fn record_audit_event(
+    database: &DbConnectionKind,
+    actor_id: Option<Uuid>,
+    action: AuditAction,
+    target: Option<&str>,
+    origin: &RequestOrigin,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO audit_log (id, occurred_at, actor_id, action, target, client_ip, user_agent)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        "#,
+        Uuid::new_v4(),
+        Utc::now(),
+        actor_id,
+        action.as_str(),
+        target,
+        origin.client_ip,
+        origin.user_agent
+    )
+        .execute(database)
+        .await
+        .context("Failed to record an audit event")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AuditAction;
+    use claim::{assert_err, assert_ok_eq};
+
+    #[test]
+    fn every_action_round_trips_through_its_string_form() {
+        for action in AuditAction::ALL {
+            assert_ok_eq!(AuditAction::parse(action.as_str()), action);
+        }
+    }
+
+    #[test]
+    fn unknown_actions_are_rejected() {
+        assert_err!(AuditAction::parse("launch_missiles"));
+    }
+}
@@ -0,0 +1,156 @@
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use secrecy::{ExposeSecret, Secret};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::domain::ApiScope;
+use crate::startup::DbConnectionKind;
+
+// The prefix makes leaked tokens easy to recognise, e.g. by secret scanners.
+const TOKEN_PREFIX: &str = "z2p_";
+const TOKEN_LENGTH: usize = 40;
+
+pub struct ApiToken {
+    pub id: Uuid,
+    pub name: String,
+    pub scopes: Vec<ApiScope>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+// The owner and scopes of a valid token.
+pub struct ApiTokenGrant {
+    pub token_id: Uuid,
+    pub user_id: Uuid,
+    pub scopes: Vec<ApiScope>,
+}
+
+// Tokens are random enough that a fast hash is sufficient: there is nothing to
+// gain from guessing them offline.
+fn hash_api_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+fn parse_scopes(scopes: Vec<String>) -> Result<Vec<ApiScope>, anyhow::Error> {
+    scopes
+        .iter()
+        .map(|scope| ApiScope::parse(scope).map_err(anyhow::Error::msg))
+        .collect()
+}
+
+// Returns the token itself, which is not stored and cannot be shown again.
+#[tracing::instrument(name = "Create API token", skip(database))]
+pub async fn create_api_token(
+    user_id: Uuid,
+    name: &str,
+    scopes: &[ApiScope],
+    database: &DbConnectionKind,
+) -> Result<Secret<String>, anyhow::Error> {
+    let random: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .map(char::from)
+        .take(TOKEN_LENGTH)
+        .collect();
+    let token = Secret::new(format!("{}{}", TOKEN_PREFIX, random));
+    let scopes: Vec<String> = scopes.iter().map(|scope| scope.as_str().to_string()).collect();
+    sqlx::query!(
+        r#"
+        INSERT INTO api_tokens (id, user_id, name, token_hash, scopes, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+        Uuid::new_v4(),
+        user_id,
+        name,
+        hash_api_token(token.expose_secret()),
+        &scopes,
+        Utc::now()
+    )
+        .execute(database)
+        .await
+        .context("Failed to store the API token")?;
+    Ok(token)
+}
+
+// Returns `None` for unknown and revoked tokens.
+#[tracing::instrument(name = "Authenticate API token", skip(token, database))]
+pub async fn authenticate_api_token(
+    token: &Secret<String>,
+    database: &DbConnectionKind,
+) -> Result<Option<ApiTokenGrant>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        UPDATE api_tokens SET last_used_at = $2
+        WHERE token_hash = $1 AND revoked_at IS NULL
+        RETURNING id, user_id, scopes
+        "#,
+        hash_api_token(token.expose_secret()),
+        Utc::now()
+    )
+        .fetch_optional(database)
+        .await
+        .context("Failed to look up the API token")?;
+    row.map(|row| {
+        Ok(ApiTokenGrant {
+            token_id: row.id,
+            user_id: row.user_id,
+            scopes: parse_scopes(row.scopes)?,
+        })
+    })
+        .transpose()
+}
+
+#[tracing::instrument(name = "List API tokens", skip(database))]
+pub async fn list_api_tokens(
+    user_id: Uuid,
+    database: &DbConnectionKind,
+) -> Result<Vec<ApiToken>, anyhow::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT id, name, scopes, created_at, last_used_at, revoked_at
+        FROM api_tokens
+        WHERE user_id = $1
+        ORDER BY created_at DESC
+        "#,
+        user_id
+    )
+        .fetch_all(database)
+        .await
+        .context("Failed to retrieve the API tokens")?;
+    rows.into_iter()
+        .map(|row| {
+            Ok(ApiToken {
+                id: row.id,
+                name: row.name,
+                scopes: parse_scopes(row.scopes)?,
+                created_at: row.created_at,
+                last_used_at: row.last_used_at,
+                revoked_at: row.revoked_at,
+            })
+        })
+        .collect()
+}
+
+// Users can only revoke their own tokens. Returns `false` if there was no such
+// token, or it had already been revoked.
+#[tracing::instrument(name = "Revoke API token", skip(database))]
+pub async fn revoke_api_token(
+    user_id: Uuid,
+    token_id: Uuid,
+    database: &DbConnectionKind,
+) -> Result<bool, anyhow::Error> {
+    let revoked = sqlx::query!(
+        "UPDATE api_tokens SET revoked_at = $3 WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL",
+        token_id,
+        user_id,
+        Utc::now()
+    )
+        .execute(database)
+        .await
+        .context("Failed to revoke the API token")?
+        .rows_affected();
+    Ok(revoked == 1)
+}
@@ -0,0 +1,95 @@
+use actix_web::{FromRequest, HttpMessage, HttpRequest};
+use actix_web::dev::Payload;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use std::future::{ready, Ready};
+
+use crate::session_state::TypedSession;
+use crate::utils::constant_time_eq;
+
+// The name of the hidden form field that carries the token.
+pub const CSRF_FORM_FIELD: &str = "csrf_token";
+// Scripts and tests that cannot easily add a form field may send the token as a header.
+pub const CSRF_HEADER: &str = "X-CSRF-Token";
+const TOKEN_LENGTH: usize = 32;
+
+// The CSRF token of the current session. A forged form on another site cannot
+// read it, so a submission that carries it came from one of our own pages.
+#[derive(Clone, Debug)]
+pub struct CsrfToken(String);
+
+impl CsrfToken {
+    // Returns the session's token, creating one the first time it is needed.
+    pub fn for_session(session: &TypedSession) -> Result<Self, serde_json::Error> {
+        if let Some(token) = session.get_csrf_token()? {
+            return Ok(Self(token));
+        }
+        let token: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .map(char::from)
+            .take(TOKEN_LENGTH)
+            .collect();
+        session.insert_csrf_token(&token)?;
+        Ok(Self(token))
+    }
+
+    pub fn matches(&self, submitted: &str) -> bool {
+        constant_time_eq(self.0.as_bytes(), submitted.as_bytes())
+    }
+
+    // The hidden input to put in every form that posts to an admin route.
+    pub fn form_field(&self) -> String {
+        format!(r#"<input type="hidden" name="{}" value="{}">"#, CSRF_FORM_FIELD, self.0)
+    }
+}
+
+// Set by `RejectUnauthorizedUsers` for logged in users. Requests authenticated by
+// API token have no session and therefore no token: the pages they get back carry
+// an empty one, which is fine since scripts do not submit forms.
+impl FromRequest for CsrfToken {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(Ok(req
+            .extensions()
+            .get::<CsrfToken>()
+            .cloned()
+            .unwrap_or_else(|| CsrfToken(String::new()))))
+    }
+}
+
+// Finds a field in a URL-encoded form body without deserializing the rest of it.
+pub fn form_field_value(body: &[u8], name: &str) -> Option<String> {
+    std::str::from_utf8(body)
+        .ok()?
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .and_then(|(_, value)| urlencoding::decode(&value.replace('+', " ")).ok())
+        .map(|value| value.into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::form_field_value;
+    use claim::{assert_none, assert_some_eq};
+
+    #[test]
+    fn a_field_is_found_anywhere_in_the_body() {
+        assert_some_eq!(form_field_value(b"csrf_token=abc&title=Hi", "csrf_token"), "abc".to_string());
+        assert_some_eq!(form_field_value(b"title=Hi&csrf_token=abc", "csrf_token"), "abc".to_string());
+    }
+
+    #[test]
+    fn values_are_decoded() {
+        assert_some_eq!(form_field_value(b"title=Hello+there%21", "title"), "Hello there!".to_string());
+    }
+
+    #[test]
+    fn a_missing_field_is_none() {
+        assert_none!(form_field_value(b"title=Hi&xcsrf_token=abc", "csrf_token"));
+        assert_none!(form_field_value(b"", "csrf_token"));
+        assert_none!(form_field_value(&[0xff, 0xfe], "csrf_token"));
+    }
+}
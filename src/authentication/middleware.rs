@@ -0,0 +1,378 @@
+use actix_web::{FromRequest, HttpMessage, HttpRequest, HttpResponse, web};
+use actix_web::web::BytesMut;
+use actix_web::dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::error::{ErrorInternalServerError, ErrorPayloadTooLarge, InternalError};
+use actix_web::http::header::{ContentType, HeaderMap, AUTHORIZATION, WWW_AUTHENTICATE};
+use actix_web::http::Method;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use futures_util::future::LocalBoxFuture;
+use futures_util::StreamExt;
+use secrecy::Secret;
+use std::future::{ready, Ready};
+use std::rc::Rc;
+use uuid::Uuid;
+
+use crate::authentication::{
+    authenticate_api_token, form_field_value, touch_user_session, CsrfToken, CSRF_FORM_FIELD, CSRF_HEADER,
+};
+use crate::domain::{ApiScope, UserRole};
+use crate::session_state::TypedSession;
+use crate::startup::DbConnectionKind;
+use crate::utils::{client_ip, e500, see_other};
+
+// Admin forms are small: the largest is a pasted CSV import, which this leaves room
+// for at 10,000 rows once URL-encoded.
+pub const MAXIMUM_FORM_BYTES: usize = 4 * 1024 * 1024;
+
+// Shown when a form is submitted without the session's CSRF token, which is most
+// likely because the session expired while the form was open.
+const EXPIRED_FORM_PAGE: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Form expired</title>
+</head>
+<body>
+    <p>This form has expired, or was not sent from this site. Nothing has been changed.</p>
+    <p>Go back, reload the page and try again.</p>
+    <p><a href="/admin/dashboard">Dashboard</a></p>
+</body>
+</html>"#;
+
+// The user behind an admin request, loaded once by `RejectUnauthorizedUsers`.
+#[derive(Clone, Debug)]
+pub struct AuthenticatedUser {
+    pub user_id: Uuid,
+    pub username: String,
+    pub role: UserRole,
+    pub authenticated_with: AuthenticationMethod,
+}
+
+// Scripts using an API token cannot follow redirects to pages or read flash
+// messages, so handlers they can reach answer them differently.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AuthenticationMethod {
+    Session,
+    ApiToken,
+}
+
+impl FromRequest for AuthenticatedUser {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(
+            req.extensions()
+                .get::<AuthenticatedUser>()
+                .cloned()
+                .ok_or_else(|| ErrorInternalServerError("The route is not behind the admin middleware"))
+        )
+    }
+}
+
+// The least privileged role allowed on an admin route. Anyone can look around and
+// manage their own account, changing anything else needs an editor, and managing
+// users or reading the audit log needs an owner. New routes that change something
+// are therefore closed to viewers unless they are listed here.
+pub fn required_role(method: &Method, path: &str) -> UserRole {
+    if path == "/admin/users" || path.starts_with("/admin/users/") || path == "/admin/audit-log" {
+        return UserRole::Owner;
+    }
+    let is_read_only = matches!(*method, Method::GET | Method::HEAD);
+    let is_own_account = matches!(path, "/admin/password" | "/admin/logout")
+        || path.starts_with("/admin/two-factor")
+        || path.starts_with("/admin/api-tokens")
+        || path.starts_with("/admin/sessions");
+    if is_read_only || is_own_account {
+        UserRole::Viewer
+    } else {
+        UserRole::Editor
+    }
+}
+
+// Wraps the `/admin` scope: anonymous users, deactivated users and revoked sessions
+// are sent to the login page, users without the role a route requires get a 403,
+// and everyone else is made available to handlers as an `AuthenticatedUser`.
+// Changes made through the session also need its `CsrfToken`. Requests with an
+// `Authorization: Bearer` header are authenticated by API token instead, and get a
+// 401 rather than a redirect when the token is not valid.
+pub struct RejectUnauthorizedUsers;
+
+impl<S, B> Transform<S, ServiceRequest> for RejectUnauthorizedUsers
+    where
+        S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+        B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Transform = RejectUnauthorizedUsersMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RejectUnauthorizedUsersMiddleware { service: Rc::new(service) }))
+    }
+}
+
+pub struct RejectUnauthorizedUsersMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for RejectUnauthorizedUsersMiddleware<S>
+    where
+        S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+        B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        Box::pin(async move {
+            let database = req
+                .app_data::<web::Data<DbConnectionKind>>()
+                .cloned()
+                .ok_or_else(|| ErrorInternalServerError("The database is not configured"))?;
+
+            let user = match bearer_token(req.headers()) {
+                Some(token) => authenticate_with_token(&token, req.method(), req.path(), &database).await?,
+                None => {
+                    let client_ip = client_ip(req.request());
+                    let session = {
+                        let (http_request, payload) = req.parts_mut();
+                        TypedSession::from_request(http_request, payload).await
+                    }?;
+                    let (user, csrf_token) = authenticate_with_session(session, &client_ip, &database).await?;
+                    // API tokens are not sent by browsers on their own, so only requests
+                    // riding on the session cookie can be forged by another site.
+                    if !matches!(*req.method(), Method::GET | Method::HEAD) {
+                        verify_csrf_token(&mut req, &csrf_token).await?;
+                    }
+                    req.extensions_mut().insert(csrf_token);
+                    user
+                }
+            };
+
+            let required = required_role(req.method(), req.path());
+            if !user.role.includes(required) {
+                let e = anyhow::anyhow!("{} needs to be {} but is {}", user.username, required, user.role);
+                return Err(forbidden(e, "You do not have permission to do that. Ask an owner to change your role."));
+            }
+
+            req.extensions_mut().insert(user);
+            service.call(req).await
+        })
+    }
+}
+
+async fn authenticate_with_session(
+    session: TypedSession,
+    client_ip: &str,
+    database: &DbConnectionKind,
+) -> Result<(AuthenticatedUser, CsrfToken), actix_web::Error> {
+    let user_id = match session.get_user_id().map_err(e500)? {
+        Some(user_id) => user_id,
+        None => return Err(redirect_to_login(anyhow::anyhow!("The user has not logged in"))),
+    };
+    let logged_in_at = session.get_logged_in_at().map_err(e500)?;
+    let active = get_active_user(user_id, database)
+        .await
+        .map_err(e500)?
+        .filter(|active| !is_revoked(logged_in_at, active.sessions_revoked_at));
+    // Sessions from before the session index have no id and cannot be listed or
+    // revoked, so they are logged out too.
+    let is_listed = match session.get_session_id().map_err(e500)? {
+        Some(session_id) if active.is_some() => touch_user_session(session_id, user_id, client_ip, database)
+            .await
+            .map_err(e500)?,
+        _ => false,
+    };
+    match active {
+        Some(active) if is_listed => {
+            let csrf_token = CsrfToken::for_session(&session).map_err(e500)?;
+            Ok((active.user, csrf_token))
+        }
+        _ => {
+            session.log_out();
+            Err(redirect_to_login(anyhow::anyhow!("The user has been deactivated or the session revoked")))
+        }
+    }
+}
+
+// Tokens act as their owner, but only on the routes their scopes cover.
+async fn authenticate_with_token(
+    token: &Secret<String>,
+    method: &Method,
+    path: &str,
+    database: &DbConnectionKind,
+) -> Result<AuthenticatedUser, actix_web::Error> {
+    let grant = match authenticate_api_token(token, database).await.map_err(e500)? {
+        Some(grant) => grant,
+        None => return Err(reject_token(anyhow::anyhow!("The API token is unknown or has been revoked"))),
+    };
+    let required = ApiScope::required_for(method, path);
+    if !required.map_or(false, |scope| grant.scopes.contains(&scope)) {
+        let e = anyhow::anyhow!("API token {} is not allowed to {} {}", grant.token_id, method, path);
+        return Err(forbidden(e, "This API token is not allowed to do that."));
+    }
+    match get_active_user(grant.user_id, database).await.map_err(e500)? {
+        Some(active) => Ok(AuthenticatedUser { authenticated_with: AuthenticationMethod::ApiToken, ..active.user }),
+        None => Err(reject_token(anyhow::anyhow!("The owner of the API token has been deactivated"))),
+    }
+}
+
+// Looks for the token in the header first, and otherwise in the form. Reading the
+// form consumes the body, so it is put back for the handler.
+async fn verify_csrf_token(req: &mut ServiceRequest, expected: &CsrfToken) -> Result<(), actix_web::Error> {
+    let header = req
+        .headers()
+        .get(CSRF_HEADER)
+        .map(|header| header.to_str().ok().map(|token| token.to_string()));
+    let submitted = match header {
+        Some(token) => token,
+        None if req.content_type() == "application/x-www-form-urlencoded" => {
+            let mut body = BytesMut::new();
+            let mut payload = req.take_payload();
+            while let Some(chunk) = payload.next().await {
+                body.extend_from_slice(&chunk?);
+                if body.len() > MAXIMUM_FORM_BYTES {
+                    return Err(ErrorPayloadTooLarge("The form is too large"));
+                }
+            }
+            let body = body.freeze();
+            let submitted = form_field_value(&body, CSRF_FORM_FIELD);
+            let (_, mut replay) = actix_http::h1::Payload::create(true);
+            replay.unread_data(body);
+            req.set_payload(replay.into());
+            submitted
+        }
+        None => None,
+    };
+    match submitted {
+        Some(submitted) if expected.matches(&submitted) => Ok(()),
+        _ => {
+            let e = anyhow::anyhow!("The CSRF token of a {} to {} is missing or wrong", req.method(), req.path());
+            let response = HttpResponse::Forbidden()
+                .content_type(ContentType::html())
+                .body(EXPIRED_FORM_PAGE);
+            Err(InternalError::from_response(e, response).into())
+        }
+    }
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<Secret<String>> {
+    headers
+        .get(AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(|token| Secret::new(token.trim().to_string()))
+}
+
+fn redirect_to_login(e: anyhow::Error) -> actix_web::Error {
+    InternalError::from_response(e, see_other("/login")).into()
+}
+
+fn reject_token(e: anyhow::Error) -> actix_web::Error {
+    let response = HttpResponse::Unauthorized()
+        .insert_header((WWW_AUTHENTICATE, r#"Bearer realm="admin""#))
+        .finish();
+    InternalError::from_response(e, response).into()
+}
+
+fn forbidden(e: anyhow::Error, message: &'static str) -> actix_web::Error {
+    InternalError::from_response(e, HttpResponse::Forbidden().body(message)).into()
+}
+
+// Sessions without a login time predate its recording, so they count as older
+// than any revocation.
+fn is_revoked(logged_in_at: Option<DateTime<Utc>>, sessions_revoked_at: Option<DateTime<Utc>>) -> bool {
+    match (logged_in_at, sessions_revoked_at) {
+        (_, None) => false,
+        (None, Some(_)) => true,
+        (Some(logged_in_at), Some(revoked_at)) => logged_in_at <= revoked_at,
+    }
+}
+
+struct ActiveUser {
+    user: AuthenticatedUser,
+    sessions_revoked_at: Option<DateTime<Utc>>,
+}
+
+// Returns `None` for users that do not exist or have been deactivated.
+#[tracing::instrument(name = "Get active user", skip(database))]
+async fn get_active_user(
+    user_id: Uuid,
+    database: &DbConnectionKind,
+) -> Result<Option<ActiveUser>, anyhow::Error> {
+    let row = sqlx::query!(
+        "SELECT username, role, sessions_revoked_at FROM users WHERE user_id = $1 AND active",
+        user_id
+    )
+        .fetch_optional(database)
+        .await
+        .context("Failed to retrieve user")?;
+    row.map(|row| {
+        Ok(ActiveUser {
+            user: AuthenticatedUser {
+                user_id,
+                username: row.username,
+                role: UserRole::parse(&row.role).map_err(anyhow::Error::msg)?,
+                authenticated_with: AuthenticationMethod::Session,
+            },
+            sessions_revoked_at: row.sessions_revoked_at,
+        })
+    })
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_revoked, required_role};
+    use crate::domain::UserRole;
+    use actix_web::http::Method;
+    use chrono::{Duration, Utc};
+
+    #[test]
+    fn every_role_can_view_admin_pages() {
+        assert_eq!(required_role(&Method::GET, "/admin/newsletter"), UserRole::Viewer);
+        assert_eq!(required_role(&Method::GET, "/admin/subscribers"), UserRole::Viewer);
+    }
+
+    #[test]
+    fn changes_need_an_editor() {
+        assert_eq!(required_role(&Method::POST, "/admin/newsletter"), UserRole::Editor);
+        assert_eq!(required_role(&Method::POST, "/admin/subscribers/delete"), UserRole::Editor);
+    }
+
+    #[test]
+    fn everyone_can_manage_their_own_account() {
+        assert_eq!(required_role(&Method::POST, "/admin/password"), UserRole::Viewer);
+        assert_eq!(required_role(&Method::POST, "/admin/logout"), UserRole::Viewer);
+        assert_eq!(required_role(&Method::POST, "/admin/two-factor/enroll"), UserRole::Viewer);
+        assert_eq!(required_role(&Method::POST, "/admin/api-tokens/revoke"), UserRole::Viewer);
+        assert_eq!(required_role(&Method::POST, "/admin/sessions/revoke-others"), UserRole::Viewer);
+    }
+
+    #[test]
+    fn managing_users_needs_an_owner() {
+        assert_eq!(required_role(&Method::GET, "/admin/users"), UserRole::Owner);
+        assert_eq!(required_role(&Method::POST, "/admin/users/deactivate"), UserRole::Owner);
+        assert_eq!(required_role(&Method::GET, "/admin/usersettings"), UserRole::Viewer);
+        assert_eq!(required_role(&Method::GET, "/admin/audit-log"), UserRole::Owner);
+    }
+
+    #[test]
+    fn sessions_from_before_a_revocation_are_revoked() {
+        let now = Utc::now();
+        assert!(!is_revoked(Some(now), None));
+        assert!(!is_revoked(None, None));
+        assert!(is_revoked(Some(now - Duration::minutes(1)), Some(now)));
+        assert!(is_revoked(None, Some(now)));
+        assert!(!is_revoked(Some(now + Duration::minutes(1)), Some(now)));
+    }
+}
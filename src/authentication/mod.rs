@@ -0,0 +1,14 @@
+mod api_token;
+mod csrf;
+mod middleware;
+mod password;
+pub mod totp;
+mod two_factor;
+mod user_session;
+
+pub use api_token::*;
+pub use csrf::*;
+pub use middleware::*;
+pub use password::*;
+pub use two_factor::*;
+pub use user_session::*;
@@ -0,0 +1,260 @@
+use secrecy::{Secret, ExposeSecret};
+use crate::startup::DbConnectionKind;
+use uuid::Uuid;
+use crate::telemetry::spawn_blocking_with_tracinig;
+use crate::authentication::revoke_other_user_sessions;
+use anyhow::Context;
+use argon2::{PasswordHash, Argon2, PasswordVerifier, Algorithm, Version, Params, PasswordHasher};
+use argon2::password_hash::SaltString;
+
+use crate::configuration::PasswordHashingSettings;
+
+#[derive(thiserror::Error, Debug)]
+pub enum AuthError {
+    #[error("Invalid Credentials")]
+    InvalidCredentials(#[source] anyhow::Error),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error)
+}
+
+// The Argon2 parameters that new password hashes are computed with. Hashes made
+// with other parameters are upgraded the next time their password is entered.
+#[derive(Clone, Debug)]
+pub struct PasswordHashing {
+    params: Params,
+}
+
+impl PasswordHashing {
+    pub fn new(settings: &PasswordHashingSettings) -> Result<Self, anyhow::Error> {
+        let params = Params::new(settings.memory_kib, settings.iterations, settings.parallelism, None)
+            .map_err(|e| anyhow::anyhow!("Invalid Argon2 parameters: {}", e))?;
+        Ok(Self { params })
+    }
+
+    fn hasher(&self) -> Argon2<'static> {
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, self.params.clone())
+    }
+
+    fn is_current(&self, hash: &PasswordHash) -> bool {
+        hash.algorithm == Algorithm::Argon2id.ident()
+            && hash.version == Some(Version::V0x13.into())
+            && Params::try_from(hash).map_or(false, |params| {
+                params.m_cost() == self.params.m_cost()
+                    && params.t_cost() == self.params.t_cost()
+                    && params.p_cost() == self.params.p_cost()
+            })
+    }
+
+    // Checked against when the username is unknown, so that the answer takes as long
+    // as for a real user. Verifying costs whatever the parameters in the hash say.
+    fn dummy_hash(&self) -> Secret<String> {
+        Secret::new(format!(
+            "$argon2id$v=19$m={},t={},p={}$\
+            gZiV/M1gPc22ElAH/Jh1Hw$\
+            CWOrkoo7oJBQ/iyh7uJ0LO2aLEfrHwTWllSAxT0zRno",
+            self.params.m_cost(),
+            self.params.t_cost(),
+            self.params.p_cost()
+        ))
+    }
+}
+
+pub struct Credentials {
+    pub username: String,
+    pub password: Secret<String>,
+}
+
+// A correct password whose stored hash predates the current parameters is rehashed
+// and saved, so that raising the cost eventually covers every active user.
+#[tracing::instrument(name = "Validate credentials", skip(credentials, hashing, database))]
+pub async fn validate_credentials(
+    credentials: Credentials,
+    hashing: &PasswordHashing,
+    database: &DbConnectionKind,
+) -> Result<Uuid, AuthError> {
+    let mut user_id = None;
+    let mut expected_password_hash = hashing.dummy_hash();
+    if let Some((stored_user_id, stored_password_hash)) = get_user_credentials(credentials.username.as_str(), database)
+        .await
+        .map_err(AuthError::UnexpectedError)?
+    {
+        user_id = Some(stored_user_id);
+        expected_password_hash = stored_password_hash;
+    }
+
+    let stored_password_hash = expected_password_hash.clone();
+    let blocking_hashing = hashing.clone();
+    let upgraded_password_hash = spawn_blocking_with_tracinig(move || {
+        verify_password_hash(expected_password_hash, credentials.password, &blocking_hashing)
+    })
+        .await
+        .context("Failed to spawn block password hashing")
+        .map_err(AuthError::UnexpectedError)??;
+
+    let user_id = user_id.ok_or_else(|| AuthError::InvalidCredentials(anyhow::anyhow!("Unknown username")))?;
+    if let Some(upgraded_password_hash) = upgraded_password_hash {
+        // The login goes ahead either way: the old hash still works.
+        if let Err(e) = upgrade_password_hash(user_id, &stored_password_hash, &upgraded_password_hash, database).await {
+            tracing::warn!(error.cause_chain = ?e, "Failed to upgrade a password hash");
+        }
+    }
+    Ok(user_id)
+}
+
+#[tracing::instrument(name = "Get stored credentials", skip(username, database))]
+async fn get_user_credentials(
+    username: &str,
+    database: &DbConnectionKind,
+) -> Result<Option<(uuid::Uuid, Secret<String>)>, anyhow::Error> {
+    let user = sqlx::query!(
+       r#"
+       SELECT user_id, password_hash
+       FROM users
+       WHERE username = $1 AND active
+       "#,
+       username,
+   ).fetch_optional(database)
+        .await
+        .context("Could not find user with  provided credentials")?
+        .map(|u| (u.user_id, Secret::new(u.password_hash)));
+    Ok(user)
+}
+
+// Returns a new hash of the password if the expected one was computed with other
+// parameters than the current ones.
+#[tracing::instrument(name = "Verify password hash", skip(expected_password_hash, password_candidate, hashing))]
+fn verify_password_hash(
+    expected_password_hash: Secret<String>,
+    password_candidate: Secret<String>,
+    hashing: &PasswordHashing,
+) -> Result<Option<Secret<String>>, AuthError> {
+    let expected_password_hash = PasswordHash::new(expected_password_hash.expose_secret())
+        .context("Failed to parse hash in PHC string format")
+        .map_err(AuthError::UnexpectedError)?;
+
+    // The parameters are taken from the expected hash, not from the hasher.
+    Argon2::default()
+        .verify_password(password_candidate.expose_secret().as_bytes(),
+                         &expected_password_hash)
+        .context("Invalid password")
+        .map_err(AuthError::InvalidCredentials)?;
+
+    if hashing.is_current(&expected_password_hash) {
+        return Ok(None);
+    }
+    compute_password_hash(password_candidate, hashing)
+        .map(Some)
+        .map_err(AuthError::UnexpectedError)
+}
+
+// Only replaces the hash that was verified, so that a password changed in the
+// meantime is not overwritten with the old one.
+#[tracing::instrument(name = "Upgrade password hash", skip(old_password_hash, new_password_hash, database))]
+async fn upgrade_password_hash(
+    user_id: Uuid,
+    old_password_hash: &Secret<String>,
+    new_password_hash: &Secret<String>,
+    database: &DbConnectionKind,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        "UPDATE users SET password_hash = $1 WHERE user_id = $2 AND password_hash = $3",
+        new_password_hash.expose_secret(),
+        user_id,
+        old_password_hash.expose_secret()
+    )
+        .execute(database)
+        .await
+        .context("Failed to store the upgraded password hash")?;
+    Ok(())
+}
+
+// Whoever else knew the old password should not stay logged in with it, so every
+// session but `current_session` is revoked in the same transaction: the password
+// is never changed while the other sessions live on. Returns how many were revoked.
+#[tracing::instrument(name = "Change password", skip(password, hashing, database))]
+pub async fn change_password(
+    user_id: uuid::Uuid,
+    password: Secret<String>,
+    current_session: Uuid,
+    hashing: &PasswordHashing,
+    database: &DbConnectionKind
+) -> Result<u64, anyhow::Error> {
+    let hashing = hashing.clone();
+    let password_hash = spawn_blocking_with_tracinig(move || compute_password_hash(password, &hashing))
+        .await?
+        .context("Failed to hash password")?;
+
+    let mut transaction = database
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    sqlx::query!(
+        r#"
+        UPDATE users
+        SET password_hash = $1
+        WHERE user_id = $2
+        "#,
+        password_hash.expose_secret(),
+        user_id
+    ).execute(&mut transaction)
+        .await
+        .context("Failed to change user's password in db")?;
+    let revoked = revoke_other_user_sessions(user_id, current_session, &mut transaction).await?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the password change")?;
+    Ok(revoked)
+}
+
+pub fn compute_password_hash(
+    password: Secret<String>,
+    hashing: &PasswordHashing,
+) -> Result<Secret<String>, anyhow::Error> {
+    let salt = SaltString::generate(&mut rand::thread_rng());
+    let password_hash = hashing
+        .hasher()
+        .hash_password(password.expose_secret().as_bytes(), &salt)?
+        .to_string();
+    Ok(Secret::new(password_hash))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PasswordHashing;
+    use crate::configuration::PasswordHashingSettings;
+    use argon2::PasswordHash;
+    use secrecy::{ExposeSecret, Secret};
+
+    fn hashing(memory_kib: u32) -> PasswordHashing {
+        PasswordHashing::new(&PasswordHashingSettings {
+            memory_kib,
+            iterations: 1,
+            parallelism: 1,
+        })
+            .unwrap()
+    }
+
+    #[test]
+    fn hashes_with_the_current_parameters_are_kept() {
+        let hashing = hashing(1024);
+        let hash = super::compute_password_hash(Secret::new("password".to_string()), &hashing).unwrap();
+        assert!(hashing.is_current(&PasswordHash::new(hash.expose_secret()).unwrap()));
+    }
+
+    #[test]
+    fn hashes_with_other_parameters_are_upgraded() {
+        let hash = super::compute_password_hash(Secret::new("password".to_string()), &hashing(1024)).unwrap();
+        let upgraded = super::verify_password_hash(hash, Secret::new("password".to_string()), &hashing(2048))
+            .unwrap()
+            .expect("The hash was not upgraded");
+        assert!(upgraded.expose_secret().contains("m=2048"));
+    }
+
+    #[test]
+    fn the_dummy_hash_costs_as_much_as_a_real_one() {
+        let hashing = hashing(1024);
+        assert!(hashing.is_current(&PasswordHash::new(hashing.dummy_hash().expose_secret()).unwrap()));
+    }
+}
+
@@ -0,0 +1,112 @@
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+
+use crate::utils::constant_time_eq;
+
+// RFC 6238 with the parameters every authenticator app supports.
+const STEP_SECONDS: i64 = 30;
+const DIGITS: usize = 6;
+const SECRET_BYTES: usize = 20;
+// Codes from the neighbouring time steps are accepted to allow for clock drift.
+const ALLOWED_DRIFT_STEPS: i64 = 1;
+const ALPHABET: base32::Alphabet = base32::Alphabet::RFC4648 { padding: false };
+
+pub fn generate_secret() -> String {
+    let mut secret = [0u8; SECRET_BYTES];
+    rand::thread_rng().fill_bytes(&mut secret);
+    base32::encode(ALPHABET, &secret)
+}
+
+// The URI authenticator apps read from the QR code.
+pub fn provisioning_uri(secret: &str, issuer: &str, account: &str) -> String {
+    format!(
+        "otpauth://totp/{issuer}:{account}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={digits}&period={period}",
+        issuer = urlencoding::encode(issuer),
+        account = urlencoding::encode(account),
+        secret = secret,
+        digits = DIGITS,
+        period = STEP_SECONDS,
+    )
+}
+
+pub fn time_step(unix_time: i64) -> i64 {
+    unix_time.div_euclid(STEP_SECONDS)
+}
+
+// The code an authenticator app shows at `unix_time`, or `None` if the secret is
+// not valid base32.
+pub fn code_at(secret: &str, unix_time: i64) -> Option<String> {
+    let secret = base32::decode(ALPHABET, secret)?;
+    Some(hotp(&secret, time_step(unix_time)))
+}
+
+// Returns the time step the code belongs to, so that callers can refuse to accept
+// the same code twice.
+pub fn verify(secret: &str, code: &str, unix_time: i64) -> Option<i64> {
+    let secret = base32::decode(ALPHABET, secret)?;
+    let code: String = code.chars().filter(|c| !c.is_whitespace()).collect();
+    if code.len() != DIGITS {
+        return None;
+    }
+    let now = time_step(unix_time);
+    (now - ALLOWED_DRIFT_STEPS..=now + ALLOWED_DRIFT_STEPS)
+        .find(|step| constant_time_eq(hotp(&secret, *step).as_bytes(), code.as_bytes()))
+}
+
+// RFC 4226, with the time step as the counter.
+fn hotp(secret: &[u8], counter: i64) -> String {
+    let mut mac = Hmac::<sha1::Sha1>::new_from_slice(secret)
+        .expect("HMAC accepts keys of any length");
+    mac.update(&counter.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let truncated = u32::from_be_bytes([hash[offset], hash[offset + 1], hash[offset + 2], hash[offset + 3]])
+        & 0x7fff_ffff;
+    format!("{:0width$}", truncated % 10u32.pow(DIGITS as u32), width = DIGITS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{code_at, generate_secret, hotp, verify, ALPHABET};
+
+    // The SHA1 test vectors from RFC 6238, truncated to six digits.
+    #[test]
+    fn codes_match_the_rfc_test_vectors() {
+        let secret = b"12345678901234567890";
+        assert_eq!(hotp(secret, 59 / 30), "287082");
+        assert_eq!(hotp(secret, 1111111109 / 30), "081804");
+        assert_eq!(hotp(secret, 1234567890 / 30), "005924");
+        assert_eq!(hotp(secret, 20000000000 / 30), "353130");
+    }
+
+    #[test]
+    fn codes_from_neighbouring_steps_are_accepted() {
+        let secret = generate_secret();
+        let now = 1_666_000_000;
+        for drift in [-30, 0, 30] {
+            let code = code_at(&secret, now + drift).unwrap();
+            assert!(verify(&secret, &code, now).is_some());
+        }
+    }
+
+    #[test]
+    fn old_codes_are_rejected() {
+        let secret = generate_secret();
+        let now = 1_666_000_000;
+        let code = code_at(&secret, now - 90).unwrap();
+        assert!(verify(&secret, &code, now).is_none());
+    }
+
+    #[test]
+    fn malformed_codes_are_rejected() {
+        let secret = generate_secret();
+        assert!(verify(&secret, "", 0).is_none());
+        assert!(verify(&secret, "12345", 0).is_none());
+        assert!(verify(&secret, "abcdef", 0).is_none());
+    }
+
+    #[test]
+    fn generated_secrets_are_valid_base32() {
+        assert_eq!(base32::decode(ALPHABET, &generate_secret()).unwrap().len(), 20);
+    }
+}
@@ -0,0 +1,254 @@
+use anyhow::Context;
+use chrono::Utc;
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::authentication::totp;
+use crate::startup::DbConnectionKind;
+
+const RECOVERY_CODE_COUNT: usize = 10;
+// Without look-alike characters, since recovery codes are often copied from paper.
+const RECOVERY_CODE_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+
+pub enum TwoFactorStatus {
+    Disabled,
+    // The user has been shown a secret but has not entered a code from it yet.
+    Pending { secret: String },
+    Enabled { remaining_recovery_codes: i64 },
+}
+
+#[tracing::instrument(name = "Get two-factor status", skip(database))]
+pub async fn get_two_factor_status(
+    user_id: Uuid,
+    database: &DbConnectionKind,
+) -> Result<TwoFactorStatus, anyhow::Error> {
+    let row = sqlx::query!(
+        "SELECT secret, confirmed_at FROM user_totp WHERE user_id = $1",
+        user_id
+    )
+        .fetch_optional(database)
+        .await
+        .context("Failed to retrieve the two-factor settings")?;
+    match row {
+        None => Ok(TwoFactorStatus::Disabled),
+        Some(row) if row.confirmed_at.is_none() => Ok(TwoFactorStatus::Pending { secret: row.secret }),
+        Some(_) => {
+            let remaining_recovery_codes = sqlx::query!(
+                r#"SELECT count(*) AS "count!" FROM user_recovery_codes WHERE user_id = $1 AND used_at IS NULL"#,
+                user_id
+            )
+                .fetch_one(database)
+                .await
+                .context("Failed to count the unused recovery codes")?
+                .count;
+            Ok(TwoFactorStatus::Enabled { remaining_recovery_codes })
+        }
+    }
+}
+
+pub async fn has_two_factor(user_id: Uuid, database: &DbConnectionKind) -> Result<bool, anyhow::Error> {
+    Ok(matches!(get_two_factor_status(user_id, database).await?, TwoFactorStatus::Enabled { .. }))
+}
+
+// Stores a new secret for the user to scan. An enabled second factor is left alone:
+// it has to be disabled first.
+#[tracing::instrument(name = "Start two-factor enrollment", skip(database))]
+pub async fn start_two_factor_enrollment(
+    user_id: Uuid,
+    database: &DbConnectionKind,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO user_totp (user_id, secret)
+        VALUES ($1, $2)
+        ON CONFLICT (user_id) DO UPDATE SET secret = EXCLUDED.secret
+        WHERE user_totp.confirmed_at IS NULL
+        "#,
+        user_id,
+        totp::generate_secret()
+    )
+        .execute(database)
+        .await
+        .context("Failed to store the two-factor secret")?;
+    Ok(())
+}
+
+// Enables the pending second factor if the code matches it, and returns the
+// recovery codes to show to the user. They are only stored hashed.
+#[tracing::instrument(name = "Confirm two-factor enrollment", skip(code, database))]
+pub async fn confirm_two_factor_enrollment(
+    user_id: Uuid,
+    code: &str,
+    database: &DbConnectionKind,
+) -> Result<Option<Vec<String>>, anyhow::Error> {
+    let mut transaction = database
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let pending = sqlx::query!(
+        "SELECT secret FROM user_totp WHERE user_id = $1 AND confirmed_at IS NULL FOR UPDATE",
+        user_id
+    )
+        .fetch_optional(&mut transaction)
+        .await
+        .context("Failed to retrieve the pending two-factor secret")?;
+    let step = match pending.and_then(|row| totp::verify(&row.secret, code, Utc::now().timestamp())) {
+        Some(step) => step,
+        None => return Ok(None),
+    };
+    sqlx::query!(
+        "UPDATE user_totp SET confirmed_at = $2, last_used_step = $3 WHERE user_id = $1",
+        user_id,
+        Utc::now(),
+        step
+    )
+        .execute(&mut transaction)
+        .await
+        .context("Failed to enable two-factor authentication")?;
+
+    let recovery_codes: Vec<String> = (0..RECOVERY_CODE_COUNT).map(|_| generate_recovery_code()).collect();
+    let code_hashes: Vec<String> = recovery_codes.iter().map(|code| hash_recovery_code(code)).collect();
+    sqlx::query!("DELETE FROM user_recovery_codes WHERE user_id = $1", user_id)
+        .execute(&mut transaction)
+        .await
+        .context("Failed to delete the old recovery codes")?;
+    sqlx::query!(
+        r#"
+        INSERT INTO user_recovery_codes (user_id, code_hash)
+        SELECT $1, code_hash FROM unnest($2::text[]) AS code_hash
+        "#,
+        user_id,
+        &code_hashes
+    )
+        .execute(&mut transaction)
+        .await
+        .context("Failed to store the recovery codes")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the two-factor enrollment")?;
+    Ok(Some(recovery_codes))
+}
+
+// Accepts either a code from the authenticator app or an unused recovery code.
+// Each of them works only once, so an intercepted code cannot be replayed.
+#[tracing::instrument(name = "Verify second factor", skip(code, database))]
+pub async fn verify_second_factor(
+    user_id: Uuid,
+    code: &str,
+    database: &DbConnectionKind,
+) -> Result<bool, anyhow::Error> {
+    let mut transaction = database
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let row = sqlx::query!(
+        r#"
+        SELECT secret, last_used_step FROM user_totp
+        WHERE user_id = $1 AND confirmed_at IS NOT NULL
+        FOR UPDATE
+        "#,
+        user_id
+    )
+        .fetch_optional(&mut transaction)
+        .await
+        .context("Failed to retrieve the two-factor secret")?;
+    let row = match row {
+        Some(row) => row,
+        None => return Ok(false),
+    };
+
+    let accepted = match totp::verify(&row.secret, code, Utc::now().timestamp()) {
+        Some(step) if row.last_used_step.map_or(true, |last_used| step > last_used) => {
+            sqlx::query!(
+                "UPDATE user_totp SET last_used_step = $2 WHERE user_id = $1",
+                user_id,
+                step
+            )
+                .execute(&mut transaction)
+                .await
+                .context("Failed to record the used two-factor code")?;
+            true
+        }
+        Some(_) => false,
+        None => sqlx::query!(
+            r#"
+            UPDATE user_recovery_codes SET used_at = $3
+            WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL
+            "#,
+            user_id,
+            hash_recovery_code(code),
+            Utc::now()
+        )
+            .execute(&mut transaction)
+            .await
+            .context("Failed to use a recovery code")?
+            .rows_affected() == 1,
+    };
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the second factor verification")?;
+    Ok(accepted)
+}
+
+#[tracing::instrument(name = "Disable two-factor authentication", skip(database))]
+pub async fn disable_two_factor(
+    user_id: Uuid,
+    database: &DbConnectionKind,
+) -> Result<(), anyhow::Error> {
+    let mut transaction = database
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    sqlx::query!("DELETE FROM user_recovery_codes WHERE user_id = $1", user_id)
+        .execute(&mut transaction)
+        .await
+        .context("Failed to delete the recovery codes")?;
+    sqlx::query!("DELETE FROM user_totp WHERE user_id = $1", user_id)
+        .execute(&mut transaction)
+        .await
+        .context("Failed to delete the two-factor secret")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit disabling two-factor authentication")?;
+    Ok(())
+}
+
+fn generate_recovery_code() -> String {
+    let mut rng = rand::thread_rng();
+    let mut code: String = (0..10)
+        .map(|_| RECOVERY_CODE_ALPHABET[rng.gen_range(0..RECOVERY_CODE_ALPHABET.len())] as char)
+        .collect();
+    code.insert(5, '-');
+    code
+}
+
+// Case and dashes are ignored, so that codes can be typed however they were written down.
+fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    hex::encode(Sha256::digest(normalized.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate_recovery_code, hash_recovery_code};
+
+    #[test]
+    fn recovery_codes_match_regardless_of_case_and_dashes() {
+        let code = generate_recovery_code();
+        assert_eq!(hash_recovery_code(&code), hash_recovery_code(&code.to_uppercase().replace('-', "")));
+        assert_eq!(hash_recovery_code(&code), hash_recovery_code(&format!(" {} ", code)));
+    }
+
+    #[test]
+    fn recovery_codes_are_different() {
+        assert_ne!(generate_recovery_code(), generate_recovery_code());
+    }
+}
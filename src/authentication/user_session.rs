@@ -0,0 +1,133 @@
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::postgres::PgExecutor;
+use uuid::Uuid;
+
+use crate::audit_log::RequestOrigin;
+use crate::startup::DbConnectionKind;
+
+// The session data itself lives in Redis, which cannot be searched by user. Every
+// login is therefore also recorded here, so that users can see where they are
+// logged in and end sessions other than their own.
+pub struct UserSession {
+    pub id: Uuid,
+    pub client_ip: String,
+    pub user_agent: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+}
+
+// Returns the id to store in the new session.
+#[tracing::instrument(name = "Start user session", skip(origin, database))]
+pub async fn start_user_session(
+    user_id: Uuid,
+    origin: &RequestOrigin,
+    database: &DbConnectionKind,
+) -> Result<Uuid, anyhow::Error> {
+    let session_id = Uuid::new_v4();
+    let now = Utc::now();
+    sqlx::query!(
+        r#"
+        INSERT INTO user_sessions (id, user_id, client_ip, user_agent, created_at, last_seen_at)
+        VALUES ($1, $2, $3, $4, $5, $5)
+        "#,
+        session_id,
+        user_id,
+        origin.client_ip,
+        origin.user_agent,
+        now
+    )
+        .execute(database)
+        .await
+        .context("Failed to record the session")?;
+    Ok(session_id)
+}
+
+// Records that the session is in use. Returns `false` if it has been revoked, or
+// belongs to someone else.
+#[tracing::instrument(name = "Touch user session", skip(database))]
+pub async fn touch_user_session(
+    session_id: Uuid,
+    user_id: Uuid,
+    client_ip: &str,
+    database: &DbConnectionKind,
+) -> Result<bool, anyhow::Error> {
+    let touched = sqlx::query!(
+        r#"
+        UPDATE user_sessions SET last_seen_at = $3, client_ip = $4
+        WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL
+        "#,
+        session_id,
+        user_id,
+        Utc::now(),
+        client_ip
+    )
+        .execute(database)
+        .await
+        .context("Failed to update the session")?
+        .rows_affected();
+    Ok(touched == 1)
+}
+
+#[tracing::instrument(name = "List user sessions", skip(database))]
+pub async fn list_user_sessions(
+    user_id: Uuid,
+    database: &DbConnectionKind,
+) -> Result<Vec<UserSession>, anyhow::Error> {
+    let sessions = sqlx::query_as!(
+        UserSession,
+        r#"
+        SELECT id, client_ip, user_agent, created_at, last_seen_at
+        FROM user_sessions
+        WHERE user_id = $1 AND revoked_at IS NULL
+        ORDER BY last_seen_at DESC
+        "#,
+        user_id
+    )
+        .fetch_all(database)
+        .await
+        .context("Failed to retrieve the sessions")?;
+    Ok(sessions)
+}
+
+// Users can only revoke their own sessions. Returns `false` if there was no such
+// session, or it had already ended.
+#[tracing::instrument(name = "Revoke user session", skip(database))]
+pub async fn revoke_user_session(
+    user_id: Uuid,
+    session_id: Uuid,
+    database: &DbConnectionKind,
+) -> Result<bool, anyhow::Error> {
+    let revoked = sqlx::query!(
+        "UPDATE user_sessions SET revoked_at = $3 WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL",
+        session_id,
+        user_id,
+        Utc::now()
+    )
+        .execute(database)
+        .await
+        .context("Failed to revoke the session")?
+        .rows_affected();
+    Ok(revoked == 1)
+}
+
+// Revokes every session of the user except `current`. Returns how many there were.
+// Takes any executor so that a password change can do it in its own transaction.
+#[tracing::instrument(name = "Revoke other user sessions", skip(executor))]
+pub async fn revoke_other_user_sessions(
+    user_id: Uuid,
+    current: Uuid,
+    executor: impl PgExecutor<'_>,
+) -> Result<u64, anyhow::Error> {
+    let revoked = sqlx::query!(
+        "UPDATE user_sessions SET revoked_at = $3 WHERE user_id = $1 AND id <> $2 AND revoked_at IS NULL",
+        user_id,
+        current,
+        Utc::now()
+    )
+        .execute(executor)
+        .await
+        .context("Failed to revoke the sessions")?
+        .rows_affected();
+    Ok(revoked)
+}
@@ -4,6 +4,7 @@ use sqlx::postgres::{PgConnectOptions, PgSslMode};
 use serde_aux::field_attributes::deserialize_number_from_string;
 use sqlx::ConnectOptions;
 use crate::domain::subscriber_email::SubscriberEmail;
+use crate::email_client::EmailClient;
 
 pub enum Environment {
     Local,
@@ -36,7 +37,157 @@ pub struct Settings {
     pub database: DatabaseSettings,
     pub application: ApplicationSettings,
     pub email_client: EmailClientSettings,
-    pub redis_uri: Secret<String>
+    pub redis_uri: Secret<String>,
+    pub subscription_protection: SubscriptionProtectionSettings,
+    pub email_domain_policy: EmailDomainPolicySettings,
+    pub pending_subscription_cleanup: PendingSubscriptionCleanupSettings,
+    pub welcome_sequence: WelcomeSequenceSettings,
+    pub login_protection: LoginProtectionSettings,
+    pub password_reset_protection: PasswordResetProtectionSettings,
+    pub subscriber_data_protection: SubscriberDataProtectionSettings,
+    pub password_hashing: PasswordHashingSettings,
+    pub password_policy: PasswordPolicySettings,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct EmailDomainPolicySettings {
+    pub blocklist_path: String,
+    pub allowlist_path: Option<String>,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct SubscriptionProtectionSettings {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub minimum_form_fill_seconds: u64,
+    pub ip_rate_limit: RateLimitSettings,
+    pub email_domain_rate_limit: RateLimitSettings,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct RateLimitSettings {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_requests: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub window_seconds: u64,
+}
+
+impl RateLimitSettings {
+    pub fn window(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.window_seconds)
+    }
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct PasswordResetProtectionSettings {
+    // Requests beyond either limit are answered as usual, but no email is sent.
+    pub email_rate_limit: RateLimitSettings,
+    pub ip_rate_limit: RateLimitSettings,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct SubscriberDataProtectionSettings {
+    // Requests beyond either limit are answered as usual, but no email is sent.
+    pub email_rate_limit: RateLimitSettings,
+    pub ip_rate_limit: RateLimitSettings,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct LoginProtectionSettings {
+    // Once either limit is used up, logins for that username or from that IP are
+    // refused until the window ends.
+    pub username_failure_limit: RateLimitSettings,
+    pub ip_failure_limit: RateLimitSettings,
+    // Failures beyond this many are answered more slowly, doubling the delay each time.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub free_failures: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub failure_delay_milliseconds: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_failure_delay_milliseconds: u64,
+}
+
+impl LoginProtectionSettings {
+    pub fn failure_delay(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.failure_delay_milliseconds)
+    }
+
+    pub fn max_failure_delay(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.max_failure_delay_milliseconds)
+    }
+}
+
+// The Argon2id cost of new password hashes. Raising it upgrades existing hashes
+// as their users log in.
+#[derive(serde::Deserialize, Clone)]
+pub struct PasswordHashingSettings {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub memory_kib: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub iterations: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub parallelism: u32,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct PasswordPolicySettings {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub minimum_length: usize,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub maximum_length: usize,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub minimum_strength_bits: f64,
+    // A file of SHA-1 hashes of known breached passwords, one per line, sorted.
+    pub breached_passwords_path: Option<String>,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct PendingSubscriptionCleanupSettings {
+    // Subscriptions still pending confirmation after this long are cleaned up.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub retention_hours: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub interval_seconds: u64,
+    pub action: CleanupAction,
+}
+
+impl PendingSubscriptionCleanupSettings {
+    pub fn retention(&self) -> chrono::Duration {
+        chrono::Duration::hours(self.retention_hours as i64)
+    }
+
+    pub fn interval(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.interval_seconds)
+    }
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct WelcomeSequenceSettings {
+    // How often to look for welcome emails that are due.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub interval_seconds: u64,
+}
+
+impl WelcomeSequenceSettings {
+    pub fn interval(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.interval_seconds)
+    }
+}
+
+#[derive(serde::Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CleanupAction {
+    Delete,
+    // Keeps a copy of each cleaned up subscription in `archived_pending_subscriptions`.
+    Archive,
+}
+
+impl CleanupAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CleanupAction::Delete => "delete",
+            CleanupAction::Archive => "archive",
+        }
+    }
 }
 
 #[derive(serde::Deserialize, Clone)]
@@ -55,6 +206,12 @@ impl EmailClientSettings {
     pub fn timeout(&self) -> std::time::Duration {
         std::time::Duration::from_millis(self.timeout_milliseconds)
     }
+
+    pub fn client(self) -> EmailClient {
+        let sender_email = self.sender().expect("Invalid email found in config");
+        let timeout = self.timeout();
+        EmailClient::new(self.base_url, sender_email, self.authorization_token, timeout)
+    }
 }
 
 #[derive(serde::Deserialize, Clone)]
@@ -74,7 +231,11 @@ pub struct ApplicationSettings {
     pub port: u16,
     pub host: String,
     pub base_url: String,
-    pub hmac_secret: Secret<String>
+    pub hmac_secret: Secret<String>,
+    // Reverse proxies whose `X-Forwarded-For` header is believed. Requests from
+    // anywhere else are identified by the address they connect from.
+    #[serde(default)]
+    pub trusted_proxies: Vec<std::net::IpAddr>,
 }
 
 impl DatabaseSettings {
@@ -0,0 +1,108 @@
+use actix_web::http::Method;
+
+// What an API token may be used for. Tokens act on behalf of the user who created
+// them, so a scope never grants more than that user's role allows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ApiScope {
+    PublishNewsletters,
+    ReadSubscribers,
+    ManageSubscribers,
+}
+
+impl ApiScope {
+    pub const ALL: [ApiScope; 3] = [Self::PublishNewsletters, Self::ReadSubscribers, Self::ManageSubscribers];
+
+    pub fn parse(s: &str) -> Result<Self, String> {
+        Self::ALL
+            .into_iter()
+            .find(|scope| scope.as_str() == s)
+            .ok_or_else(|| format!("{} is not an API token scope", s))
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::PublishNewsletters => "publish_newsletters",
+            Self::ReadSubscribers => "read_subscribers",
+            Self::ManageSubscribers => "manage_subscribers",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::PublishNewsletters => "Publish newsletter issues",
+            Self::ReadSubscribers => "List and export subscribers",
+            Self::ManageSubscribers => "Confirm, unsubscribe, delete and import subscribers",
+        }
+    }
+
+    // The scope an API token needs for an admin route, or `None` for routes that
+    // are only available to people logged in with a browser: passwords, users,
+    // tokens and everything else that is not listed here.
+    pub fn required_for(method: &Method, path: &str) -> Option<ApiScope> {
+        match (method.clone(), path) {
+            (Method::POST, "/admin/newsletter") => Some(Self::PublishNewsletters),
+            (Method::GET | Method::HEAD, "/admin/subscribers" | "/admin/subscribers/export") => {
+                Some(Self::ReadSubscribers)
+            }
+            (
+                Method::POST,
+                "/admin/subscribers/confirm"
+                | "/admin/subscribers/resend"
+                | "/admin/subscribers/unsubscribe"
+                | "/admin/subscribers/delete"
+                | "/admin/subscribers/import",
+            ) => Some(Self::ManageSubscribers),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for ApiScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ApiScope;
+    use actix_web::http::Method;
+    use claim::{assert_err, assert_ok_eq};
+
+    #[test]
+    fn every_scope_round_trips_through_its_string_form() {
+        for scope in ApiScope::ALL {
+            assert_ok_eq!(ApiScope::parse(scope.as_str()), scope);
+        }
+    }
+
+    #[test]
+    fn unknown_scopes_are_rejected() {
+        assert_err!(ApiScope::parse("everything"));
+    }
+
+    #[test]
+    fn publishing_needs_the_publish_scope() {
+        assert_eq!(ApiScope::required_for(&Method::POST, "/admin/newsletter"), Some(ApiScope::PublishNewsletters));
+        assert_eq!(ApiScope::required_for(&Method::GET, "/admin/newsletter"), None);
+    }
+
+    #[test]
+    fn reading_and_changing_subscribers_need_different_scopes() {
+        assert_eq!(ApiScope::required_for(&Method::GET, "/admin/subscribers/export"), Some(ApiScope::ReadSubscribers));
+        assert_eq!(ApiScope::required_for(&Method::POST, "/admin/subscribers/delete"), Some(ApiScope::ManageSubscribers));
+    }
+
+    #[test]
+    fn account_management_is_never_available_to_tokens() {
+        for (method, path) in [
+            (Method::POST, "/admin/password"),
+            (Method::POST, "/admin/api-tokens"),
+            (Method::POST, "/admin/users/invite"),
+            (Method::POST, "/admin/two-factor/disable"),
+            (Method::GET, "/admin/audit-log"),
+        ] {
+            assert_eq!(ApiScope::required_for(&method, path), None, "{} {}", method, path);
+        }
+    }
+}
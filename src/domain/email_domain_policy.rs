@@ -0,0 +1,174 @@
+use anyhow::Context;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::SystemTime;
+
+use crate::configuration::EmailDomainPolicySettings;
+use crate::domain::subscriber_email::SubscriberEmail;
+
+// Blocked and allowed email domains, read from plain text files with one domain per
+// line. The files are re-read whenever they change on disk, so the lists can be
+// updated without a redeploy.
+pub struct EmailDomainPolicy {
+    blocklist_path: PathBuf,
+    allowlist_path: Option<PathBuf>,
+    state: RwLock<PolicyState>,
+}
+
+struct PolicyState {
+    lists: DomainLists,
+    versions: Vec<Option<FileVersion>>,
+}
+
+type FileVersion = (SystemTime, u64);
+
+impl EmailDomainPolicy {
+    pub fn load(settings: &EmailDomainPolicySettings) -> Result<Self, anyhow::Error> {
+        let policy = Self {
+            blocklist_path: PathBuf::from(&settings.blocklist_path),
+            allowlist_path: settings.allowlist_path.as_ref().map(PathBuf::from),
+            state: RwLock::new(PolicyState {
+                lists: DomainLists::default(),
+                versions: vec![],
+            }),
+        };
+        let lists = policy.read_lists()?;
+        *policy.state.write().unwrap() = PolicyState {
+            lists,
+            versions: policy.file_versions(),
+        };
+        Ok(policy)
+    }
+
+    pub fn check(&self, email: &SubscriberEmail) -> Result<(), String> {
+        self.reload_if_changed();
+        let domain = email.domain();
+        if self.state.read().unwrap().lists.is_blocked(domain) {
+            Err(format!(
+                "Sign-ups from {} are not accepted, please use a different email address",
+                domain
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn reload_if_changed(&self) {
+        let versions = self.file_versions();
+        if self.state.read().unwrap().versions == versions {
+            return;
+        }
+        match self.read_lists() {
+            Ok(lists) => {
+                *self.state.write().unwrap() = PolicyState { lists, versions };
+                tracing::info!("Reloaded email domain blocklist");
+            }
+            Err(e) => {
+                // Keep enforcing the lists we already have rather than letting everything through
+                tracing::error!(error.cause_chain = ?e, "Failed to reload email domain blocklist");
+            }
+        }
+    }
+
+    fn file_versions(&self) -> Vec<Option<FileVersion>> {
+        std::iter::once(self.blocklist_path.as_path())
+            .chain(self.allowlist_path.as_deref())
+            .map(|path| {
+                let metadata = std::fs::metadata(path).ok()?;
+                Some((metadata.modified().ok()?, metadata.len()))
+            })
+            .collect()
+    }
+
+    fn read_lists(&self) -> Result<DomainLists, anyhow::Error> {
+        let blocked = read_domain_file(&self.blocklist_path)?;
+        let allowed = match &self.allowlist_path {
+            Some(path) => read_domain_file(path)?,
+            None => HashSet::new(),
+        };
+        Ok(DomainLists { blocked, allowed })
+    }
+}
+
+fn read_domain_file(path: &Path) -> Result<HashSet<String>, anyhow::Error> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read email domain list at {}", path.display()))?;
+    Ok(parse_domain_list(&contents))
+}
+
+// One domain per line; blank lines and lines starting with `#` are ignored.
+fn parse_domain_list(contents: &str) -> HashSet<String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_lowercase)
+        .collect()
+}
+
+#[derive(Default)]
+struct DomainLists {
+    blocked: HashSet<String>,
+    allowed: HashSet<String>,
+}
+
+impl DomainLists {
+    // A listed domain also covers its subdomains. The allowlist wins over the blocklist.
+    fn is_blocked(&self, domain: &str) -> bool {
+        let mut candidates = vec![domain];
+        let mut rest = domain;
+        while let Some((_, parent)) = rest.split_once('.') {
+            candidates.push(parent);
+            rest = parent;
+        }
+        if candidates.iter().any(|d| self.allowed.contains(*d)) {
+            return false;
+        }
+        candidates.iter().any(|d| self.blocked.contains(*d))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_domain_list, DomainLists};
+
+    fn lists(blocked: &str, allowed: &str) -> DomainLists {
+        DomainLists {
+            blocked: parse_domain_list(blocked),
+            allowed: parse_domain_list(allowed),
+        }
+    }
+
+    #[test]
+    fn comments_and_blank_lines_are_ignored() {
+        let domains = parse_domain_list("# disposable\n\nmailinator.com\n  Trashmail.com  \n");
+        assert_eq!(domains.len(), 2);
+        assert!(domains.contains("mailinator.com"));
+        assert!(domains.contains("trashmail.com"));
+    }
+
+    #[test]
+    fn a_listed_domain_is_blocked() {
+        assert!(lists("mailinator.com", "").is_blocked("mailinator.com"));
+    }
+
+    #[test]
+    fn subdomains_of_a_listed_domain_are_blocked() {
+        assert!(lists("mailinator.com", "").is_blocked("eu.mailinator.com"));
+    }
+
+    #[test]
+    fn unlisted_domains_are_not_blocked() {
+        let lists = lists("mailinator.com", "");
+        assert!(!lists.is_blocked("gmail.com"));
+        assert!(!lists.is_blocked("notmailinator.com"));
+    }
+
+    #[test]
+    fn the_allowlist_overrides_the_blocklist() {
+        let lists = lists("example.com", "partner.example.com");
+        assert!(!lists.is_blocked("partner.example.com"));
+        assert!(lists.is_blocked("other.example.com"));
+    }
+}
@@ -1,7 +1,22 @@
 pub mod subscriber_name;
 pub mod subscriber_email;
 pub mod new_subscriber;
+pub mod validation_error;
+pub mod email_domain_policy;
+pub mod subscriber_attributes;
+pub mod subscriber_import;
+pub mod subscription_status;
+pub mod user_role;
+pub mod api_scope;
+pub mod password_policy;
 
 pub use subscriber_name::SubscriberName;
 pub use new_subscriber::NewSubscriber;
-
+pub use validation_error::ValidationError;
+pub use email_domain_policy::EmailDomainPolicy;
+pub use subscriber_attributes::{AttributeKey, AttributeSchema, AttributeType, SubscriberAttributes};
+pub use subscriber_import::{parse_import, ImportRow, ImportedSubscriber};
+pub use subscription_status::SubscriptionStatus;
+pub use user_role::UserRole;
+pub use api_scope::ApiScope;
+pub use password_policy::PasswordPolicy;
@@ -1,19 +1,29 @@
-use crate::domain::SubscriberName;
+use crate::domain::{SubscriberName, ValidationError, EmailDomainPolicy, AttributeSchema, SubscriberAttributes};
 use crate::domain::subscriber_email::SubscriberEmail;
-use std::prelude::rust_2021::{TryFrom};
 use crate::FormData;
+use crate::i18n::Locale;
 
 pub struct NewSubscriber {
     pub email: SubscriberEmail,
-    pub name: SubscriberName
+    pub name: SubscriberName,
+    pub attributes: SubscriberAttributes,
+    pub locale: Locale,
 }
 
-impl TryFrom<FormData> for NewSubscriber {
-    type Error = String;
-
-    fn try_from(form: FormData) -> Result<NewSubscriber, String> {
-        let name = SubscriberName::parse(form.name)?;
-        let email = SubscriberEmail::parse(form.email)?;
-        Ok(NewSubscriber { name, email })
+impl NewSubscriber {
+    pub fn parse(
+        form: FormData,
+        domain_policy: &EmailDomainPolicy,
+        attribute_schema: &AttributeSchema,
+        locale: Locale,
+    ) -> Result<NewSubscriber, ValidationError> {
+        let name = SubscriberName::parse(form.name)
+            .map_err(|e| ValidationError::new("name", e))?;
+        let email = SubscriberEmail::parse(form.email)
+            .map_err(|e| ValidationError::new("email", e))?;
+        domain_policy.check(&email)
+            .map_err(|e| ValidationError::new("email", e))?;
+        let attributes = SubscriberAttributes::parse(form.attributes, attribute_schema)?;
+        Ok(NewSubscriber { name, email, attributes, locale })
     }
 }
@@ -0,0 +1,279 @@
+use anyhow::Context;
+use secrecy::{ExposeSecret, Secret};
+use sha1::{Digest, Sha1};
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use crate::configuration::PasswordPolicySettings;
+use crate::telemetry::spawn_blocking_with_tracinig;
+
+// The rules every new password has to follow, whether it is chosen when accepting
+// an invitation, after a reset or on the change password page.
+pub struct PasswordPolicy {
+    minimum_length: usize,
+    maximum_length: usize,
+    minimum_strength_bits: f64,
+    breached: BreachedPasswords,
+}
+
+impl PasswordPolicy {
+    pub fn load(settings: &PasswordPolicySettings) -> Result<Self, anyhow::Error> {
+        let breached = match &settings.breached_passwords_path {
+            Some(path) => BreachedPasswords::open(Path::new(path))?,
+            None => BreachedPasswords::default(),
+        };
+        Ok(Self {
+            minimum_length: settings.minimum_length,
+            maximum_length: settings.maximum_length,
+            minimum_strength_bits: settings.minimum_strength_bits,
+            breached,
+        })
+    }
+
+    pub async fn check(&self, password: &Secret<String>) -> Result<(), String> {
+        let length = password.expose_secret().chars().count();
+        if length < self.minimum_length || length > self.maximum_length {
+            return Err(format!(
+                "The password must be between {} and {} characters long",
+                self.minimum_length, self.maximum_length
+            ));
+        }
+        if self.breached.contains(password).await {
+            return Err("This password has appeared in a data breach. Please choose a different one.".into());
+        }
+        if estimate_strength_bits(password.expose_secret()) < self.minimum_strength_bits {
+            return Err("This password is too easy to guess. Try a longer one, or mix in other kinds of characters.".into());
+        }
+        Ok(())
+    }
+}
+
+// A rough estimate of how hard the password is to guess, in bits: the size of the
+// alphabet it draws from, counted once for every character that does not simply
+// repeat or continue the one before, so that `aaaaaaaaaaaa` and `123456789012`
+// score low however long they are.
+pub fn estimate_strength_bits(password: &str) -> f64 {
+    let (mut lower, mut upper, mut digit, mut symbol, mut other) = (false, false, false, false, false);
+    for c in password.chars() {
+        match c {
+            'a'..='z' => lower = true,
+            'A'..='Z' => upper = true,
+            '0'..='9' => digit = true,
+            c if c.is_ascii() => symbol = true,
+            _ => other = true,
+        }
+    }
+    let alphabet_size = [(lower, 26), (upper, 26), (digit, 10), (symbol, 33), (other, 100)]
+        .iter()
+        .filter(|(used, _)| *used)
+        .map(|(_, size)| size)
+        .sum::<u32>();
+
+    let mut previous: Option<char> = None;
+    let mut distinct_steps = 0u32;
+    for c in password.chars() {
+        let predictable = previous.map_or(false, |previous| {
+            let step = c as i64 - previous as i64;
+            (-1..=1).contains(&step)
+        });
+        if !predictable {
+            distinct_steps += 1;
+        }
+        previous = Some(c);
+    }
+    if alphabet_size == 0 {
+        return 0.0;
+    }
+    distinct_steps as f64 * (alphabet_size as f64).log2()
+}
+
+// A sorted file of SHA-1 hashes of breached passwords. The Pwned Passwords download
+// runs to tens of gigabytes, so the file is binary searched on every lookup rather
+// than loaded: a lookup reads a few dozen lines whatever the size of the file.
+#[derive(Default)]
+struct BreachedPasswords {
+    path: Option<PathBuf>,
+}
+
+impl BreachedPasswords {
+    fn open(path: &Path) -> Result<Self, anyhow::Error> {
+        File::open(path)
+            .with_context(|| format!("Failed to open breached password list at {}", path.display()))?;
+        Ok(Self { path: Some(path.to_path_buf()) })
+    }
+
+    // A list that cannot be read is logged rather than blocking every password
+    // change, the other rules still apply. The search reads from disk, so it runs on
+    // the blocking thread pool rather than holding up the async runtime.
+    async fn contains(&self, password: &Secret<String>) -> bool {
+        let path = match &self.path {
+            Some(path) => path.clone(),
+            None => return false,
+        };
+        let hash = hex::encode_upper(Sha1::digest(password.expose_secret().as_bytes()));
+        let search = spawn_blocking_with_tracinig(move || {
+            match File::open(&path).and_then(|file| contains_hash(BufReader::new(file), &hash)) {
+                Ok(found) => found,
+                Err(e) => {
+                    tracing::error!(
+                        error.cause_chain = ?e,
+                        error.message = %e,
+                        path = %path.display(),
+                        "Failed to search the breached password list"
+                    );
+                    false
+                }
+            }
+        });
+        match search.await {
+            Ok(found) => found,
+            Err(e) => {
+                tracing::error!(error.cause_chain = ?e, "The breached password search did not complete");
+                false
+            }
+        }
+    }
+}
+
+// Searches lines of upper- or lower-case SHA-1 hashes sorted in ascending order, each
+// optionally followed by `:` and a count. Blank lines, lines starting with `#` and
+// anything else that is not a hash are skipped.
+fn contains_hash<R: BufRead + Seek>(mut reader: R, hash: &str) -> std::io::Result<bool> {
+    // Every hash on a line starting before `low` is smaller than the one we are
+    // looking for, and every hash on a line starting at or after `high` is larger.
+    let mut low = 0;
+    let mut high = reader.seek(SeekFrom::End(0))?;
+    while low < high {
+        let middle = low + (high - low) / 2;
+        match next_hash_from(&mut reader, middle)? {
+            Some((line_hash, line_end)) => match line_hash.as_str().cmp(hash) {
+                Ordering::Less => low = line_end,
+                Ordering::Equal => return Ok(true),
+                Ordering::Greater => high = middle,
+            },
+            None => high = middle,
+        }
+    }
+    Ok(false)
+}
+
+// The first hash on a line starting at or after `position`, with the position just
+// after that line.
+fn next_hash_from<R: BufRead + Seek>(reader: &mut R, position: u64) -> std::io::Result<Option<(String, u64)>> {
+    let mut line = Vec::new();
+    let mut line_end = position;
+    if position > 0 {
+        // Skip the rest of the line `position` falls in.
+        reader.seek(SeekFrom::Start(position - 1))?;
+        line_end = position - 1 + reader.read_until(b'\n', &mut line)? as u64;
+    } else {
+        reader.seek(SeekFrom::Start(0))?;
+    }
+    loop {
+        line.clear();
+        let read = reader.read_until(b'\n', &mut line)?;
+        if read == 0 {
+            return Ok(None);
+        }
+        line_end += read as u64;
+        let hash = String::from_utf8_lossy(&line)
+            .trim()
+            .split(':')
+            .next()
+            .unwrap_or_default()
+            .to_uppercase();
+        if hash.len() == 40 && hash.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Ok(Some((hash, line_end)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{contains_hash, estimate_strength_bits, BreachedPasswords, PasswordPolicy};
+    use claim::{assert_err, assert_ok};
+    use secrecy::Secret;
+    use std::io::Cursor;
+    use std::path::Path;
+
+    const SAMPLE_LIST: &str = "configuration/breached_passwords.txt";
+
+    fn policy(breached: BreachedPasswords) -> PasswordPolicy {
+        PasswordPolicy {
+            minimum_length: 12,
+            maximum_length: 128,
+            minimum_strength_bits: 40.0,
+            breached,
+        }
+    }
+
+    async fn check(policy: &PasswordPolicy, password: &str) -> Result<(), String> {
+        policy.check(&Secret::new(password.to_string())).await
+    }
+
+    #[tokio::test]
+    async fn the_length_is_counted_in_characters_without_wrapping() {
+        let policy = policy(BreachedPasswords::default());
+        assert_err!(check(&policy, "short_pass").await);
+        assert_ok!(check(&policy, "ünïcödé-pässwörd").await);
+        assert_ok!(check(&policy, &"correct horse battery staple ".repeat(4)[..116]).await);
+        assert_err!(check(&policy, &"correct horse battery staple ".repeat(10)).await);
+        // 264 characters wrap around to 8 as a u8, which used to be accepted.
+        assert_err!(check(&policy, &"a-b".repeat(88)).await);
+    }
+
+    #[tokio::test]
+    async fn breached_passwords_are_rejected_by_hash() {
+        let policy = policy(BreachedPasswords::open(Path::new(SAMPLE_LIST)).unwrap());
+        assert_err!(check(&policy, "password1234").await);
+        assert_ok!(check(&policy, "correct horse battery staple").await);
+    }
+
+    #[test]
+    fn every_hash_in_a_sorted_list_is_found() {
+        let hashes = [
+            "0000000A0E3B9F25FF41DE4B5AC238C2D545C7A8",
+            "1E4C9B93F3F0682250B6CF8331B7EE68FD8ABCDE",
+            "5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8",
+            "ABF7AAD6438836DBE526AA231ABDE2D0EEF74D42",
+            "FFFFFFF8A0E3B9F25FF41DE4B5AC238C2D545C7A",
+        ];
+        let contents = format!(
+            "# comment\n\n{}:3\n{}\r\n{}:12\n{}\n{}",
+            hashes[0], hashes[1], hashes[2].to_lowercase(), hashes[3], hashes[4]
+        );
+        for hash in hashes {
+            assert!(contains_hash(Cursor::new(&contents), hash).unwrap(), "{} was not found", hash);
+        }
+        for missing in ["0000000000000000000000000000000000000000", "5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD9", "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF"] {
+            assert!(!contains_hash(Cursor::new(&contents), missing).unwrap(), "{} was found", missing);
+        }
+        assert!(!contains_hash(Cursor::new(""), hashes[0]).unwrap());
+    }
+
+    #[tokio::test]
+    async fn the_sample_list_only_holds_passwords_the_other_rules_allow() {
+        // Shorter or weaker passwords are rejected before the list is consulted,
+        // so listing them would not protect anybody.
+        let policy = policy(BreachedPasswords::default());
+        for password in ["password1234", "Password123!", "1qaz2wsx3edc", "administrator"] {
+            assert_ok!(check(&policy, password).await);
+        }
+    }
+
+    #[test]
+    fn repeated_and_sequential_characters_are_weak() {
+        assert!(estimate_strength_bits("aaaaaaaaaaaa") < 40.0);
+        assert!(estimate_strength_bits("123456789012") < 40.0);
+        assert!(estimate_strength_bits("abcdefghijkl") < 40.0);
+        assert!(estimate_strength_bits("a-long-enough-password") >= 40.0);
+        assert!(estimate_strength_bits("Tr0ub4dor&3x") >= 40.0);
+    }
+
+    #[test]
+    fn an_empty_password_has_no_strength() {
+        assert_eq!(estimate_strength_bits(""), 0.0);
+    }
+}
@@ -0,0 +1,214 @@
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+use crate::domain::ValidationError;
+
+// Form fields that are part of the subscribe form itself and so cannot be used as
+// attribute keys.
+const RESERVED_KEYS: [&str; 5] = ["email", "name", "website", "form_token", "locale"];
+const MAXIMUM_KEY_LENGTH: usize = 50;
+const MAXIMUM_VALUE_LENGTH: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AttributeType {
+    String,
+    Number,
+    Boolean,
+}
+
+impl AttributeType {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "string" => Ok(Self::String),
+            "number" => Ok(Self::Number),
+            "boolean" => Ok(Self::Boolean),
+            other => Err(format!("{} is not a supported attribute type. Use 'string', 'number' or 'boolean'", other)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::String => "string",
+            Self::Number => "number",
+            Self::Boolean => "boolean",
+        }
+    }
+
+    // Form bodies only carry strings, so string representations are accepted for
+    // every type alongside native JSON values.
+    fn coerce(&self, value: Value) -> Option<Value> {
+        match (self, value) {
+            (Self::String, Value::String(s)) => Some(Value::String(s)),
+            (Self::String, Value::Number(n)) => Some(Value::String(n.to_string())),
+            (Self::String, Value::Bool(b)) => Some(Value::String(b.to_string())),
+            (Self::Number, Value::Number(n)) => Some(Value::Number(n)),
+            (Self::Number, Value::String(s)) => parse_number(s.trim()),
+            (Self::Boolean, Value::Bool(b)) => Some(Value::Bool(b)),
+            (Self::Boolean, Value::String(s)) => match s.trim() {
+                "true" | "on" | "yes" => Some(Value::Bool(true)),
+                "false" | "off" | "no" => Some(Value::Bool(false)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+fn parse_number(s: &str) -> Option<Value> {
+    if let Ok(n) = s.parse::<i64>() {
+        return Some(Value::from(n));
+    }
+    s.parse::<f64>()
+        .ok()
+        .and_then(serde_json::Number::from_f64)
+        .map(Value::Number)
+}
+
+#[derive(Debug)]
+pub struct AttributeKey(String);
+
+impl AttributeKey {
+    pub fn parse(s: String) -> Result<AttributeKey, String> {
+        let is_well_formed = s.chars().next().map_or(false, |c| c.is_ascii_lowercase())
+            && s.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+        if !is_well_formed || s.len() > MAXIMUM_KEY_LENGTH {
+            Err(format!(
+                "{} is not a valid attribute key. Use lowercase letters, digits and underscores, starting with a letter",
+                s
+            ))
+        } else if RESERVED_KEYS.contains(&s.as_str()) {
+            Err(format!("{} is reserved and cannot be used as an attribute key", s))
+        } else {
+            Ok(Self(s))
+        }
+    }
+}
+
+impl AsRef<str> for AttributeKey {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// The attribute keys admins have allowed, with the type of value each one holds.
+#[derive(Default)]
+pub struct AttributeSchema(HashMap<String, AttributeType>);
+
+impl AttributeSchema {
+    pub fn new(definitions: impl IntoIterator<Item = (String, AttributeType)>) -> Self {
+        Self(definitions.into_iter().collect())
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SubscriberAttributes(Map<String, Value>);
+
+impl SubscriberAttributes {
+    // Blank values are dropped, as optional fields on a form are submitted empty.
+    pub fn parse(
+        raw: HashMap<String, Value>,
+        schema: &AttributeSchema,
+    ) -> Result<SubscriberAttributes, ValidationError> {
+        let mut attributes = Map::new();
+        for (key, value) in raw {
+            let is_blank = match &value {
+                Value::Null => true,
+                Value::String(s) => s.trim().is_empty(),
+                _ => false,
+            };
+            if is_blank {
+                continue;
+            }
+            let attribute_type = schema.0.get(&key).ok_or_else(|| {
+                ValidationError::new(key.as_str(), format!("{} is not a recognised field", key))
+            })?;
+            let value = attribute_type.coerce(value).ok_or_else(|| {
+                ValidationError::new(key.as_str(), format!("{} must be a {}", key, attribute_type.as_str()))
+            })?;
+            if matches!(&value, Value::String(s) if s.chars().count() > MAXIMUM_VALUE_LENGTH) {
+                return Err(ValidationError::new(
+                    key.as_str(),
+                    format!("{} must be at most {} characters long", key, MAXIMUM_VALUE_LENGTH),
+                ));
+            }
+            attributes.insert(key, value);
+        }
+        Ok(Self(attributes))
+    }
+
+    pub fn from_stored(value: Value) -> Self {
+        match value {
+            Value::Object(map) => Self(map),
+            _ => Self::default(),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.0.get(key)
+    }
+
+    pub fn to_json(&self) -> Value {
+        Value::Object(self.0.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AttributeKey, AttributeSchema, AttributeType, SubscriberAttributes};
+    use claim::{assert_err, assert_ok};
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+
+    fn schema() -> AttributeSchema {
+        AttributeSchema::new(vec![
+            ("company".to_string(), AttributeType::String),
+            ("seats".to_string(), AttributeType::Number),
+            ("beta_tester".to_string(), AttributeType::Boolean),
+        ])
+    }
+
+    fn raw(value: Value) -> HashMap<String, Value> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn well_formed_keys_are_accepted() {
+        assert_ok!(AttributeKey::parse("signup_source2".to_string()));
+    }
+
+    #[test]
+    fn malformed_and_reserved_keys_are_rejected() {
+        for key in ["", "Company", "2fa", "sign-up", "email", "form_token"] {
+            assert_err!(AttributeKey::parse(key.to_string()));
+        }
+    }
+
+    #[test]
+    fn values_are_coerced_to_their_declared_type() {
+        let attributes = SubscriberAttributes::parse(
+            raw(json!({ "company": "Acme", "seats": "12", "beta_tester": "on" })),
+            &schema(),
+        ).unwrap();
+        assert_eq!(attributes.get("company"), Some(&json!("Acme")));
+        assert_eq!(attributes.get("seats"), Some(&json!(12)));
+        assert_eq!(attributes.get("beta_tester"), Some(&json!(true)));
+    }
+
+    #[test]
+    fn blank_values_are_dropped() {
+        let attributes = SubscriberAttributes::parse(raw(json!({ "company": " " })), &schema()).unwrap();
+        assert_eq!(attributes.get("company"), None);
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected_with_the_field_name() {
+        let error = SubscriberAttributes::parse(raw(json!({ "role": "CTO" })), &schema()).unwrap_err();
+        assert_eq!(error.field, "role");
+    }
+
+    #[test]
+    fn values_of_the_wrong_type_are_rejected() {
+        assert_err!(SubscriberAttributes::parse(raw(json!({ "seats": "a dozen" })), &schema()));
+        assert_err!(SubscriberAttributes::parse(raw(json!({ "beta_tester": 3 })), &schema()));
+    }
+}
@@ -4,14 +4,24 @@ use validator::validate_email;
 pub struct SubscriberEmail(String);
 
 impl SubscriberEmail {
+    // Domains are case-insensitive, so they are lower-cased to give every address a
+    // single canonical form. The local part is kept as typed.
     pub fn parse(s: String) -> Result<SubscriberEmail, String> {
-
-        if validate_email(&s) {
-            Ok(Self(s))
-        } else {
-            Err(format!("{} is not a valid subscriber email", s))
+        let s = s.trim();
+        if !validate_email(s) {
+            return Err(format!("{} is not a valid subscriber email", s));
+        }
+        match s.rsplit_once('@') {
+            Some((local_part, domain)) => Ok(Self(format!("{}@{}", local_part, domain.to_lowercase()))),
+            None => Err(format!("{} is not a valid subscriber email", s)),
         }
     }
+
+    // Always lower-case, see `parse`.
+    pub fn domain(&self) -> &str {
+        // A valid email always contains an `@`
+        self.0.rsplit('@').next().unwrap_or_default()
+    }
 }
 
 impl std::fmt::Display for SubscriberEmail {
@@ -64,6 +74,24 @@ mod tests {
         assert_err!(SubscriberEmail::parse(email));
     }
 
+    #[test]
+    fn domain_is_the_part_after_the_at_symbol() {
+        let email = SubscriberEmail::parse("dione@email.com".to_string()).unwrap();
+        assert_eq!(email.domain(), "email.com");
+    }
+
+    #[test]
+    fn the_domain_is_lower_cased() {
+        let email = SubscriberEmail::parse("Dione@Email.COM".to_string()).unwrap();
+        assert_eq!(email.as_ref(), "Dione@email.com");
+    }
+
+    #[test]
+    fn surrounding_whitespace_is_trimmed() {
+        let email = SubscriberEmail::parse("  dione@email.com ".to_string()).unwrap();
+        assert_eq!(email.as_ref(), "dione@email.com");
+    }
+
     #[quickcheck_macros::quickcheck]
     fn valid_emails_successfully_parsed(valid_email: ValidEmailFixture) -> bool {
         dbg!(&valid_email.0);
@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+
+use crate::domain::SubscriberName;
+use crate::domain::subscriber_email::SubscriberEmail;
+
+pub const MAXIMUM_IMPORT_ROWS: usize = 10_000;
+
+pub struct ImportRow {
+    // Line in the uploaded file, counting the header as line 1.
+    pub line: u64,
+    pub subscriber: Result<ImportedSubscriber, String>,
+}
+
+#[derive(Debug)]
+pub struct ImportedSubscriber {
+    pub email: SubscriberEmail,
+    pub name: SubscriberName,
+}
+
+// Parses a CSV with `email` and `name` columns, in any order and alongside any other
+// columns. Errors are reported per row so that one bad row does not hide the rest;
+// only a missing column or an unreadable file fails the whole import.
+pub fn parse_import(csv: &str) -> Result<Vec<ImportRow>, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .from_reader(csv.as_bytes());
+    let headers = reader.headers()
+        .map_err(|e| format!("The file could not be read: {}", e))?
+        .clone();
+    let column = |name: &str| {
+        headers.iter()
+            .position(|header| header.eq_ignore_ascii_case(name))
+            .ok_or_else(|| format!("The file must have a header row with an `{}` column", name))
+    };
+    let email_column = column("email")?;
+    let name_column = column("name")?;
+
+    let mut rows = Vec::new();
+    let mut first_seen_on: HashMap<String, u64> = HashMap::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| format!("The file could not be read: {}", e))?;
+        if record.iter().all(|field| field.is_empty()) {
+            continue;
+        }
+        if rows.len() == MAXIMUM_IMPORT_ROWS {
+            return Err(format!("Imports are limited to {} rows at a time", MAXIMUM_IMPORT_ROWS));
+        }
+        let line = record.position().map_or(0, |position| position.line());
+        let field = |index| record.get(index).unwrap_or_default().to_string();
+        let subscriber = parse_row(field(email_column), field(name_column))
+            .and_then(|subscriber| {
+                // Stored emails are unique regardless of case.
+                let key = subscriber.email.as_ref().to_lowercase();
+                match first_seen_on.get(&key) {
+                    Some(first_line) => Err(format!("Duplicate of line {}", first_line)),
+                    None => {
+                        first_seen_on.insert(key, line);
+                        Ok(subscriber)
+                    }
+                }
+            });
+        rows.push(ImportRow { line, subscriber });
+    }
+    Ok(rows)
+}
+
+fn parse_row(email: String, name: String) -> Result<ImportedSubscriber, String> {
+    let email = SubscriberEmail::parse(email)?;
+    let name = SubscriberName::parse(name)?;
+    Ok(ImportedSubscriber { email, name })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_import;
+    use claim::{assert_err, assert_ok};
+
+    #[test]
+    fn columns_are_found_by_header_in_any_order() {
+        let rows = parse_import("Name,Company,EMAIL\nDione,Acme,dione@email.com\n").unwrap();
+        assert_eq!(rows.len(), 1);
+        let subscriber = rows[0].subscriber.as_ref().unwrap();
+        assert_eq!(subscriber.email.as_ref(), "dione@email.com");
+        assert_eq!(subscriber.name.as_ref(), "Dione");
+    }
+
+    #[test]
+    fn a_missing_column_fails_the_whole_import() {
+        assert_err!(parse_import("email\ndione@email.com\n"));
+    }
+
+    #[test]
+    fn invalid_rows_are_reported_with_their_line_number() {
+        let rows = parse_import("email,name\ndione@email.com,Dione\nnot-an-email,Titan\n").unwrap();
+        assert_ok!(&rows[0].subscriber);
+        assert_eq!(rows[1].line, 3);
+        assert_err!(&rows[1].subscriber);
+    }
+
+    #[test]
+    fn duplicate_emails_are_reported_against_the_first_occurrence() {
+        let rows = parse_import("email,name\ndione@email.com,Dione\nDione@EMAIL.com,Dione\n").unwrap();
+        assert_eq!(rows[1].subscriber.as_ref().err().unwrap(), "Duplicate of line 2");
+    }
+
+    #[test]
+    fn blank_lines_are_skipped() {
+        let rows = parse_import("email,name\n\ndione@email.com,Dione\n,\n").unwrap();
+        assert_eq!(rows.len(), 1);
+    }
+}
@@ -0,0 +1,100 @@
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SubscriptionStatus {
+    PendingConfirmation,
+    Confirmed,
+    Unsubscribed,
+    // The subscriber's mail server rejected our emails.
+    Bounced,
+    // The subscriber reported our emails as spam.
+    Complained,
+}
+
+impl SubscriptionStatus {
+    pub const ALL: [SubscriptionStatus; 5] = [
+        Self::PendingConfirmation,
+        Self::Confirmed,
+        Self::Unsubscribed,
+        Self::Bounced,
+        Self::Complained,
+    ];
+
+    pub fn parse(s: &str) -> Result<Self, String> {
+        Self::ALL
+            .into_iter()
+            .find(|status| status.as_str() == s)
+            .ok_or_else(|| format!("{} is not a subscription status", s))
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::PendingConfirmation => "pending_confirmation",
+            Self::Confirmed => "confirmed",
+            Self::Unsubscribed => "unsubscribed",
+            Self::Bounced => "bounced",
+            Self::Complained => "complained",
+        }
+    }
+
+    // The only place that decides which status changes are allowed. Staying in the
+    // same status is always allowed so that repeated requests are harmless.
+    pub fn can_transition_to(&self, next: SubscriptionStatus) -> bool {
+        use SubscriptionStatus::*;
+        *self == next || matches!(
+            (self, next),
+            (PendingConfirmation, Confirmed | Unsubscribed | Bounced | Complained)
+                | (Confirmed, Unsubscribed | Bounced | Complained)
+                // Someone who left, or whose address was fixed, can sign up again.
+                | (Unsubscribed | Bounced, PendingConfirmation)
+        )
+    }
+}
+
+impl std::fmt::Display for SubscriptionStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SubscriptionStatus;
+    use super::SubscriptionStatus::*;
+    use claim::{assert_err, assert_ok_eq};
+
+    #[test]
+    fn every_status_round_trips_through_its_string_form() {
+        for status in SubscriptionStatus::ALL {
+            assert_ok_eq!(SubscriptionStatus::parse(status.as_str()), status);
+        }
+    }
+
+    #[test]
+    fn unknown_statuses_are_rejected() {
+        assert_err!(SubscriptionStatus::parse("deleted"));
+    }
+
+    #[test]
+    fn pending_subscribers_can_be_confirmed() {
+        assert!(PendingConfirmation.can_transition_to(Confirmed));
+    }
+
+    #[test]
+    fn unsubscribed_subscribers_cannot_be_confirmed_without_signing_up_again() {
+        assert!(!Unsubscribed.can_transition_to(Confirmed));
+        assert!(Unsubscribed.can_transition_to(PendingConfirmation));
+    }
+
+    #[test]
+    fn complaints_are_final() {
+        for status in SubscriptionStatus::ALL {
+            assert_eq!(Complained.can_transition_to(status), status == Complained);
+        }
+    }
+
+    #[test]
+    fn staying_in_the_same_status_is_allowed() {
+        for status in SubscriptionStatus::ALL {
+            assert!(status.can_transition_to(status));
+        }
+    }
+}
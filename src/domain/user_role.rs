@@ -0,0 +1,86 @@
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UserRole {
+    // Can do everything, including managing other users.
+    Owner,
+    // Can publish newsletters and manage subscribers.
+    Editor,
+    // Can look around the admin area without changing anything.
+    Viewer,
+}
+
+impl UserRole {
+    pub const ALL: [UserRole; 3] = [Self::Owner, Self::Editor, Self::Viewer];
+
+    pub fn parse(s: &str) -> Result<Self, String> {
+        Self::ALL
+            .into_iter()
+            .find(|role| role.as_str() == s)
+            .ok_or_else(|| format!("{} is not a user role", s))
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Owner => "owner",
+            Self::Editor => "editor",
+            Self::Viewer => "viewer",
+        }
+    }
+
+    // Each role can do everything the roles below it can.
+    pub fn includes(&self, required: UserRole) -> bool {
+        self.rank() >= required.rank()
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            Self::Owner => 2,
+            Self::Editor => 1,
+            Self::Viewer => 0,
+        }
+    }
+}
+
+impl std::fmt::Display for UserRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UserRole;
+    use super::UserRole::*;
+    use claim::{assert_err, assert_ok_eq};
+
+    #[test]
+    fn every_role_round_trips_through_its_string_form() {
+        for role in UserRole::ALL {
+            assert_ok_eq!(UserRole::parse(role.as_str()), role);
+        }
+    }
+
+    #[test]
+    fn unknown_roles_are_rejected() {
+        assert_err!(UserRole::parse("admin"));
+    }
+
+    #[test]
+    fn owners_can_do_everything() {
+        for role in UserRole::ALL {
+            assert!(Owner.includes(role));
+        }
+    }
+
+    #[test]
+    fn viewers_can_only_view() {
+        assert!(Viewer.includes(Viewer));
+        assert!(!Viewer.includes(Editor));
+        assert!(!Viewer.includes(Owner));
+    }
+
+    #[test]
+    fn editors_cannot_manage_users() {
+        assert!(Editor.includes(Viewer));
+        assert!(!Editor.includes(Owner));
+    }
+}
@@ -0,0 +1,131 @@
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+
+type Catalog = HashMap<String, String>;
+
+// Catalogs are compiled in so that a missing or malformed file fails the tests
+// rather than a deployment.
+static CATALOGS: Lazy<HashMap<Locale, Catalog>> = Lazy::new(|| {
+    Locale::ALL
+        .into_iter()
+        .map(|locale| {
+            let source = match locale {
+                Locale::En => include_str!("../locales/en.json"),
+                Locale::Es => include_str!("../locales/es.json"),
+                Locale::Fr => include_str!("../locales/fr.json"),
+            };
+            let catalog = serde_json::from_str(source)
+                .unwrap_or_else(|e| panic!("The {} message catalog is invalid: {}", locale.as_str(), e));
+            (locale, catalog)
+        })
+        .collect()
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Locale {
+    En,
+    Es,
+    Fr,
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Self::En
+    }
+}
+
+impl Locale {
+    pub const ALL: [Locale; 3] = [Self::En, Self::Es, Self::Fr];
+
+    // Accepts language tags such as `fr` or `fr-CA`, matching on the language only.
+    pub fn parse(tag: &str) -> Result<Self, String> {
+        let language = tag.trim().split(|c: char| c == '-' || c == '_').next().unwrap_or_default();
+        Self::ALL
+            .into_iter()
+            .find(|locale| locale.as_str().eq_ignore_ascii_case(language))
+            .ok_or_else(|| format!("{} is not a supported locale", tag))
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::En => "en",
+            Self::Es => "es",
+            Self::Fr => "fr",
+        }
+    }
+
+    // An explicit choice wins over the browser's preferences, and anything we
+    // cannot serve falls back to the default locale.
+    pub fn negotiate(requested: Option<&str>, accept_language: Option<&str>) -> Self {
+        requested
+            .and_then(|tag| Self::parse(tag).ok())
+            .or_else(|| accept_language.and_then(Self::from_accept_language))
+            .unwrap_or_default()
+    }
+
+    // Picks the supported language with the highest quality in an
+    // `Accept-Language` header, e.g. `fr-CA,fr;q=0.9,en;q=0.8`.
+    fn from_accept_language(header: &str) -> Option<Self> {
+        let mut candidates: Vec<(Self, f32)> = header
+            .split(',')
+            .filter_map(|entry| {
+                let mut parts = entry.split(';');
+                let locale = Self::parse(parts.next()?).ok()?;
+                let quality = parts
+                    .find_map(|parameter| parameter.trim().strip_prefix("q="))
+                    .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())?;
+                Some((locale, quality))
+            })
+            .filter(|(_, quality)| *quality > 0.0)
+            .collect();
+        // A stable sort keeps the header's order between equal qualities.
+        candidates.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        candidates.first().map(|(locale, _)| *locale)
+    }
+
+    // Messages missing from a catalog fall back to the default locale.
+    pub fn message(&self, key: &str) -> &'static str {
+        CATALOGS[self]
+            .get(key)
+            .or_else(|| CATALOGS[&Locale::default()].get(key))
+            .map(String::as_str)
+            .unwrap_or_else(|| panic!("There is no {} message in the default catalog", key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Locale, CATALOGS};
+
+    #[test]
+    fn every_catalog_translates_every_message() {
+        let default_catalog = &CATALOGS[&Locale::default()];
+        for locale in Locale::ALL {
+            for key in default_catalog.keys() {
+                assert!(CATALOGS[&locale].contains_key(key), "{} is missing {}", locale.as_str(), key);
+            }
+        }
+    }
+
+    #[test]
+    fn regional_tags_match_their_language() {
+        assert_eq!(Locale::parse("fr-CA"), Ok(Locale::Fr));
+        assert_eq!(Locale::parse("ES"), Ok(Locale::Es));
+    }
+
+    #[test]
+    fn an_explicit_choice_wins_over_the_header() {
+        assert_eq!(Locale::negotiate(Some("es"), Some("fr")), Locale::Es);
+    }
+
+    #[test]
+    fn the_highest_quality_supported_language_is_picked() {
+        assert_eq!(Locale::negotiate(None, Some("de;q=1.0, en;q=0.5, fr;q=0.8")), Locale::Fr);
+    }
+
+    #[test]
+    fn unsupported_languages_fall_back_to_the_default() {
+        assert_eq!(Locale::negotiate(Some("xx"), Some("de, ja;q=0.5")), Locale::En);
+        assert_eq!(Locale::negotiate(None, None), Locale::En);
+    }
+}
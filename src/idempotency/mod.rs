@@ -1,2 +1,5 @@
 mod key;
-pub use key::IdempotencyKey;
\ No newline at end of file
+mod persistence;
+
+pub use key::IdempotencyKey;
+pub use persistence::{save_response, try_processing, NextAction};
@@ -0,0 +1,149 @@
+use actix_web::body::to_bytes;
+use actix_web::http::StatusCode;
+use actix_web::HttpResponse;
+use anyhow::Context;
+use chrono::Utc;
+use sqlx::postgres::PgHasArrayType;
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use super::IdempotencyKey;
+use crate::startup::DbConnectionKind;
+
+#[derive(Debug, sqlx::Type)]
+#[sqlx(type_name = "header_pair")]
+struct HeaderPairRecord {
+    name: String,
+    value: Vec<u8>,
+}
+
+impl PgHasArrayType for HeaderPairRecord {
+    fn array_type_info() -> sqlx::postgres::PgTypeInfo {
+        sqlx::postgres::PgTypeInfo::with_name("_header_pair")
+    }
+}
+
+pub enum NextAction {
+    // The key is new: the request should be processed and its response saved
+    // through the transaction, which holds the key until then.
+    StartProcessing(Transaction<'static, Postgres>),
+    // The key was used before: this is the response that was sent then.
+    ReturnSavedResponse(HttpResponse),
+}
+
+// Claims the key for the user. A retry that arrives while the first request is
+// still being processed waits on the key's row until that request is done, and
+// then gets its response rather than processing the request a second time.
+#[tracing::instrument(name = "Claiming an idempotency key", skip(database, idempotency_key))]
+pub async fn try_processing(
+    database: &DbConnectionKind,
+    idempotency_key: &IdempotencyKey,
+    user_id: Uuid,
+) -> Result<NextAction, anyhow::Error> {
+    let mut transaction = database
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let inserted = sqlx::query!(
+        r#"
+        INSERT INTO idempotency (user_id, idempotency_key, created_at)
+        VALUES ($1, $2, $3)
+        ON CONFLICT DO NOTHING
+        "#,
+        user_id,
+        idempotency_key.as_ref(),
+        Utc::now()
+    )
+        .execute(&mut transaction)
+        .await
+        .context("Failed to store the idempotency key")?
+        .rows_affected();
+    if inserted > 0 {
+        return Ok(NextAction::StartProcessing(transaction));
+    }
+    let saved_response = get_saved_response(database, idempotency_key, user_id)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("We expected a saved response, we didn't find it"))?;
+    Ok(NextAction::ReturnSavedResponse(saved_response))
+}
+
+async fn get_saved_response(
+    database: &DbConnectionKind,
+    idempotency_key: &IdempotencyKey,
+    user_id: Uuid,
+) -> Result<Option<HttpResponse>, anyhow::Error> {
+    let saved_response = sqlx::query!(
+        r#"
+        SELECT
+            response_status_code AS "response_status_code!",
+            response_headers AS "response_headers!: Vec<HeaderPairRecord>",
+            response_body AS "response_body!"
+        FROM idempotency
+        WHERE user_id = $1 AND idempotency_key = $2
+        "#,
+        user_id,
+        idempotency_key.as_ref()
+    )
+        .fetch_optional(database)
+        .await
+        .context("Failed to retrieve the saved response")?;
+    saved_response
+        .map(|record| {
+            let status_code = StatusCode::from_u16(record.response_status_code.try_into()?)?;
+            let mut response = HttpResponse::build(status_code);
+            for HeaderPairRecord { name, value } in record.response_headers {
+                response.append_header((name, value));
+            }
+            Ok(response.body(record.response_body))
+        })
+        .transpose()
+}
+
+// Stores the response for retries to replay, releases the key and returns the
+// response so that it can be sent.
+#[tracing::instrument(name = "Saving the response to an idempotent request", skip(transaction, idempotency_key, response))]
+pub async fn save_response(
+    mut transaction: Transaction<'static, Postgres>,
+    idempotency_key: &IdempotencyKey,
+    user_id: Uuid,
+    response: HttpResponse,
+) -> Result<HttpResponse, anyhow::Error> {
+    let (response_head, body) = response.into_parts();
+    // The whole body is buffered, which is fine for the small responses we save.
+    let body = to_bytes(body)
+        .await
+        .map_err(|e| anyhow::anyhow!("{}", e))
+        .context("Failed to read the response body")?;
+    let status_code = response_head.status().as_u16() as i16;
+    let headers = response_head
+        .headers()
+        .iter()
+        .map(|(name, value)| HeaderPairRecord {
+            name: name.as_str().to_owned(),
+            value: value.as_bytes().to_owned(),
+        })
+        .collect::<Vec<_>>();
+    sqlx::query_unchecked!(
+        r#"
+        UPDATE idempotency
+        SET
+            response_status_code = $3,
+            response_headers = $4,
+            response_body = $5
+        WHERE user_id = $1 AND idempotency_key = $2
+        "#,
+        user_id,
+        idempotency_key.as_ref(),
+        status_code,
+        headers,
+        body.as_ref()
+    )
+        .execute(&mut transaction)
+        .await
+        .context("Failed to save the response")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the saved response")?;
+    Ok(response_head.set_body(body).map_into_boxed_body())
+}
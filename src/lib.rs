@@ -8,9 +8,31 @@ pub mod authentication;
 pub mod session_state;
 pub mod utils;
 pub mod idempotency;
+pub mod signed_token;
+pub mod rate_limit;
+pub mod subscription_protection;
+pub mod login_throttle;
+pub mod password_reset_throttle;
+pub mod subscriber_data_throttle;
+pub mod pending_subscription_cleanup;
+pub mod subscription_lifecycle;
+pub mod i18n;
+pub mod welcome_sequence;
+pub mod audit_log;
 
-#[derive(serde::Deserialize)]
+// Missing fields default to empty strings so that they are reported by the
+// same field-level validation as fields that are present but invalid.
+#[derive(serde::Deserialize, Default)]
+#[serde(default)]
 pub struct FormData {
     email: String,
     name: String,
+    // Hidden from people on the subscribe form; only bots fill it in.
+    website: String,
+    form_token: Option<String>,
+    // Overrides the `Accept-Language` header when picking the subscriber's locale.
+    locale: Option<String>,
+    // Anything else is treated as a custom subscriber attribute.
+    #[serde(flatten)]
+    attributes: std::collections::HashMap<String, serde_json::Value>,
 }
\ No newline at end of file
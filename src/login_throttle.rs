@@ -0,0 +1,191 @@
+use sha2::{Digest, Sha256};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::configuration::LoginProtectionSettings;
+use crate::rate_limit::Windows;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LockoutReason {
+    TooManyAccountFailures,
+    TooManyIpFailures,
+}
+
+impl LockoutReason {
+    // Stable identifier used in logs, so that lockouts can be counted by reason.
+    pub fn code(&self) -> &'static str {
+        match self {
+            LockoutReason::TooManyAccountFailures => "too_many_account_failures",
+            LockoutReason::TooManyIpFailures => "too_many_ip_failures",
+        }
+    }
+}
+
+// Tracks failed logins per account and per client IP. Only failures are counted,
+// so a user who types their password correctly is never slowed down by it.
+// `account` is whatever identifies the target of a guess: the username for a
+// password, the user id for a second factor.
+pub struct LoginThrottle {
+    failures: Mutex<Failures>,
+    free_failures: u32,
+    failure_delay: Duration,
+    max_failure_delay: Duration,
+}
+
+// Both counters sit behind one lock, so that an attempt is checked against and
+// recorded in both at once.
+struct Failures {
+    by_account: Windows,
+    by_ip: Windows,
+}
+
+impl LoginThrottle {
+    pub fn new(settings: &LoginProtectionSettings) -> Self {
+        Self {
+            failures: Mutex::new(Failures {
+                by_account: Windows::new(
+                    settings.username_failure_limit.max_requests,
+                    settings.username_failure_limit.window(),
+                ),
+                by_ip: Windows::new(
+                    settings.ip_failure_limit.max_requests,
+                    settings.ip_failure_limit.window(),
+                ),
+            }),
+            free_failures: settings.free_failures,
+            failure_delay: settings.failure_delay(),
+            max_failure_delay: settings.max_failure_delay(),
+        }
+    }
+
+    // Refuses locked out attempts before any password is hashed, and otherwise
+    // returns how long to wait before answering. The attempt is counted as a
+    // failure right away, so that guesses sent in parallel cannot all get past the
+    // limit while the first ones are still being verified: `record_success` gives
+    // it back.
+    pub fn check(&self, account: &str, client_ip: &str) -> Result<Duration, LockoutReason> {
+        let account = account_key(account);
+        let now = Instant::now();
+        let mut failures = self.failures.lock().unwrap();
+        if failures.by_account.is_exhausted(&account, now) {
+            return Err(LockoutReason::TooManyAccountFailures);
+        }
+        if failures.by_ip.is_exhausted(client_ip, now) {
+            return Err(LockoutReason::TooManyIpFailures);
+        }
+        let earlier_failures = failures.by_account.record(&account, now)
+            .max(failures.by_ip.record(client_ip, now))
+            - 1;
+        Ok(failure_delay(earlier_failures, self.free_failures, self.failure_delay, self.max_failure_delay))
+    }
+
+    // The failure itself was already counted by `check`, this only reports the
+    // lockout it may have caused.
+    pub fn record_failure(&self, account: &str, client_ip: &str) {
+        let now = Instant::now();
+        let reason = {
+            let failures = self.failures.lock().unwrap();
+            if failures.by_account.is_exhausted(&account_key(account), now) {
+                LockoutReason::TooManyAccountFailures
+            } else if failures.by_ip.is_exhausted(client_ip, now) {
+                LockoutReason::TooManyIpFailures
+            } else {
+                return;
+            }
+        };
+        tracing::warn!(
+            account,
+            client_ip,
+            reason = reason.code(),
+            "Locking out logins after too many failed attempts"
+        );
+    }
+
+    // The IP only gets back the attempt that succeeded and keeps its earlier
+    // failures: logging into one account must not buy more guesses at the others.
+    pub fn record_success(&self, account: &str, client_ip: &str) {
+        let mut failures = self.failures.lock().unwrap();
+        failures.by_account.reset(&account_key(account));
+        failures.by_ip.refund(client_ip);
+    }
+}
+
+// Usernames are chosen by whoever is guessing and can be of any length, so they
+// are hashed to keep every key small.
+fn account_key(account: &str) -> String {
+    hex::encode(Sha256::digest(account.as_bytes()))
+}
+
+// `failures` counts the earlier failures, so the attempt after the free ones is the
+// first to wait.
+fn failure_delay(failures: u32, free_failures: u32, delay: Duration, max_delay: Duration) -> Duration {
+    match failures.checked_sub(free_failures) {
+        None => Duration::ZERO,
+        Some(doublings) => delay
+            .checked_mul(2u32.saturating_pow(doublings))
+            .map_or(max_delay, |delay| delay.min(max_delay)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{failure_delay, LoginThrottle};
+    use crate::configuration::{LoginProtectionSettings, RateLimitSettings};
+    use claim::assert_ok;
+    use std::time::Duration;
+
+    const DELAY: Duration = Duration::from_millis(100);
+    const MAX_DELAY: Duration = Duration::from_millis(1000);
+
+    #[test]
+    fn the_first_failures_are_not_delayed() {
+        assert_eq!(failure_delay(0, 2, DELAY, MAX_DELAY), Duration::ZERO);
+        assert_eq!(failure_delay(1, 2, DELAY, MAX_DELAY), Duration::ZERO);
+    }
+
+    #[test]
+    fn the_delay_doubles_with_each_further_failure() {
+        assert_eq!(failure_delay(2, 2, DELAY, MAX_DELAY), Duration::from_millis(100));
+        assert_eq!(failure_delay(3, 2, DELAY, MAX_DELAY), Duration::from_millis(200));
+        assert_eq!(failure_delay(4, 2, DELAY, MAX_DELAY), Duration::from_millis(400));
+    }
+
+    #[test]
+    fn the_delay_is_capped() {
+        assert_eq!(failure_delay(10, 2, DELAY, MAX_DELAY), MAX_DELAY);
+        assert_eq!(failure_delay(u32::MAX, 0, DELAY, MAX_DELAY), MAX_DELAY);
+    }
+
+    #[test]
+    fn parallel_attempts_cannot_get_past_the_limit() {
+        let settings = LoginProtectionSettings {
+            username_failure_limit: RateLimitSettings { max_requests: 3, window_seconds: 60 },
+            ip_failure_limit: RateLimitSettings { max_requests: 100, window_seconds: 60 },
+            free_failures: 0,
+            failure_delay_milliseconds: 0,
+            max_failure_delay_milliseconds: 0,
+        };
+        let throttle = LoginThrottle::new(&settings);
+        // None of these attempts has finished yet.
+        let allowed = (0..10)
+            .filter(|_| throttle.check("rhea", "127.0.0.1").is_ok())
+            .count();
+        assert_eq!(allowed, 3);
+    }
+
+    #[test]
+    fn a_success_is_not_counted_against_the_ip() {
+        let settings = LoginProtectionSettings {
+            username_failure_limit: RateLimitSettings { max_requests: 100, window_seconds: 60 },
+            ip_failure_limit: RateLimitSettings { max_requests: 2, window_seconds: 60 },
+            free_failures: 0,
+            failure_delay_milliseconds: 0,
+            max_failure_delay_milliseconds: 0,
+        };
+        let throttle = LoginThrottle::new(&settings);
+        for _ in 0..5 {
+            assert_ok!(throttle.check("rhea", "127.0.0.1"));
+            throttle.record_success("rhea", "127.0.0.1");
+        }
+    }
+}
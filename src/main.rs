@@ -1,6 +1,8 @@
 use zero2prod::startup::{Application};
 use zero2prod::configuration::{get_configuration};
+use zero2prod::pending_subscription_cleanup::run_cleanup_until_stopped;
 use zero2prod::telemetry::{get_subscriber, init_subscriber};
+use zero2prod::welcome_sequence::run_welcome_sequence_until_stopped;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -16,6 +18,10 @@ async fn main() -> anyhow::Result<()> {
     let application = Application::build(config.clone())
         .await
         .expect("Failed to build application");
-    application.run_until_stopped().await?;
+    tokio::select! {
+        outcome = application.run_until_stopped() => outcome?,
+        outcome = run_cleanup_until_stopped(config.clone()) => outcome?,
+        outcome = run_welcome_sequence_until_stopped(config) => outcome?,
+    };
     Ok(())
 }
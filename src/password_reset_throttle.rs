@@ -0,0 +1,55 @@
+use crate::configuration::PasswordResetProtectionSettings;
+use crate::rate_limit::{email_key, RateLimiter};
+
+// Limits how many password reset emails can be asked for, so that the form cannot
+// be used to flood an admin's inbox or to send mail on someone else's behalf.
+pub struct PasswordResetThrottle {
+    by_email: RateLimiter,
+    by_ip: RateLimiter,
+}
+
+impl PasswordResetThrottle {
+    pub fn new(settings: &PasswordResetProtectionSettings) -> Self {
+        Self {
+            by_email: RateLimiter::new(settings.email_rate_limit.max_requests, settings.email_rate_limit.window()),
+            by_ip: RateLimiter::new(settings.ip_rate_limit.max_requests, settings.ip_rate_limit.window()),
+        }
+    }
+
+    // Records a request and returns whether an email may be sent for it. Requests
+    // refused by the IP limit are not counted against the email.
+    pub fn check(&self, email: &str, client_ip: &str) -> bool {
+        self.by_ip.check(client_ip) && self.by_email.check(&email_key(email))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PasswordResetThrottle;
+    use crate::configuration::{PasswordResetProtectionSettings, RateLimitSettings};
+
+    fn throttle() -> PasswordResetThrottle {
+        PasswordResetThrottle::new(&PasswordResetProtectionSettings {
+            email_rate_limit: RateLimitSettings { max_requests: 2, window_seconds: 3600 },
+            ip_rate_limit: RateLimitSettings { max_requests: 3, window_seconds: 3600 },
+        })
+    }
+
+    #[test]
+    fn emails_are_limited_whatever_their_case() {
+        let throttle = throttle();
+        assert!(throttle.check("rhea@email.com", "10.0.0.1"));
+        assert!(throttle.check(" Rhea@Email.com", "10.0.0.2"));
+        assert!(!throttle.check("RHEA@EMAIL.COM", "10.0.0.3"));
+    }
+
+    #[test]
+    fn ips_are_limited_across_emails() {
+        let throttle = throttle();
+        for email in ["a@email.com", "b@email.com", "c@email.com"] {
+            assert!(throttle.check(email, "10.0.0.1"));
+        }
+        assert!(!throttle.check("d@email.com", "10.0.0.1"));
+        assert!(throttle.check("d@email.com", "10.0.0.2"));
+    }
+}
@@ -0,0 +1,97 @@
+use anyhow::Context;
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::configuration::{CleanupAction, PendingSubscriptionCleanupSettings, Settings};
+use crate::domain::SubscriptionStatus;
+use crate::startup::{get_database_connection, DbConnectionKind};
+use crate::subscription_lifecycle::{delete_subscribers, DeletedSubscribers};
+
+#[derive(Debug, PartialEq)]
+pub struct CleanupReport {
+    pub subscriptions: u64,
+    pub tokens: u64,
+}
+
+// Runs the cleanup forever. A failed run is logged and retried on the next tick,
+// so a database outage does not take the application down with it.
+pub async fn run_cleanup_until_stopped(config: Settings) -> Result<(), anyhow::Error> {
+    let database = get_database_connection(&config.database);
+    let settings = config.pending_subscription_cleanup;
+    let mut interval = tokio::time::interval(settings.interval());
+    loop {
+        interval.tick().await;
+        if let Err(e) = clean_up_pending_subscriptions(&database, &settings).await {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to clean up stale pending subscriptions"
+            );
+        }
+    }
+}
+
+#[tracing::instrument(
+name = "Cleaning up stale pending subscriptions",
+skip(database, settings),
+fields(action = settings.action.as_str())
+)]
+pub async fn clean_up_pending_subscriptions(
+    database: &DbConnectionKind,
+    settings: &PendingSubscriptionCleanupSettings,
+) -> Result<CleanupReport, anyhow::Error> {
+    let cutoff = Utc::now() - settings.retention();
+    let mut transaction = database
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+
+    // Locking the rows stops a confirmation that races with the cleanup from
+    // confirming a subscription that is about to be deleted.
+    let stale_ids: Vec<Uuid> = sqlx::query!(
+        r#"
+        SELECT id FROM subscriptions
+        WHERE status = $1 AND subscribed_at < $2
+        FOR UPDATE
+        "#,
+        SubscriptionStatus::PendingConfirmation.as_str(),
+        cutoff
+    )
+        .fetch_all(&mut transaction)
+        .await
+        .context("Failed to find stale pending subscriptions")?
+        .into_iter()
+        .map(|row| row.id)
+        .collect();
+
+    if settings.action == CleanupAction::Archive {
+        sqlx::query!(
+            r#"
+            INSERT INTO archived_pending_subscriptions (id, email, name, subscribed_at, attributes, archived_at)
+            SELECT id, email, name, subscribed_at, attributes, $2
+            FROM subscriptions
+            WHERE id = ANY($1)
+            "#,
+            &stale_ids,
+            Utc::now()
+        )
+            .execute(&mut transaction)
+            .await
+            .context("Failed to archive stale pending subscriptions")?;
+    }
+    let DeletedSubscribers { subscriptions, tokens } = delete_subscribers(&mut transaction, &stale_ids)
+        .await
+        .context("Failed to delete stale pending subscriptions")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the pending subscription cleanup")?;
+
+    tracing::info!(
+        action = settings.action.as_str(),
+        subscriptions,
+        tokens,
+        "Cleaned up stale pending subscriptions"
+    );
+    Ok(CleanupReport { subscriptions, tokens })
+}
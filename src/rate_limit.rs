@@ -0,0 +1,221 @@
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Keys are often chosen by clients, so only this many are remembered at a time.
+// Once there are more, the oldest windows are forgotten first.
+const MAXIMUM_KEYS: usize = 100_000;
+
+// A fixed-window counter keyed by an arbitrary string (client IP, email domain...).
+// State lives in memory, so limits apply per application instance.
+pub struct RateLimiter {
+    windows: Mutex<Windows>,
+}
+
+impl RateLimiter {
+    pub fn new(max_requests: u32, window: Duration) -> Self {
+        Self {
+            windows: Mutex::new(Windows::new(max_requests, window)),
+        }
+    }
+
+    // Records an attempt for `key` and returns whether it is within the limit.
+    pub fn check(&self, key: &str) -> bool {
+        let mut windows = self.windows.lock().unwrap();
+        windows.record(key, Instant::now()) <= windows.max_requests
+    }
+
+    // The number of attempts recorded for `key` in the current window.
+    pub fn count(&self, key: &str) -> u32 {
+        self.windows.lock().unwrap().count(key, Instant::now())
+    }
+
+    // Whether `key` has used up its limit, without recording an attempt.
+    pub fn is_exhausted(&self, key: &str) -> bool {
+        self.windows.lock().unwrap().is_exhausted(key, Instant::now())
+    }
+
+    // Forgets the attempts recorded for `key`.
+    pub fn reset(&self, key: &str) {
+        self.windows.lock().unwrap().reset(key);
+    }
+}
+
+// Emails are typed in by whoever asks and can be of any length, so they are hashed
+// to keep every key small.
+pub fn email_key(email: &str) -> String {
+    hex::encode(Sha256::digest(email.trim().to_lowercase().as_bytes()))
+}
+
+// The counters behind a `RateLimiter`, for callers that need to update several of
+// them under a single lock.
+pub struct Windows {
+    max_requests: u32,
+    window: Duration,
+    counts: HashMap<String, Window>,
+    // Keys in the order their windows started, which is also the order in which
+    // they expire. Keys that were reset since stay behind until they reach the
+    // front, where their start no longer matches `counts` and they are skipped.
+    started: VecDeque<(String, Instant)>,
+}
+
+struct Window {
+    started_at: Instant,
+    count: u32,
+}
+
+impl Windows {
+    pub fn new(max_requests: u32, window: Duration) -> Self {
+        Self {
+            max_requests,
+            window,
+            counts: HashMap::new(),
+            started: VecDeque::new(),
+        }
+    }
+
+    // Records an attempt for `key` and returns how many there have been in the
+    // current window, this one included.
+    pub fn record(&mut self, key: &str, now: Instant) -> u32 {
+        self.forget_old_windows(now);
+        if let Some(window) = self.counts.get_mut(key) {
+            window.count += 1;
+            return window.count;
+        }
+        self.counts.insert(key.to_string(), Window { started_at: now, count: 1 });
+        self.started.push_back((key.to_string(), now));
+        1
+    }
+
+    pub fn count(&self, key: &str, now: Instant) -> u32 {
+        self.counts
+            .get(key)
+            .filter(|window| now.duration_since(window.started_at) < self.window)
+            .map_or(0, |window| window.count)
+    }
+
+    pub fn is_exhausted(&self, key: &str, now: Instant) -> bool {
+        self.count(key, now) >= self.max_requests
+    }
+
+    // Takes back one recorded attempt, e.g. one that turned out to be legitimate.
+    pub fn refund(&mut self, key: &str) {
+        if let Some(window) = self.counts.get_mut(key) {
+            window.count = window.count.saturating_sub(1);
+        }
+    }
+
+    pub fn reset(&mut self, key: &str) {
+        self.counts.remove(key);
+    }
+
+    // Drops expired windows and, if there are still too many keys, the oldest ones,
+    // making room for one more. Each key is only ever popped once, so this is cheap
+    // on average however many keys there are.
+    fn forget_old_windows(&mut self, now: Instant) {
+        while let Some((_, started_at)) = self.started.front() {
+            let is_expired = now.duration_since(*started_at) >= self.window;
+            if !is_expired && self.counts.len() < MAXIMUM_KEYS {
+                break;
+            }
+            let (key, started_at) = self.started.pop_front().unwrap();
+            if self.counts.get(&key).map_or(false, |window| window.started_at == started_at) {
+                self.counts.remove(&key);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RateLimiter, Windows, MAXIMUM_KEYS};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn requests_within_the_limit_are_allowed() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(60));
+        assert!(limiter.check("key"));
+        assert!(limiter.check("key"));
+    }
+
+    #[test]
+    fn requests_over_the_limit_are_rejected() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(60));
+        limiter.check("key");
+        limiter.check("key");
+        assert!(!limiter.check("key"));
+    }
+
+    #[test]
+    fn keys_are_limited_independently() {
+        let limiter = RateLimiter::new(1, Duration::from_secs(60));
+        assert!(limiter.check("a"));
+        assert!(limiter.check("b"));
+        assert!(!limiter.check("a"));
+    }
+
+    #[test]
+    fn counting_does_not_record_an_attempt() {
+        let limiter = RateLimiter::new(1, Duration::from_secs(60));
+        assert_eq!(limiter.count("key"), 0);
+        assert!(!limiter.is_exhausted("key"));
+        limiter.check("key");
+        assert_eq!(limiter.count("key"), 1);
+        assert!(limiter.is_exhausted("key"));
+    }
+
+    #[test]
+    fn a_reset_key_starts_over() {
+        let limiter = RateLimiter::new(1, Duration::from_secs(60));
+        limiter.check("key");
+        limiter.reset("key");
+        assert!(limiter.check("key"));
+    }
+
+    #[test]
+    fn the_limit_resets_once_the_window_has_passed() {
+        let limiter = RateLimiter::new(1, Duration::from_millis(10));
+        assert!(limiter.check("key"));
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(limiter.check("key"));
+    }
+
+    #[test]
+    fn a_refund_takes_back_one_attempt() {
+        let mut windows = Windows::new(2, Duration::from_secs(60));
+        let now = Instant::now();
+        windows.record("key", now);
+        windows.record("key", now);
+        windows.refund("key");
+        assert_eq!(windows.count("key", now), 1);
+        windows.refund("key");
+        windows.refund("key");
+        assert_eq!(windows.count("key", now), 0);
+    }
+
+    #[test]
+    fn the_number_of_keys_is_bounded() {
+        let mut windows = Windows::new(1, Duration::from_secs(60));
+        let now = Instant::now();
+        for i in 0..MAXIMUM_KEYS + 10 {
+            windows.record(&i.to_string(), now);
+        }
+        assert_eq!(windows.counts.len(), MAXIMUM_KEYS);
+        // The oldest keys were forgotten first.
+        assert_eq!(windows.count("0", now), 0);
+        assert_eq!(windows.count(&(MAXIMUM_KEYS + 9).to_string(), now), 1);
+    }
+
+    #[test]
+    fn a_key_reset_and_recorded_again_is_not_dropped_by_its_old_window() {
+        let mut windows = Windows::new(5, Duration::from_millis(50));
+        let start = Instant::now();
+        windows.record("key", start);
+        windows.reset("key");
+        let later = start + Duration::from_millis(30);
+        windows.record("key", later);
+        windows.record("other", start + Duration::from_millis(60));
+        assert_eq!(windows.count("key", start + Duration::from_millis(60)), 1);
+    }
+}
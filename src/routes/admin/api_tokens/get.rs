@@ -0,0 +1,100 @@
+use actix_web::{HttpResponse, web};
+use actix_web::http::header::ContentType;
+use actix_web_flash_messages::IncomingFlashMessages;
+use std::fmt::Write;
+
+use crate::authentication::{list_api_tokens, AuthenticatedUser, CsrfToken};
+use crate::domain::ApiScope;
+use crate::startup::DbConnectionKind;
+use crate::utils::e500;
+
+pub async fn api_tokens_page(
+    user: AuthenticatedUser,
+    csrf_token: CsrfToken,
+    database: web::Data<DbConnectionKind>,
+    flash_messages: IncomingFlashMessages
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for message in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", htmlescape::encode_minimal(message.content())).unwrap();
+    }
+
+    let tokens = list_api_tokens(user.user_id, &database).await.map_err(e500)?;
+    let mut rows_html = String::new();
+    for token in tokens {
+        let scopes: Vec<&str> = token.scopes.iter().map(|scope| scope.as_str()).collect();
+        let last_used = token
+            .last_used_at
+            .map_or_else(|| "never".to_string(), |at| at.format("%Y-%m-%d %H:%M").to_string());
+        let status = match token.revoked_at {
+            Some(revoked_at) => format!("Revoked on {}", revoked_at.format("%Y-%m-%d %H:%M")),
+            None => format!(
+                r#"<form action="/admin/api-tokens/revoke" method="post">
+                {}
+                <input type="hidden" name="token_id" value="{}">
+                <button type="submit">Revoke</button>
+            </form>"#,
+                csrf_token.form_field(),
+                token.id
+            ),
+        };
+        writeln!(
+            rows_html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            htmlescape::encode_minimal(&token.name),
+            scopes.join(", "),
+            token.created_at.format("%Y-%m-%d %H:%M"),
+            last_used,
+            status,
+        )
+            .unwrap();
+    }
+
+    let mut scopes_html = String::new();
+    for scope in ApiScope::ALL {
+        writeln!(
+            scopes_html,
+            r#"<label><input type="checkbox" name="scope" value="{}"> {}</label><br>"#,
+            scope,
+            scope.description()
+        )
+            .unwrap();
+    }
+
+    Ok(HttpResponse::Ok().content_type(ContentType::html()).body(format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>API tokens</title>
+</head>
+<body>
+    {msg_html}
+    <p>API tokens let scripts use the admin endpoints on your behalf. Send them as
+    <code>Authorization: Bearer &lt;token&gt;</code>.</p>
+    <table>
+        <tr><th>Name</th><th>Scopes</th><th>Created (UTC)</th><th>Last used (UTC)</th><th></th></tr>
+        {rows_html}
+    </table>
+    <form action="/admin/api-tokens" method="post">
+        {csrf_field}
+        <label>Name
+            <input
+                type="text"
+                placeholder="Publishing script"
+                name="name"
+            >
+        </label>
+        <br>
+        {scopes_html}
+        <button type="submit">Create token</button>
+    </form>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        msg_html = msg_html,
+        rows_html = rows_html,
+        scopes_html = scopes_html,
+        csrf_field = csrf_token.form_field(),
+    )))
+}
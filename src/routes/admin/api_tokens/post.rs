@@ -0,0 +1,95 @@
+use actix_web::{HttpResponse, web};
+use actix_web::http::header::ContentType;
+use actix_web_flash_messages::FlashMessage;
+use secrecy::ExposeSecret;
+use uuid::Uuid;
+
+use crate::audit_log::{record_audit_event, AuditAction, RequestOrigin};
+use crate::authentication::{create_api_token, revoke_api_token, AuthenticatedUser};
+use crate::domain::ApiScope;
+use crate::startup::DbConnectionKind;
+use crate::utils::{e400, e500, see_other};
+
+#[derive(serde::Deserialize)]
+pub struct RevokeApiTokenFormData {
+    token_id: Uuid,
+}
+
+// The form repeats `scope` once for every box that is ticked, which does not fit a
+// struct, so it is read as a list of pairs.
+#[tracing::instrument(
+name = "Creating an API token",
+skip(form, user, origin, database),
+fields(user_id = %user.user_id)
+)]
+pub async fn create_token(
+    form: web::Form<Vec<(String, String)>>,
+    user: AuthenticatedUser,
+    origin: RequestOrigin,
+    database: web::Data<DbConnectionKind>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut name = String::new();
+    let mut scopes = Vec::new();
+    for (key, value) in form.0 {
+        match key.as_str() {
+            "name" => name = value.trim().to_string(),
+            "scope" => {
+                let scope = ApiScope::parse(&value).map_err(e400)?;
+                if !scopes.contains(&scope) {
+                    scopes.push(scope);
+                }
+            }
+            _ => {}
+        }
+    }
+    if name.is_empty() || scopes.is_empty() {
+        FlashMessage::error("Give the token a name and pick at least one scope.").send();
+        return Ok(see_other("/admin/api-tokens"));
+    }
+
+    let token = create_api_token(user.user_id, &name, &scopes, &database).await.map_err(e500)?;
+    record_audit_event(&database, Some(user.user_id), AuditAction::ApiTokenCreated, Some(&name), &origin)
+        .await
+        .map_err(e500)?;
+
+    Ok(HttpResponse::Ok().content_type(ContentType::html()).body(format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>New API token</title>
+</head>
+<body>
+    <p>Your new API token <b>{name}</b>:</p>
+    <p><code>{token}</code></p>
+    <p>Copy it now: it is only stored hashed and will not be shown again.</p>
+    <p><a href="/admin/api-tokens">&lt;- Back</a></p>
+</body>
+</html>"#,
+        name = htmlescape::encode_minimal(&name),
+        token = token.expose_secret(),
+    )))
+}
+
+#[tracing::instrument(
+name = "Revoking an API token",
+skip(form, user, origin, database),
+fields(user_id = %user.user_id, token_id = %form.token_id)
+)]
+pub async fn revoke_token(
+    form: web::Form<RevokeApiTokenFormData>,
+    user: AuthenticatedUser,
+    origin: RequestOrigin,
+    database: web::Data<DbConnectionKind>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if !revoke_api_token(user.user_id, form.token_id, &database).await.map_err(e500)? {
+        FlashMessage::error("The token does not exist or has already been revoked.").send();
+        return Ok(see_other("/admin/api-tokens"));
+    }
+    let target = form.token_id.to_string();
+    record_audit_event(&database, Some(user.user_id), AuditAction::ApiTokenRevoked, Some(&target), &origin)
+        .await
+        .map_err(e500)?;
+    FlashMessage::info("The token has been revoked.").send();
+    Ok(see_other("/admin/api-tokens"))
+}
@@ -0,0 +1,88 @@
+use actix_web::{HttpResponse, web};
+use actix_web::http::header::ContentType;
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use std::fmt::Write;
+
+use crate::authentication::CsrfToken;
+use crate::startup::DbConnectionKind;
+use crate::utils::e500;
+
+pub async fn attributes_form(
+    csrf_token: CsrfToken,
+    database: web::Data<DbConnectionKind>,
+    flash_messages: IncomingFlashMessages
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for message in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", htmlescape::encode_minimal(message.content())).unwrap();
+    }
+
+    let definitions = sqlx::query!(
+        "SELECT key, value_type FROM subscriber_attribute_definitions ORDER BY key"
+    )
+        .fetch_all(database.get_ref())
+        .await
+        .context("Failed to retrieve subscriber attribute definitions")
+        .map_err(e500)?;
+
+    let mut rows_html = String::new();
+    for definition in definitions {
+        writeln!(
+            rows_html,
+            r#"<tr>
+            <td>{key}</td>
+            <td>{value_type}</td>
+            <td>
+                <form action="/admin/attributes/delete" method="post">
+                    {csrf_field}
+                    <input hidden type="text" name="key" value="{key}">
+                    <button type="submit">Remove</button>
+                </form>
+            </td>
+        </tr>"#,
+            key = htmlescape::encode_minimal(&definition.key),
+            value_type = htmlescape::encode_minimal(&definition.value_type),
+            csrf_field = csrf_token.form_field(),
+        ).unwrap();
+    }
+
+    Ok(HttpResponse::Ok().content_type(ContentType::html()).body(format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Subscriber attributes</title>
+</head>
+<body>
+    {msg_html}
+    <table>
+        <tr><th>Key</th><th>Type</th><th></th></tr>
+        {rows_html}
+    </table>
+    <form action="/admin/attributes" method="post">
+        {csrf_field}
+        <label>Key
+            <input
+                type="text"
+                placeholder="e.g. company"
+                name="key"
+            >
+        </label>
+        <label>Type
+            <select name="value_type">
+                <option value="string">Text</option>
+                <option value="number">Number</option>
+                <option value="boolean">Yes/No</option>
+            </select>
+        </label>
+        <button type="submit">Add attribute</button>
+    </form>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        msg_html = msg_html,
+        rows_html = rows_html,
+        csrf_field = csrf_token.form_field(),
+    )))
+}
@@ -0,0 +1,25 @@
+mod get;
+mod post;
+
+pub use get::*;
+pub use post::*;
+
+use crate::domain::{AttributeSchema, AttributeType};
+use crate::startup::DbConnectionKind;
+
+#[tracing::instrument(name = "Loading the subscriber attribute schema", skip(database))]
+pub async fn get_attribute_schema(
+    database: &DbConnectionKind
+) -> Result<AttributeSchema, sqlx::Error> {
+    let rows = sqlx::query!("SELECT key, value_type FROM subscriber_attribute_definitions")
+        .fetch_all(database)
+        .await?;
+    let definitions = rows
+        .into_iter()
+        .filter_map(|row| {
+            AttributeType::parse(&row.value_type)
+                .map(|value_type| (row.key, value_type))
+                .ok()
+        });
+    Ok(AttributeSchema::new(definitions))
+}
@@ -0,0 +1,83 @@
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use chrono::Utc;
+
+use crate::domain::{AttributeKey, AttributeType};
+use crate::startup::DbConnectionKind;
+use crate::utils::{e500, see_other};
+
+#[derive(serde::Deserialize)]
+pub struct AttributeFormData {
+    key: String,
+    value_type: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct DeleteAttributeFormData {
+    key: String,
+}
+
+#[tracing::instrument(
+name = "Adding a subscriber attribute",
+skip(form, database)
+)]
+pub async fn add_attribute(
+    form: web::Form<AttributeFormData>,
+    database: web::Data<DbConnectionKind>
+) -> Result<HttpResponse, actix_web::Error> {
+    let AttributeFormData { key, value_type } = form.0;
+    let (key, value_type) = match (AttributeKey::parse(key), AttributeType::parse(&value_type)) {
+        (Ok(key), Ok(value_type)) => (key, value_type),
+        (Err(e), _) | (_, Err(e)) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/attributes"));
+        }
+    };
+
+    let inserted = sqlx::query!(
+        r#"
+        INSERT INTO subscriber_attribute_definitions (key, value_type, created_at)
+        VALUES ($1, $2, $3)
+        ON CONFLICT DO NOTHING
+        "#,
+        key.as_ref(),
+        value_type.as_str(),
+        Utc::now()
+    )
+        .execute(database.get_ref())
+        .await
+        .context("Failed to store subscriber attribute definition")
+        .map_err(e500)?
+        .rows_affected();
+
+    if inserted == 0 {
+        FlashMessage::error(format!("An attribute called {} already exists.", key.as_ref())).send();
+    } else {
+        FlashMessage::info(format!("The {} attribute has been added.", key.as_ref())).send();
+    }
+    Ok(see_other("/admin/attributes"))
+}
+
+// Values already stored on subscribers are kept, so re-adding the definition makes
+// them available again.
+#[tracing::instrument(
+name = "Removing a subscriber attribute",
+skip(form, database)
+)]
+pub async fn delete_attribute(
+    form: web::Form<DeleteAttributeFormData>,
+    database: web::Data<DbConnectionKind>
+) -> Result<HttpResponse, actix_web::Error> {
+    sqlx::query!(
+        "DELETE FROM subscriber_attribute_definitions WHERE key = $1",
+        form.0.key
+    )
+        .execute(database.get_ref())
+        .await
+        .context("Failed to delete subscriber attribute definition")
+        .map_err(e500)?;
+
+    FlashMessage::info("The attribute has been removed.").send();
+    Ok(see_other("/admin/attributes"))
+}
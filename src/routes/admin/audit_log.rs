@@ -0,0 +1,186 @@
+use actix_web::{HttpResponse, web};
+use actix_web::http::header::ContentType;
+use anyhow::Context;
+use chrono::{DateTime, Duration, Utc};
+use std::fmt::Write;
+
+use crate::audit_log::AuditAction;
+use crate::routes::admin::export::parse_date;
+use crate::startup::DbConnectionKind;
+use crate::utils::{e400, e500};
+
+const PAGE_SIZE: i64 = 50;
+
+#[derive(serde::Deserialize)]
+pub struct AuditLogParameters {
+    #[serde(default)]
+    action: String,
+    // The username of whoever did it.
+    #[serde(default)]
+    actor: String,
+    from: Option<String>,
+    to: Option<String>,
+    #[serde(default = "first_page")]
+    page: i64,
+}
+
+fn first_page() -> i64 {
+    1
+}
+
+struct AuditEventRow {
+    occurred_at: DateTime<Utc>,
+    actor: Option<String>,
+    action: String,
+    target: Option<String>,
+    client_ip: String,
+    user_agent: Option<String>,
+}
+
+pub async fn audit_log(
+    params: web::Query<AuditLogParameters>,
+    database: web::Data<DbConnectionKind>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let params = params.0;
+    if !params.action.is_empty() {
+        AuditAction::parse(&params.action).map_err(e400)?;
+    }
+    let from = parse_date(params.from.clone()).map_err(e400)?;
+    // The end date is inclusive, so the range runs until the start of the next day.
+    let to = parse_date(params.to.clone()).map_err(e400)?
+        .map(|date| date + Duration::days(1));
+    let page = params.page.max(1);
+
+    // Failed logins have no actor, only the username that was tried as their target,
+    // so filtering by actor matches on that too.
+    let total = sqlx::query!(
+        r#"
+        SELECT COUNT(*) AS "count!"
+        FROM audit_log a
+        LEFT JOIN users u ON u.user_id = a.actor_id
+        WHERE ($1 = '' OR a.action = $1)
+        AND ($2 = '' OR u.username = $2 OR (a.actor_id IS NULL AND a.action = $5 AND a.target = $2))
+        AND ($3::timestamptz IS NULL OR a.occurred_at >= $3)
+        AND ($4::timestamptz IS NULL OR a.occurred_at < $4)
+        "#,
+        params.action,
+        params.actor.trim(),
+        from,
+        to,
+        AuditAction::LoginFailed.as_str()
+    )
+        .fetch_one(database.get_ref())
+        .await
+        .context("Failed to count audit events")
+        .map_err(e500)?
+        .count;
+    let events = sqlx::query_as!(
+        AuditEventRow,
+        r#"
+        SELECT a.occurred_at, u.username AS "actor?", a.action, a.target, a.client_ip, a.user_agent
+        FROM audit_log a
+        LEFT JOIN users u ON u.user_id = a.actor_id
+        WHERE ($1 = '' OR a.action = $1)
+        AND ($2 = '' OR u.username = $2 OR (a.actor_id IS NULL AND a.action = $5 AND a.target = $2))
+        AND ($3::timestamptz IS NULL OR a.occurred_at >= $3)
+        AND ($4::timestamptz IS NULL OR a.occurred_at < $4)
+        ORDER BY a.occurred_at DESC, a.id
+        LIMIT $6 OFFSET $7
+        "#,
+        params.action,
+        params.actor.trim(),
+        from,
+        to,
+        AuditAction::LoginFailed.as_str(),
+        PAGE_SIZE,
+        (page - 1).saturating_mul(PAGE_SIZE)
+    )
+        .fetch_all(database.get_ref())
+        .await
+        .context("Failed to retrieve audit events")
+        .map_err(e500)?;
+
+    let mut rows_html = String::new();
+    for event in &events {
+        writeln!(
+            rows_html,
+            r#"<tr>
+            <td>{occurred_at}</td>
+            <td>{actor}</td>
+            <td>{action}</td>
+            <td>{target}</td>
+            <td>{client_ip}</td>
+            <td>{user_agent}</td>
+        </tr>"#,
+            occurred_at = event.occurred_at.format("%Y-%m-%d %H:%M:%S"),
+            actor = htmlescape::encode_minimal(event.actor.as_deref().unwrap_or("-")),
+            action = htmlescape::encode_minimal(&event.action),
+            target = htmlescape::encode_minimal(event.target.as_deref().unwrap_or("")),
+            client_ip = htmlescape::encode_minimal(&event.client_ip),
+            user_agent = htmlescape::encode_minimal(event.user_agent.as_deref().unwrap_or("")),
+        ).unwrap();
+    }
+
+    let last_page = ((total + PAGE_SIZE - 1) / PAGE_SIZE).max(1);
+    let mut pagination_html = format!("<p>Page {} of {} ({} events)", page, last_page, total);
+    if page > 1 {
+        write!(pagination_html, r#" <a href="{}">Previous</a>"#, page_link(&params, page - 1)).unwrap();
+    }
+    if page < last_page {
+        write!(pagination_html, r#" <a href="{}">Next</a>"#, page_link(&params, page + 1)).unwrap();
+    }
+    pagination_html.push_str("</p>");
+
+    let mut action_options = String::from(r#"<option value="">Any action</option>"#);
+    for action in AuditAction::ALL {
+        let selected = if action.as_str() == params.action { "selected" } else { "" };
+        write!(action_options, r#"<option value="{0}" {1}>{0}</option>"#, action, selected).unwrap();
+    }
+
+    Ok(HttpResponse::Ok().content_type(ContentType::html()).body(format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Audit log</title>
+</head>
+<body>
+    <form action="/admin/audit-log" method="get">
+        <select name="action">{action_options}</select>
+        <input
+            type="text"
+            placeholder="Username"
+            name="actor"
+            value="{actor}"
+        >
+        from <input type="date" name="from" value="{from}">
+        to <input type="date" name="to" value="{to}">
+        <button type="submit">Filter</button>
+    </form>
+    <table>
+        <tr><th>When (UTC)</th><th>Who</th><th>Action</th><th>Target</th><th>IP</th><th>User agent</th></tr>
+        {rows_html}
+    </table>
+    {pagination_html}
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        action_options = action_options,
+        actor = htmlescape::encode_attribute(&params.actor),
+        from = htmlescape::encode_attribute(params.from.as_deref().unwrap_or("")),
+        to = htmlescape::encode_attribute(params.to.as_deref().unwrap_or("")),
+        rows_html = rows_html,
+        pagination_html = pagination_html,
+    )))
+}
+
+fn page_link(params: &AuditLogParameters, page: i64) -> String {
+    htmlescape::encode_attribute(&format!(
+        "/admin/audit-log?action={}&actor={}&from={}&to={}&page={}",
+        urlencoding::encode(&params.action),
+        urlencoding::encode(&params.actor),
+        urlencoding::encode(params.from.as_deref().unwrap_or("")),
+        urlencoding::encode(params.to.as_deref().unwrap_or("")),
+        page
+    ))
+}
@@ -1,27 +1,15 @@
-use actix_web::{HttpResponse, web};
-use uuid::Uuid;
-use crate::startup::DbConnectionKind;
-use anyhow::Context;
-use crate::session_state::TypedSession;
-use reqwest::header::LOCATION;
-use crate::utils::e500;
+use actix_web::HttpResponse;
+use crate::authentication::{AuthenticatedUser, CsrfToken};
+use crate::domain::UserRole;
 
-pub async fn admin_dashboard(
-    session: TypedSession,
-    database: web::Data<DbConnectionKind>
-) -> Result<HttpResponse, actix_web::Error> {
-    let username = if let Some(user_id) = session
-        .get_user_id()
-        .map_err(e500)?
-    {
-        get_username(user_id, &database).await.map_err(e500)?
+pub async fn admin_dashboard(user: AuthenticatedUser, csrf_token: CsrfToken) -> HttpResponse {
+    let manage_users = if user.role == UserRole::Owner {
+        r#"<li><a href="/admin/users">Manage users</a></li>
+                    <li><a href="/admin/audit-log">Audit log</a></li>"#
     } else {
-        return Ok(HttpResponse::SeeOther()
-            .insert_header((LOCATION, "/login"))
-            .finish()
-        )
+        ""
     };
-    Ok(HttpResponse::Ok()
+    HttpResponse::Ok()
         .body(format!(
             r#"
             <!DOCTYPE html>
@@ -31,40 +19,53 @@ pub async fn admin_dashboard(
                 <title>Admin dashboard</title>
             </head>
             <body>
-                <p>Welcome {}!</p>
+                <p>Welcome {username}! You are signed in as {role}.</p>
                 <p>Available actions:</p>
                 <ol>
                     <li><a href="/admin/password">Change password</a></li>
+                    <li><a href="/admin/two-factor">Two-factor authentication</a></li>
+                    <li><a href="/admin/api-tokens">API tokens</a></li>
+                    <li><a href="/admin/sessions">Sessions</a></li>
                     <li>
                         <a href="javascript:document.logoutForm.submit()">Logout</a>
                         <form name="logoutForm" action="/admin/logout" method="post" hidden>
+                            {csrf_field}
                             <input hidden type="submit" value="Logout">
                         </form>
                     </li>
                     <li><a href="/admin/newsletter">Send a newsletter issue</a></li>
+                    <li><a href="/admin/subscribers">Manage subscribers</a></li>
+                    <li><a href="/admin/attributes">Manage subscriber attributes</a></li>
+                    <li><a href="/admin/subscribers/import">Import subscribers</a></li>
+                    <li><a href="/admin/welcome-sequence">Manage the welcome sequence</a></li>
+                    {manage_users}
+                    <li>
+                        <form action="/admin/subscribers/export" method="get">
+                            Export subscribers as
+                            <select name="format">
+                                <option value="csv">CSV</option>
+                                <option value="json">JSON</option>
+                            </select>
+                            <select name="status">
+                                <option value="">with any status</option>
+                                <option value="confirmed">confirmed</option>
+                                <option value="pending_confirmation">pending confirmation</option>
+                                <option value="unsubscribed">unsubscribed</option>
+                                <option value="bounced">bounced</option>
+                                <option value="complained">complained</option>
+                            </select>
+                            who signed up from <input type="date" name="subscribed_from">
+                            to <input type="date" name="subscribed_to">
+                            <button type="submit">Export</button>
+                        </form>
+                    </li>
                 </ol>
             </body>
             </html>
             "#,
-            username
-        )))
+            username = htmlescape::encode_minimal(&user.username),
+            role = user.role,
+            manage_users = manage_users,
+            csrf_field = csrf_token.form_field(),
+        ))
 }
-
-#[tracing::instrument(name = "Get username", skip(database))]
-pub async fn get_username(
-    user_id: Uuid,
-    database: &DbConnectionKind
-) -> Result<String, anyhow::Error> {
-    let row = sqlx::query!(
-        r#"
-        SELECT username
-        FROM users
-        WHERE user_id = $1
-        "#,
-        user_id
-    )
-        .fetch_one(database)
-        .await
-        .context("Failed to get username")?;
-    Ok(row.username)
-}
\ No newline at end of file
@@ -0,0 +1,224 @@
+use actix_web::{HttpResponse, web};
+use actix_web::web::Bytes;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use futures_util::StreamExt;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use crate::domain::SubscriptionStatus;
+use crate::startup::DbConnectionKind;
+use crate::utils::e400;
+
+// Rows buffered between the database cursor and a slow client.
+const BUFFERED_CHUNKS: usize = 64;
+
+#[derive(serde::Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+impl Default for ExportFormat {
+    fn default() -> Self {
+        Self::Csv
+    }
+}
+
+// Dates are taken as strings so that the empty values submitted by a form are
+// treated as "no filter".
+#[derive(serde::Deserialize)]
+pub struct ExportParameters {
+    #[serde(default)]
+    format: ExportFormat,
+    status: Option<String>,
+    subscribed_from: Option<String>,
+    subscribed_to: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+struct ExportedSubscriber {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+    paused_until: Option<DateTime<Utc>>,
+    attributes: serde_json::Value,
+}
+
+#[tracing::instrument(
+name = "Exporting subscribers",
+skip(params, database)
+)]
+pub async fn export_subscribers(
+    params: web::Query<ExportParameters>,
+    database: web::Data<DbConnectionKind>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let ExportParameters { format, status, subscribed_from, subscribed_to } = params.0;
+    let status = status.filter(|status| !status.is_empty());
+    if let Some(status) = &status {
+        SubscriptionStatus::parse(status).map_err(e400)?;
+    }
+    let subscribed_from = parse_date(subscribed_from).map_err(e400)?;
+    // The end date is inclusive, so the range runs until the start of the next day.
+    let subscribed_to = parse_date(subscribed_to).map_err(e400)?
+        .map(|date| date + Duration::days(1));
+
+    let (sender, receiver) = mpsc::channel::<Result<Bytes, sqlx::Error>>(BUFFERED_CHUNKS);
+    let database = database.get_ref().clone();
+    actix_web::rt::spawn(async move {
+        let mut rows = sqlx::query_as!(
+            ExportedSubscriber,
+            r#"
+            SELECT id, email, name, status, subscribed_at, paused_until, attributes
+            FROM subscriptions
+            WHERE ($1::text IS NULL OR status = $1)
+            AND ($2::timestamptz IS NULL OR subscribed_at >= $2)
+            AND ($3::timestamptz IS NULL OR subscribed_at < $3)
+            ORDER BY subscribed_at, id
+            "#,
+            status,
+            subscribed_from,
+            subscribed_to
+        )
+            .fetch(&database);
+
+        if sender.send(Ok(header(format))).await.is_err() {
+            return;
+        }
+        let mut is_first = true;
+        while let Some(row) = rows.next().await {
+            let chunk = match row {
+                Ok(subscriber) => Ok(encode(format, &subscriber, is_first)),
+                Err(e) => {
+                    // The response has already started, so all we can do is cut it short.
+                    tracing::error!(error.cause_chain = ?e, "Failed to read subscribers while exporting");
+                    Err(e)
+                }
+            };
+            let is_error = chunk.is_err();
+            // The client has gone away.
+            if sender.send(chunk).await.is_err() || is_error {
+                return;
+            }
+            is_first = false;
+        }
+        let _ = sender.send(Ok(footer(format))).await;
+    });
+
+    let body = futures_util::stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|chunk| (chunk, receiver))
+    });
+    let (content_type, file_name) = match format {
+        ExportFormat::Csv => ("text/csv; charset=utf-8", "subscribers.csv"),
+        ExportFormat::Json => ("application/json", "subscribers.json"),
+    };
+    Ok(HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header(("Content-Disposition", format!("attachment; filename=\"{}\"", file_name)))
+        .streaming(body))
+}
+
+pub fn parse_date(date: Option<String>) -> Result<Option<DateTime<Utc>>, String> {
+    match date.as_deref().map(str::trim) {
+        None | Some("") => Ok(None),
+        Some(date) => NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .map(|date| Some(DateTime::from_utc(date.and_hms(0, 0, 0), Utc)))
+            .map_err(|_| format!("{} is not a date in the YYYY-MM-DD format", date)),
+    }
+}
+
+fn header(format: ExportFormat) -> Bytes {
+    match format {
+        ExportFormat::Csv => Bytes::from_static(b"id,email,name,status,subscribed_at,paused_until,attributes\n"),
+        ExportFormat::Json => Bytes::from_static(b"["),
+    }
+}
+
+fn footer(format: ExportFormat) -> Bytes {
+    match format {
+        ExportFormat::Csv => Bytes::new(),
+        ExportFormat::Json => Bytes::from_static(b"]"),
+    }
+}
+
+fn encode(format: ExportFormat, subscriber: &ExportedSubscriber, is_first: bool) -> Bytes {
+    match format {
+        ExportFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(Vec::new());
+            writer
+                .write_record(&[
+                    subscriber.id.to_string(),
+                    escape_formula(&subscriber.email),
+                    escape_formula(&subscriber.name),
+                    subscriber.status.clone(),
+                    subscriber.subscribed_at.to_rfc3339(),
+                    subscriber.paused_until.map(|t| t.to_rfc3339()).unwrap_or_default(),
+                    escape_formula(&subscriber.attributes.to_string()),
+                ])
+                .expect("Writing to a Vec cannot fail");
+            Bytes::from(writer.into_inner().expect("Writing to a Vec cannot fail"))
+        }
+        ExportFormat::Json => {
+            let mut chunk = if is_first { Vec::new() } else { vec![b','] };
+            serde_json::to_writer(&mut chunk, subscriber).expect("Subscribers are always serializable");
+            Bytes::from(chunk)
+        }
+    }
+}
+
+// Spreadsheets run cells starting with these as formulas, and names are chosen by
+// whoever subscribes. A leading `'` makes them plain text.
+fn escape_formula(cell: &str) -> String {
+    if cell.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{}", cell)
+    } else {
+        cell.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{encode, ExportFormat, ExportedSubscriber};
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    fn exported(name: &str) -> ExportedSubscriber {
+        ExportedSubscriber {
+            id: Uuid::new_v4(),
+            email: "dione@email.com".into(),
+            name: name.into(),
+            status: "confirmed".into(),
+            subscribed_at: Utc::now(),
+            paused_until: None,
+            attributes: serde_json::json!({}),
+        }
+    }
+
+    fn csv_name(name: &str) -> String {
+        let row = encode(ExportFormat::Csv, &exported(name), true);
+        let mut reader = csv::ReaderBuilder::new().has_headers(false).from_reader(row.as_ref());
+        reader.records().next().unwrap().unwrap()[2].to_string()
+    }
+
+    #[test]
+    fn cells_that_spreadsheets_would_run_as_formulas_are_escaped() {
+        for name in ["=HYPERLINK(\"http://evil.example\")", "+1", "-1", "@SUM(A1)", "\tTab", "\rReturn"] {
+            assert_eq!(csv_name(name), format!("'{}", name));
+        }
+    }
+
+    #[test]
+    fn other_cells_are_left_alone() {
+        assert_eq!(csv_name("Dione"), "Dione");
+        assert_eq!(csv_name("Dione = Moon"), "Dione = Moon");
+    }
+
+    #[test]
+    fn json_exports_are_not_escaped() {
+        let row = encode(ExportFormat::Json, &exported("=1+1"), true);
+        let subscriber: serde_json::Value = serde_json::from_slice(&row).unwrap();
+        assert_eq!(subscriber["name"], "=1+1");
+    }
+}
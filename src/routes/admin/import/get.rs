@@ -0,0 +1,8 @@
+use actix_web::HttpResponse;
+
+use crate::authentication::CsrfToken;
+use crate::routes::admin::import::{import_page, ImportMode};
+
+pub async fn import_form(csrf_token: CsrfToken) -> HttpResponse {
+    import_page(&csrf_token, &[], &[], "", ImportMode::SendConfirmation, "")
+}
@@ -0,0 +1,131 @@
+mod get;
+mod post;
+
+pub use get::*;
+pub use post::*;
+
+use actix_web::HttpResponse;
+use actix_web::http::header::ContentType;
+use std::fmt::Write;
+
+use crate::authentication::CsrfToken;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImportMode {
+    // The admin vouches for consent, so no confirmation email is sent.
+    Confirmed,
+    SendConfirmation,
+}
+
+impl ImportMode {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "confirmed" => Ok(Self::Confirmed),
+            "send_confirmation" => Ok(Self::SendConfirmation),
+            other => Err(format!("{} is not a supported import mode", other)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Confirmed => "confirmed",
+            Self::SendConfirmation => "send_confirmation",
+        }
+    }
+}
+
+pub struct ReportRow {
+    pub line: u64,
+    pub email: String,
+    pub outcome: String,
+}
+
+// The import page is re-rendered with the submitted values after a preview or an
+// import, so that a large paste does not have to be repeated.
+fn import_page(
+    csrf_token: &CsrfToken,
+    messages: &[String],
+    report: &[ReportRow],
+    csv: &str,
+    mode: ImportMode,
+    consent_note: &str,
+) -> HttpResponse {
+    let mut msg_html = String::new();
+    for message in messages {
+        writeln!(msg_html, "<p><i>{}</i></p>", htmlescape::encode_minimal(message)).unwrap();
+    }
+
+    let mut report_html = String::new();
+    if !report.is_empty() {
+        report_html.push_str("<table>\n        <tr><th>Line</th><th>Email</th><th>Outcome</th></tr>\n");
+        for row in report {
+            writeln!(
+                report_html,
+                "        <tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+                row.line,
+                htmlescape::encode_minimal(&row.email),
+                htmlescape::encode_minimal(&row.outcome),
+            ).unwrap();
+        }
+        report_html.push_str("    </table>");
+    }
+
+    let checked = |option: ImportMode| if option == mode { "checked" } else { "" };
+    HttpResponse::Ok().content_type(ContentType::html()).body(format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Import subscribers</title>
+</head>
+<body>
+    {msg_html}
+    {report_html}
+    <form action="/admin/subscribers/import" method="post">
+        {csrf_field}
+        <label>CSV with a header row containing <code>email</code> and <code>name</code>
+            <br/>
+            <textarea
+                rows=20
+                cols=80
+                placeholder="email,name"
+                name="csv"
+            >{csv}</textarea>
+        </label>
+        <br>
+        <label>
+            <input type="radio" name="mode" value="send_confirmation" {send_confirmation}>
+            Send a confirmation email to every new subscriber
+        </label>
+        <br>
+        <label>
+            <input type="radio" name="mode" value="confirmed" {confirmed}>
+            Import as confirmed
+        </label>
+        <br>
+        <label>How was consent obtained? (required when importing as confirmed)
+            <br/>
+            <input
+                type="text"
+                size=80
+                placeholder="e.g. Opted in through our previous provider"
+                name="consent_note"
+                value="{consent_note}"
+            >
+        </label>
+        <br>
+        <button type="submit" name="action" value="preview">Preview</button>
+        <button type="submit" name="action" value="import">Import</button>
+    </form>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        msg_html = msg_html,
+        report_html = report_html,
+        csrf_field = csrf_token.form_field(),
+        csv = htmlescape::encode_minimal(csv),
+        send_confirmation = checked(ImportMode::SendConfirmation),
+        confirmed = checked(ImportMode::Confirmed),
+        consent_note = htmlescape::encode_attribute(consent_note),
+    ))
+}
@@ -0,0 +1,301 @@
+use actix_web::{HttpResponse, web};
+use anyhow::Context;
+use chrono::Utc;
+use sqlx::{Postgres, Transaction};
+use std::collections::HashSet;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::authentication::{AuthenticatedUser, CsrfToken};
+use crate::domain::{
+    parse_import, EmailDomainPolicy, ImportedSubscriber, NewSubscriber, SubscriberAttributes, SubscriptionStatus,
+};
+use crate::email_client::EmailClient;
+use crate::i18n::Locale;
+use crate::routes::admin::import::{import_page, ImportMode, ReportRow};
+use crate::routes::subscriptions::{generate_subscription_token, send_confirmation_email, store_token};
+use crate::routes::subscriptions_preferences::preferences_link;
+use crate::startup::{ApplicationBaseUrl, DbConnectionKind, HmacSecret};
+use crate::subscription_lifecycle::record_initial_status;
+use crate::utils::e500;
+
+#[derive(serde::Deserialize)]
+pub struct ImportFormData {
+    csv: String,
+    mode: String,
+    #[serde(default)]
+    consent_note: String,
+    // Which button was pressed: anything but `import` only previews the import.
+    #[serde(default)]
+    action: String,
+}
+
+#[tracing::instrument(
+name = "Importing subscribers",
+skip(form, user, csrf_token, database, email_client, base_url, hmac_secret, domain_policy),
+fields(user_id = %user.user_id, dry_run = tracing::field::Empty)
+)]
+pub async fn import_subscribers(
+    form: web::Form<ImportFormData>,
+    user: AuthenticatedUser,
+    csrf_token: CsrfToken,
+    database: web::Data<DbConnectionKind>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    hmac_secret: web::Data<HmacSecret>,
+    domain_policy: web::Data<EmailDomainPolicy>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let ImportFormData { csv, mode, consent_note, action } = form.0;
+    let dry_run = action != "import";
+    tracing::Span::current().record("dry_run", &dry_run);
+
+    let mode = match ImportMode::parse(&mode) {
+        Ok(mode) => mode,
+        Err(e) => return Ok(import_page(&csrf_token, &[e], &[], &csv, ImportMode::SendConfirmation, &consent_note)),
+    };
+    let consent_note = consent_note.trim();
+    if mode == ImportMode::Confirmed && consent_note.is_empty() {
+        let error = "Describe how consent was obtained to import subscribers as confirmed.".to_string();
+        return Ok(import_page(&csrf_token, &[error], &[], &csv, mode, consent_note));
+    }
+    let rows = match parse_import(&csv) {
+        Ok(rows) => rows,
+        Err(e) => return Ok(import_page(&csrf_token, &[e], &[], &csv, mode, consent_note)),
+    };
+
+    let emails: Vec<String> = rows
+        .iter()
+        .filter_map(|row| row.subscriber.as_ref().ok())
+        .map(|subscriber| subscriber.email.as_ref().to_lowercase())
+        .collect();
+    let existing = get_existing_emails(&database, &emails).await.map_err(e500)?;
+
+    let mut report = Vec::with_capacity(rows.len());
+    let mut to_import = Vec::new();
+    for row in rows {
+        match row.subscriber {
+            Err(e) => report.push(ReportRow { line: row.line, email: String::new(), outcome: e }),
+            Ok(subscriber) if existing.contains(&subscriber.email.as_ref().to_lowercase()) => {
+                report.push(ReportRow {
+                    line: row.line,
+                    email: subscriber.email.to_string(),
+                    outcome: "Already subscribed".into(),
+                })
+            }
+            // The same domains are refused here as on the subscribe form.
+            Ok(subscriber) if domain_policy.check(&subscriber.email).is_err() => {
+                report.push(ReportRow {
+                    line: row.line,
+                    email: subscriber.email.to_string(),
+                    outcome: "Blocked by the email domain policy".into(),
+                })
+            }
+            Ok(subscriber) => to_import.push((row.line, subscriber)),
+        }
+    }
+
+    if dry_run {
+        let outcome = match mode {
+            ImportMode::Confirmed => "Will be imported as confirmed",
+            ImportMode::SendConfirmation => "Will be sent a confirmation email",
+        };
+        let summary = format!(
+            "Preview: {} of {} rows can be imported. Nothing has been saved yet.",
+            to_import.len(),
+            to_import.len() + report.len()
+        );
+        report.extend(to_import.iter().map(|(line, subscriber)| ReportRow {
+            line: *line,
+            email: subscriber.email.to_string(),
+            outcome: outcome.into(),
+        }));
+        report.sort_by_key(|row| row.line);
+        return Ok(import_page(&csrf_token, &[summary], &report, &csv, mode, consent_note));
+    }
+
+    let mut transaction = database
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    let import_id = record_import(&mut transaction, user.user_id, mode, consent_note)
+        .await
+        .map_err(e500)?;
+    let mut confirmations = Vec::new();
+    let mut imported = 0;
+    for (line, subscriber) in to_import {
+        let subscriber_id = insert_imported_subscriber(&mut transaction, &subscriber, mode, import_id)
+            .await
+            .map_err(e500)?;
+        let subscriber_id = match subscriber_id {
+            Some(subscriber_id) => subscriber_id,
+            // Someone subscribed with the same email since we checked.
+            None => {
+                report.push(ReportRow {
+                    line,
+                    email: subscriber.email.to_string(),
+                    outcome: "Already subscribed".into(),
+                });
+                continue;
+            }
+        };
+        imported += 1;
+        match mode {
+            ImportMode::Confirmed => report.push(ReportRow {
+                line,
+                email: subscriber.email.to_string(),
+                outcome: "Imported as confirmed".into(),
+            }),
+            ImportMode::SendConfirmation => {
+                let subscription_token = generate_subscription_token();
+                store_token(&mut transaction, &subscriber_id, &subscription_token)
+                    .await
+                    .context("Failed to store the subscription token of an imported subscriber")
+                    .map_err(e500)?;
+                confirmations.push((line, subscriber, subscriber_id, subscription_token));
+            }
+        }
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the subscriber import")
+        .map_err(e500)?;
+
+    // Sending thousands of emails would keep the request open for minutes, so they
+    // go out in the background once the import is committed. A failed delivery
+    // leaves the subscriber pending, and the email can be resent from the
+    // subscriber list.
+    report.extend(confirmations.iter().map(|(line, subscriber, _, _)| ReportRow {
+        line: *line,
+        email: subscriber.email.to_string(),
+        outcome: "Imported, confirmation email on its way".into(),
+    }));
+    actix_web::rt::spawn(send_import_confirmations(
+        confirmations,
+        email_client.into_inner(),
+        base_url.into_inner(),
+        hmac_secret.into_inner(),
+    ));
+
+    report.sort_by_key(|row| row.line);
+    let summary = format!("Imported {} of {} rows.", imported, report.len());
+    Ok(import_page(&csrf_token, &[summary], &report, "", mode, ""))
+}
+
+type PendingConfirmation = (u64, ImportedSubscriber, Uuid, String);
+
+#[tracing::instrument(
+name = "Sending import confirmation emails",
+skip(confirmations, email_client, base_url, hmac_secret),
+fields(count = confirmations.len())
+)]
+async fn send_import_confirmations(
+    confirmations: Vec<PendingConfirmation>,
+    email_client: Arc<EmailClient>,
+    base_url: Arc<ApplicationBaseUrl>,
+    hmac_secret: Arc<HmacSecret>,
+) {
+    for (line, subscriber, subscriber_id, subscription_token) in confirmations {
+        let new_subscriber = NewSubscriber {
+            email: subscriber.email,
+            name: subscriber.name,
+            attributes: SubscriberAttributes::default(),
+            locale: Locale::default(),
+        };
+        if let Err(e) = send_confirmation_email(
+            &email_client,
+            new_subscriber,
+            &base_url.0,
+            &subscription_token,
+            &preferences_link(&base_url.0, &hmac_secret.0, subscriber_id),
+        ).await {
+            tracing::warn!(
+                error.cause_chain = ?e,
+                line,
+                %subscriber_id,
+                "Failed to send a confirmation email to an imported subscriber"
+            );
+        }
+    }
+}
+
+#[tracing::instrument(name = "Looking up existing subscriber emails", skip(database, emails))]
+async fn get_existing_emails(
+    database: &DbConnectionKind,
+    emails: &[String],
+) -> Result<HashSet<String>, anyhow::Error> {
+    let rows = sqlx::query!(
+        r#"SELECT lower(email) AS "email!" FROM subscriptions WHERE lower(email) = ANY($1)"#,
+        emails
+    )
+        .fetch_all(database)
+        .await
+        .context("Failed to look up existing subscriber emails")?;
+    Ok(rows.into_iter().map(|row| row.email).collect())
+}
+
+#[tracing::instrument(name = "Recording a subscriber import", skip(transaction, consent_note))]
+async fn record_import(
+    transaction: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    mode: ImportMode,
+    consent_note: &str,
+) -> Result<Uuid, anyhow::Error> {
+    let import_id = Uuid::new_v4();
+    let consent_note = Some(consent_note).filter(|note| !note.is_empty());
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriber_imports (id, imported_by, imported_at, mode, consent_note)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        import_id,
+        user_id,
+        Utc::now(),
+        mode.as_str(),
+        consent_note
+    )
+        .execute(transaction)
+        .await
+        .context("Failed to record the subscriber import")?;
+    Ok(import_id)
+}
+
+// Returns `None` if a subscriber with the same email already exists.
+async fn insert_imported_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber: &ImportedSubscriber,
+    mode: ImportMode,
+    import_id: Uuid,
+) -> Result<Option<Uuid>, anyhow::Error> {
+    let status = match mode {
+        ImportMode::Confirmed => SubscriptionStatus::Confirmed,
+        ImportMode::SendConfirmation => SubscriptionStatus::PendingConfirmation,
+    };
+    let row = sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status, import_id)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT DO NOTHING
+        RETURNING id
+        "#,
+        Uuid::new_v4(),
+        subscriber.email.as_ref(),
+        subscriber.name.as_ref(),
+        Utc::now(),
+        status.as_str(),
+        import_id
+    )
+        .fetch_optional(&mut *transaction)
+        .await
+        .context("Failed to insert an imported subscriber")?;
+    match row {
+        Some(row) => {
+            record_initial_status(transaction, row.id, status)
+                .await
+                .context("Failed to record the status of an imported subscriber")?;
+            Ok(Some(row.id))
+        }
+        None => Ok(None),
+    }
+}
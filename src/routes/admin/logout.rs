@@ -1,13 +1,24 @@
 use crate::session_state::TypedSession;
-use actix_web::HttpResponse;
+use actix_web::{HttpResponse, web};
+use crate::audit_log::{record_audit_event, AuditAction, RequestOrigin};
+use crate::authentication::{revoke_user_session, AuthenticatedUser};
+use crate::startup::DbConnectionKind;
 use crate::utils::{e500, see_other};
 use actix_web_flash_messages::FlashMessage;
 
-pub async fn logout(session: TypedSession) -> Result<HttpResponse, actix_web::Error> {
-    if session.get_user_id().map_err(e500)?.is_none() {
-        Ok(see_other("/login"))
-    } else {
-        FlashMessage::info("You have successfully logged out.").send();
-        Ok(see_other("/login"))
+pub async fn logout(
+    session: TypedSession,
+    user: AuthenticatedUser,
+    origin: RequestOrigin,
+    database: web::Data<DbConnectionKind>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if let Some(session_id) = session.get_session_id().map_err(e500)? {
+        revoke_user_session(user.user_id, session_id, &database).await.map_err(e500)?;
     }
-}
\ No newline at end of file
+    session.log_out();
+    record_audit_event(&database, Some(user.user_id), AuditAction::Logout, None, &origin)
+        .await
+        .map_err(e500)?;
+    FlashMessage::info("You have successfully logged out.").send();
+    Ok(see_other("/login"))
+}
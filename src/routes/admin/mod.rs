@@ -1,4 +1,13 @@
 pub mod dashboard;
 pub mod password;
 pub mod logout;
-pub mod newsletter;
\ No newline at end of file
+pub mod newsletter;
+pub mod attributes;
+pub mod import;
+pub mod export;
+pub mod subscribers;
+pub mod welcome_sequence;
+pub mod users;pub mod two_factor;
+pub mod audit_log;
+pub mod api_tokens;
+pub mod sessions;
@@ -2,12 +2,15 @@ use actix_web::HttpResponse;
 use actix_web_flash_messages::IncomingFlashMessages;
 use std::fmt::Write;
 
+use crate::authentication::CsrfToken;
+
 pub async fn newsletter_form(
+    csrf_token: CsrfToken,
     flash_messages: IncomingFlashMessages
 ) -> Result<HttpResponse, actix_web::Error> {
     let mut msg_html = String::new();
     for message in flash_messages.iter() {
-        writeln!(msg_html, "<p><i>{}</i></p>", message.content()).unwrap();
+        writeln!(msg_html, "<p><i>{}</i></p>", htmlescape::encode_minimal(message.content())).unwrap();
     }
     let idempotency_key = uuid::Uuid::new_v4().to_string();
     Ok(HttpResponse::Ok()
@@ -23,6 +26,7 @@ pub async fn newsletter_form(
                 {}
                 <p>Create a newsletter:</p>
                 <form action="/admin/newsletter" method="post">
+                    {csrf_field}
                     <label>Title
                         <br/>
                         <input
@@ -52,13 +56,29 @@ pub async fn newsletter_form(
                         ></textarea>
                     </label>
                     <br>
-                    <input hidden type="text" name="idempotency_key" value="{idempotency_key}"
+                    <label>Only send to subscribers whose attribute
+                        <input
+                            type="text"
+                            placeholder="Attribute key (optional)"
+                            name="audience_attribute"
+                        >
+                    </label>
+                    <label>equals
+                        <input
+                            type="text"
+                            placeholder="Attribute value"
+                            name="audience_value"
+                        >
+                    </label>
+                    <br>
+                    <input hidden type="text" name="idempotency_key" value="{idempotency_key}">
                     <button type="submit">Send newsletter</button>
                 </form>
                 <a href="/admin/dashboard">Dashboard</a>
             </body>
                 </html>
             "#,
-            msg_html
+            msg_html,
+            csrf_field = csrf_token.form_field(),
         )))
 }
\ No newline at end of file
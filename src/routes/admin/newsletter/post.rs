@@ -1,23 +1,31 @@
 use actix_web::{HttpResponse, web};
-use crate::startup::DbConnectionKind;
+use crate::startup::{DbConnectionKind, ApplicationBaseUrl, HmacSecret};
+use crate::routes::subscriptions_preferences::preferences_link;
 use std::fmt::Formatter;
 use crate::routes::error_chain_fmt;
 use crate::email_client::EmailClient;
 use crate::domain::subscriber_email::SubscriberEmail;
+use crate::authentication::{AuthenticatedUser, AuthenticationMethod};
+use crate::audit_log::{record_audit_event, AuditAction, RequestOrigin};
+use crate::domain::{SubscriberAttributes, SubscriptionStatus};
 use anyhow::Context;
-use crate::session_state::TypedSession;
 use crate::utils::{see_other, e500, e400};
-use crate::routes::admin::dashboard::get_username;
 use validator::HasLen;
 use actix_web_flash_messages::FlashMessage;
-use crate::idempotency::IdempotencyKey;
+use crate::idempotency::{save_response, try_processing, IdempotencyKey, NextAction};
+use uuid::Uuid;
+use chrono::Utc;
 
 #[derive(serde::Deserialize)]
 pub struct BodyData {
     title: String,
     html_content: String,
     text_content: String,
-    idempotency_key: String
+    idempotency_key: String,
+    #[serde(default)]
+    audience_attribute: String,
+    #[serde(default)]
+    audience_value: String,
 }
 
 #[derive(thiserror::Error)]
@@ -36,45 +44,90 @@ impl std::fmt::Debug for PublishError {
 
 #[tracing::instrument(
 name = "Publish a newsletter issue",
-skip(form, database, email_client, session)
+skip(form, database, email_client, base_url, hmac_secret, user, origin)
 fields(username = tracing::field::Empty, user_id = tracing::field::Empty)
 )]
 pub async fn publish_newsletter(
     form: web::Form<BodyData>,
     database: web::Data<DbConnectionKind>,
     email_client: web::Data<EmailClient>,
-    session: TypedSession
+    base_url: web::Data<ApplicationBaseUrl>,
+    hmac_secret: web::Data<HmacSecret>,
+    user: AuthenticatedUser,
+    origin: RequestOrigin,
 ) -> Result<HttpResponse, actix_web::Error> {
-    let BodyData { title, html_content, text_content, idempotency_key } = form.0;
-    let user_id = session.get_user_id().map_err(e500)?;
-    if user_id.is_none() {
-        return Ok(see_other("/login"))
-    }
-    let user_id = user_id.unwrap();
-    let username = get_username(user_id, &database).await.map_err(e500)?;
+    let BodyData {
+        title,
+        html_content,
+        text_content,
+        idempotency_key,
+        audience_attribute,
+        audience_value,
+    } = form.0;
     tracing::Span::current().record(
         "username",
-        &tracing::field::display(&username),
+        &tracing::field::display(&user.username),
     );
     tracing::Span::current().record(
         "user_id",
-        &tracing::field::display(&user_id),
+        &tracing::field::display(&user.user_id),
     );
     let idempotency_key: IdempotencyKey = idempotency_key.try_into().map_err(e400)?;
-    let confirmed_subscribers = get_confirmed_subscribers(&database).await.map_err(e500)?;
+    let transaction = match try_processing(&database, &idempotency_key, user.user_id)
+        .await
+        .map_err(e500)?
+    {
+        NextAction::StartProcessing(transaction) => transaction,
+        NextAction::ReturnSavedResponse(saved_response) => {
+            if user.authenticated_with == AuthenticationMethod::Session {
+                FlashMessage::info("Emails have been sent!").send();
+            }
+            return Ok(saved_response);
+        }
+    };
+    let audience = Audience { attribute: audience_attribute.trim(), value: audience_value.trim() };
+    let confirmed_subscribers = get_confirmed_subscribers(&database, &audience).await.map_err(e500)?;
     tracing::info!("{}", &format!("# confirmed subscribers: {}", confirmed_subscribers.length()));
+    // A failed send does not stop the others: the response is saved against the
+    // idempotency key, so retrying the request would not reach them either.
+    let mut outcome = PublishOutcome::default();
     for subscriber in confirmed_subscribers {
         match subscriber {
             Ok(subscriber) => {
-                email_client.send_email(
+                let preferences_link = preferences_link(&base_url.0, &hmac_secret.0, subscriber.id);
+                let sent = email_client.send_email(
                     &subscriber.email,
                     &title,
-                    &html_content,
-                    &text_content,
-                ).await
-                    .with_context(|| {
-                        format!("Failed to send newsletter issue to {}", subscriber.email)
-                    }).map_err(e500)?;
+                    &format!(
+                        "{}<p><a href=\"{}\">Manage your preferences</a></p>",
+                        personalise(&html_content, &subscriber, htmlescape::encode_minimal),
+                        preferences_link
+                    ),
+                    &format!(
+                        "{}\n\nManage your preferences: {}",
+                        personalise(&text_content, &subscriber, |value| value.to_string()),
+                        preferences_link
+                    ),
+                ).await;
+                if let Err(e) = sent {
+                    tracing::warn!(
+                        error.cause_chain = ?e,
+                        subscriber_id = %subscriber.id,
+                        "Failed to send newsletter issue to a confirmed subscriber"
+                    );
+                    outcome.failed += 1;
+                    continue;
+                }
+                outcome.delivered += 1;
+                // The email is already out, so failing here must not abort the
+                // send: a retry would then email everyone again.
+                if let Err(e) = record_delivery(&database, subscriber.id, &title).await {
+                    tracing::error!(
+                        error.cause_chain = ?e,
+                        subscriber_id = %subscriber.id,
+                        "Failed to record a newsletter delivery"
+                    );
+                }
             }
             Err(error) => {
                 tracing::warn!(
@@ -84,12 +137,89 @@ pub async fn publish_newsletter(
             }
         }
     }
-    FlashMessage::info("Emails have been sent!").send();
-    Ok(see_other("/admin/newsletter"))
+    record_audit_event(&database, Some(user.user_id), AuditAction::NewsletterPublished, Some(&title), &origin)
+        .await
+        .map_err(e500)?;
+    let response = match user.authenticated_with {
+        AuthenticationMethod::Session => {
+            FlashMessage::info(outcome.message()).send();
+            see_other("/admin/newsletter")
+        }
+        AuthenticationMethod::ApiToken => HttpResponse::Ok().json(&outcome),
+    };
+    let response = save_response(transaction, &idempotency_key, user.user_id, response)
+        .await
+        .map_err(e500)?;
+    Ok(response)
+}
+
+// What token clients are told about a published issue.
+#[derive(Default, serde::Serialize)]
+struct PublishOutcome {
+    delivered: u64,
+    failed: u64,
+}
+
+impl PublishOutcome {
+    fn message(&self) -> String {
+        if self.failed == 0 {
+            "Emails have been sent!".into()
+        } else {
+            format!(
+                "Emails have been sent to {} subscribers, but {} could not be sent. Check the logs for details.",
+                self.delivered, self.failed
+            )
+        }
+    }
 }
 
 struct ConfirmedSubscriber {
+    id: Uuid,
     email: SubscriberEmail,
+    name: String,
+    attributes: SubscriberAttributes,
+}
+
+// Restricts an issue to subscribers whose attribute matches a value. An empty
+// attribute sends to everyone.
+#[derive(Debug)]
+struct Audience<'a> {
+    attribute: &'a str,
+    value: &'a str,
+}
+
+// Replaces `{{name}}` and `{{attributes.<key>}}` with the subscriber's details.
+// Attributes the subscriber has not set are replaced with an empty string.
+fn personalise(
+    template: &str,
+    subscriber: &ConfirmedSubscriber,
+    escape: impl Fn(&str) -> String,
+) -> String {
+    let mut personalised = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let end = match rest[start..].find("}}") {
+            Some(end) => start + end,
+            None => break,
+        };
+        personalised.push_str(&rest[..start]);
+        let placeholder = rest[start + 2..end].trim();
+        if placeholder == "name" {
+            personalised.push_str(&escape(&subscriber.name));
+        } else if let Some(key) = placeholder.strip_prefix("attributes.") {
+            let value = match subscriber.attributes.get(key) {
+                Some(serde_json::Value::String(s)) => s.clone(),
+                Some(value) => value.to_string(),
+                None => String::new(),
+            };
+            personalised.push_str(&escape(&value));
+        } else {
+            personalised.push_str(&rest[start..end + 2]);
+        }
+        rest = &rest[end + 2..];
+    }
+    personalised.push_str(rest);
+    personalised
 }
 
 #[tracing::instrument(
@@ -97,18 +227,27 @@ name = "Retrieving CONFIRMED subscribers"
 skip(database)
 )]
 async fn get_confirmed_subscribers(
-    database: &DbConnectionKind
+    database: &DbConnectionKind,
+    audience: &Audience<'_>,
 ) -> Result<Vec<Result<ConfirmedSubscriber, anyhow::Error>>, anyhow::Error> {
     struct Row {
+        id: Uuid,
         email: String,
+        name: String,
+        attributes: serde_json::Value,
     }
     let rows = sqlx::query_as!(
         Row,
         r#"
-        SELECT email
+        SELECT id, email, name, attributes
         FROM subscriptions
-        WHERE status = 'confirmed'
-        "#
+        WHERE status = $1
+        AND (paused_until IS NULL OR paused_until <= now())
+        AND ($2 = '' OR attributes ->> $2 = $3)
+        "#,
+        SubscriptionStatus::Confirmed.as_str(),
+        audience.attribute,
+        audience.value
     )
         .fetch_all(database)
         .await?;
@@ -116,9 +255,38 @@ async fn get_confirmed_subscribers(
     let confirmed_subscribers = rows
         .into_iter()
         .map(|row| match SubscriberEmail::parse(row.email) {
-            Ok(email) => Ok(ConfirmedSubscriber { email }),
+            Ok(email) => Ok(ConfirmedSubscriber {
+                id: row.id,
+                email,
+                name: row.name,
+                attributes: SubscriberAttributes::from_stored(row.attributes),
+            }),
             Err(error) => Err(anyhow::anyhow!(error))
         })
         .collect();
     Ok(confirmed_subscribers)
+}
+
+#[tracing::instrument(
+name = "Recording newsletter delivery",
+skip(database, title)
+)]
+async fn record_delivery(
+    database: &DbConnectionKind,
+    subscriber_id: Uuid,
+    title: &str,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_deliveries (subscriber_id, title, delivered_at)
+        VALUES ($1, $2, $3)
+        "#,
+        subscriber_id,
+        title,
+        Utc::now()
+    )
+        .execute(database)
+        .await
+        .context("Failed to record newsletter delivery")?;
+    Ok(())
 }
\ No newline at end of file
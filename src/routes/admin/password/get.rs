@@ -1,24 +1,20 @@
 use actix_web::HttpResponse;
 use actix_web::http::header::ContentType;
-use crate::session_state::TypedSession;
-use crate::utils::{e500, see_other};
 use actix_web_flash_messages::IncomingFlashMessages;
 use std::fmt::Write;
 
+use crate::authentication::CsrfToken;
+
 pub async fn change_password_form(
-    session: TypedSession,
+    csrf_token: CsrfToken,
     flash_messages: IncomingFlashMessages
-) -> Result<HttpResponse, actix_web::Error> {
-    if session.get_user_id().map_err(e500)?.is_none() {
-        return Ok(see_other("/login"));
-    }
-
+) -> HttpResponse {
     let mut msg_html = String::new();
     for message in flash_messages.iter() {
-        writeln!(msg_html, "<p><i>{}</i></p>", message.content()).unwrap();
+        writeln!(msg_html, "<p><i>{}</i></p>", htmlescape::encode_minimal(message.content())).unwrap();
     }
 
-    Ok(HttpResponse::Ok().content_type(ContentType::html()).body(format!(
+    HttpResponse::Ok().content_type(ContentType::html()).body(format!(
         r#"<!DOCTYPE html>
 <html lang="en">
 <head>
@@ -28,6 +24,7 @@ pub async fn change_password_form(
 <body>
     {errors}
     <form action="/admin/password" method="post">
+        {csrf_field}
         <label>Current password
             <input
                 type="password"
@@ -57,6 +54,7 @@ pub async fn change_password_form(
     <p><a href="/admin/dashboard">&lt;- Back</a></p>
 </body>
 </html>"#,
-        errors = msg_html
-    )))
+        errors = msg_html,
+        csrf_field = csrf_token.form_field(),
+    ))
 }
\ No newline at end of file
@@ -1,14 +1,12 @@
 use actix_web::{HttpResponse, web};
 use secrecy::{Secret, ExposeSecret};
-use crate::session_state::TypedSession;
 use crate::utils::{e500, see_other};
 use actix_web_flash_messages::FlashMessage;
-use crate::routes::admin::dashboard::get_username;
 use crate::startup::DbConnectionKind;
-use crate::authentication::{Credentials, validate_credentials, AuthError};
-
-static MINIMUM_PASSWORD_LENGTH: u8 = 8;
-static MAXIMUM_PASSWORD_LENGTH: u8 = 128;
+use crate::authentication::{AuthenticatedUser, Credentials, validate_credentials, AuthError, PasswordHashing};
+use crate::audit_log::{record_audit_event, AuditAction, RequestOrigin};
+use crate::domain::PasswordPolicy;
+use crate::session_state::TypedSession;
 
 
 #[derive(serde::Deserialize)]
@@ -20,16 +18,13 @@ pub struct FormData {
 
 pub async fn change_password(
     form: web::Form<FormData>,
+    user: AuthenticatedUser,
     session: TypedSession,
+    origin: RequestOrigin,
+    hashing: web::Data<PasswordHashing>,
+    policy: web::Data<PasswordPolicy>,
     database: web::Data<DbConnectionKind>
 ) -> Result<HttpResponse, actix_web::Error> {
-    let user_id = session.get_user_id().map_err(e500)?;
-
-    if user_id.is_none() {
-        return Ok(see_other("/login"));
-    }
-
-    let user_id = user_id.unwrap();
 
     if form.new_password.expose_secret() != form.new_password_check.expose_secret() {
         FlashMessage::error("You entered two different new password - the field values must match")
@@ -37,18 +32,16 @@ pub async fn change_password(
         return Ok(see_other("/admin/password"));
     }
 
-    if !(MINIMUM_PASSWORD_LENGTH..MAXIMUM_PASSWORD_LENGTH).contains(&(form.new_password.expose_secret().chars().count() as u8)) {
-        FlashMessage::error("The new password must be between 12 and 128 characters long")
-            .send();
+    if let Err(e) = policy.check(&form.new_password).await {
+        FlashMessage::error(e).send();
         return Ok(see_other("/admin/password"));
     }
 
-    let username = get_username(user_id, &database).await.map_err(e500)?;
     let credentials = Credentials {
-        username,
+        username: user.username.clone(),
         password: form.0.current_password
     };
-    if let Err(validation_error) = validate_credentials(credentials, &database).await {
+    if let Err(validation_error) = validate_credentials(credentials, &hashing, &database).await {
         return match validation_error {
             AuthError::InvalidCredentials(_) => {
                 FlashMessage::error("The current password is incorrect")
@@ -59,9 +52,25 @@ pub async fn change_password(
         }
     }
 
-    crate::authentication::change_password(user_id, form.0.new_password, &database)
+    let current_session = session
+        .get_session_id()
+        .map_err(e500)?
+        .ok_or_else(|| e500("The session is not in the session index"))?;
+    let revoked = crate::authentication::change_password(
+        user.user_id,
+        form.0.new_password,
+        current_session,
+        &hashing,
+        &database,
+    )
+        .await
+        .map_err(e500)?;
+    record_audit_event(&database, Some(user.user_id), AuditAction::PasswordChanged, None, &origin)
         .await
         .map_err(e500)?;
     FlashMessage::info("Your password has been changed.").send();
+    if revoked > 0 {
+        FlashMessage::info("Your other sessions have been logged out.").send();
+    }
     Ok(see_other("/admin/password"))
 }
\ No newline at end of file
@@ -0,0 +1,78 @@
+use actix_web::{HttpResponse, web};
+use actix_web::http::header::ContentType;
+use actix_web_flash_messages::IncomingFlashMessages;
+use std::fmt::Write;
+
+use crate::authentication::{list_user_sessions, AuthenticatedUser, CsrfToken};
+use crate::session_state::TypedSession;
+use crate::startup::DbConnectionKind;
+use crate::utils::e500;
+
+pub async fn sessions_page(
+    user: AuthenticatedUser,
+    session: TypedSession,
+    csrf_token: CsrfToken,
+    database: web::Data<DbConnectionKind>,
+    flash_messages: IncomingFlashMessages
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for message in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", htmlescape::encode_minimal(message.content())).unwrap();
+    }
+
+    let current_session = session.get_session_id().map_err(e500)?;
+    let sessions = list_user_sessions(user.user_id, &database).await.map_err(e500)?;
+    let mut rows_html = String::new();
+    for listed in sessions {
+        let action = if Some(listed.id) == current_session {
+            "This session".to_string()
+        } else {
+            format!(
+                r#"<form action="/admin/sessions/revoke" method="post">
+                {}
+                <input type="hidden" name="session_id" value="{}">
+                <button type="submit">Log out</button>
+            </form>"#,
+                csrf_token.form_field(),
+                listed.id
+            )
+        };
+        writeln!(
+            rows_html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            htmlescape::encode_minimal(listed.user_agent.as_deref().unwrap_or("Unknown device")),
+            htmlescape::encode_minimal(&listed.client_ip),
+            listed.created_at.format("%Y-%m-%d %H:%M"),
+            listed.last_seen_at.format("%Y-%m-%d %H:%M"),
+            action,
+        )
+            .unwrap();
+    }
+
+    Ok(HttpResponse::Ok().content_type(ContentType::html()).body(format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Sessions</title>
+</head>
+<body>
+    {msg_html}
+    <p>You are logged in on these devices. Log out any you do not recognise, and
+    change your password.</p>
+    <table>
+        <tr><th>Device</th><th>IP address</th><th>Logged in (UTC)</th><th>Last seen (UTC)</th><th></th></tr>
+        {rows_html}
+    </table>
+    <form action="/admin/sessions/revoke-others" method="post">
+        {csrf_field}
+        <button type="submit">Log out all other sessions</button>
+    </form>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        msg_html = msg_html,
+        rows_html = rows_html,
+        csrf_field = csrf_token.form_field(),
+    )))
+}
@@ -0,0 +1,70 @@
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use uuid::Uuid;
+
+use crate::audit_log::{record_audit_event, AuditAction, RequestOrigin};
+use crate::authentication::{revoke_other_user_sessions, revoke_user_session, AuthenticatedUser};
+use crate::session_state::TypedSession;
+use crate::startup::DbConnectionKind;
+use crate::utils::{e500, see_other};
+
+#[derive(serde::Deserialize)]
+pub struct RevokeSessionFormData {
+    session_id: Uuid,
+}
+
+// The current session is ended by logging out, so it cannot be revoked here.
+#[tracing::instrument(
+name = "Revoking a session",
+skip(form, user, session, origin, database),
+fields(user_id = %user.user_id, session_id = %form.session_id)
+)]
+pub async fn revoke_session(
+    form: web::Form<RevokeSessionFormData>,
+    user: AuthenticatedUser,
+    session: TypedSession,
+    origin: RequestOrigin,
+    database: web::Data<DbConnectionKind>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let is_current = session.get_session_id().map_err(e500)? == Some(form.session_id);
+    if is_current || !revoke_user_session(user.user_id, form.session_id, &database).await.map_err(e500)? {
+        FlashMessage::error("The session does not exist or has already ended.").send();
+        return Ok(see_other("/admin/sessions"));
+    }
+    let target = form.session_id.to_string();
+    record_audit_event(&database, Some(user.user_id), AuditAction::SessionRevoked, Some(&target), &origin)
+        .await
+        .map_err(e500)?;
+    FlashMessage::info("The session has been logged out.").send();
+    Ok(see_other("/admin/sessions"))
+}
+
+#[tracing::instrument(
+name = "Revoking all other sessions",
+skip(user, session, origin, database),
+fields(user_id = %user.user_id)
+)]
+pub async fn revoke_other_sessions(
+    user: AuthenticatedUser,
+    session: TypedSession,
+    origin: RequestOrigin,
+    database: web::Data<DbConnectionKind>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let current_session = session
+        .get_session_id()
+        .map_err(e500)?
+        .ok_or_else(|| e500("The session is not in the session index"))?;
+    let revoked = revoke_other_user_sessions(user.user_id, current_session, database.get_ref())
+        .await
+        .map_err(e500)?;
+    if revoked == 0 {
+        FlashMessage::info("You are not logged in anywhere else.").send();
+        return Ok(see_other("/admin/sessions"));
+    }
+    let target = "all other sessions";
+    record_audit_event(&database, Some(user.user_id), AuditAction::SessionRevoked, Some(target), &origin)
+        .await
+        .map_err(e500)?;
+    FlashMessage::info("All other sessions have been logged out.").send();
+    Ok(see_other("/admin/sessions"))
+}
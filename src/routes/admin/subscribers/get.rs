@@ -0,0 +1,230 @@
+use actix_web::{HttpResponse, web};
+use actix_web::http::header::ContentType;
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use std::fmt::Write;
+use uuid::Uuid;
+
+use crate::authentication::CsrfToken;
+use crate::domain::SubscriptionStatus;
+use crate::startup::DbConnectionKind;
+use crate::utils::{e400, e500};
+
+const PAGE_SIZE: i64 = 50;
+
+#[derive(serde::Deserialize)]
+pub struct SubscriberListParameters {
+    #[serde(default)]
+    search: String,
+    #[serde(default)]
+    status: String,
+    #[serde(default)]
+    attribute: String,
+    #[serde(default)]
+    attribute_value: String,
+    #[serde(default = "first_page")]
+    page: i64,
+}
+
+fn first_page() -> i64 {
+    1
+}
+
+struct SubscriberRow {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+}
+
+pub async fn list_subscribers(
+    params: web::Query<SubscriberListParameters>,
+    csrf_token: CsrfToken,
+    database: web::Data<DbConnectionKind>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let params = params.0;
+    if !params.status.is_empty() {
+        SubscriptionStatus::parse(&params.status).map_err(e400)?;
+    }
+    let page = params.page.max(1);
+
+    let mut msg_html = String::new();
+    for message in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", htmlescape::encode_minimal(message.content())).unwrap();
+    }
+
+    let search_pattern = like_pattern(params.search.trim());
+    let total = sqlx::query!(
+        r#"
+        SELECT COUNT(*) AS "count!"
+        FROM subscriptions
+        WHERE ($1 = '%%' OR email ILIKE $1 OR name ILIKE $1)
+        AND ($2 = '' OR status = $2)
+        AND ($3 = '' OR attributes ->> $3 = $4)
+        "#,
+        search_pattern,
+        params.status,
+        params.attribute.trim(),
+        params.attribute_value.trim()
+    )
+        .fetch_one(database.get_ref())
+        .await
+        .context("Failed to count subscribers")
+        .map_err(e500)?
+        .count;
+    let subscribers = sqlx::query_as!(
+        SubscriberRow,
+        r#"
+        SELECT id, email, name, status, subscribed_at
+        FROM subscriptions
+        WHERE ($1 = '%%' OR email ILIKE $1 OR name ILIKE $1)
+        AND ($2 = '' OR status = $2)
+        AND ($3 = '' OR attributes ->> $3 = $4)
+        ORDER BY subscribed_at DESC, id
+        LIMIT $5 OFFSET $6
+        "#,
+        search_pattern,
+        params.status,
+        params.attribute.trim(),
+        params.attribute_value.trim(),
+        PAGE_SIZE,
+        (page - 1).saturating_mul(PAGE_SIZE)
+    )
+        .fetch_all(database.get_ref())
+        .await
+        .context("Failed to retrieve subscribers")
+        .map_err(e500)?;
+
+    let mut rows_html = String::new();
+    for subscriber in &subscribers {
+        writeln!(
+            rows_html,
+            r#"<tr>
+            <td>{email}</td>
+            <td>{name}</td>
+            <td>{status}</td>
+            <td>{subscribed_at}</td>
+            <td>{actions}</td>
+        </tr>"#,
+            email = htmlescape::encode_minimal(&subscriber.email),
+            name = htmlescape::encode_minimal(&subscriber.name),
+            status = subscriber.status,
+            subscribed_at = subscriber.subscribed_at.format("%Y-%m-%d %H:%M"),
+            actions = actions_html(subscriber, &csrf_token),
+        ).unwrap();
+    }
+
+    let last_page = ((total + PAGE_SIZE - 1) / PAGE_SIZE).max(1);
+    let mut pagination_html = format!("<p>Page {} of {} ({} subscribers)", page, last_page, total);
+    if page > 1 {
+        write!(pagination_html, r#" <a href="{}">Previous</a>"#, page_link(&params, page - 1)).unwrap();
+    }
+    if page < last_page {
+        write!(pagination_html, r#" <a href="{}">Next</a>"#, page_link(&params, page + 1)).unwrap();
+    }
+    pagination_html.push_str("</p>");
+
+    let mut status_options = String::from(r#"<option value="">Any status</option>"#);
+    for status in SubscriptionStatus::ALL {
+        let selected = if status.as_str() == params.status { "selected" } else { "" };
+        write!(status_options, r#"<option value="{0}" {1}>{0}</option>"#, status, selected).unwrap();
+    }
+
+    Ok(HttpResponse::Ok().content_type(ContentType::html()).body(format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Subscribers</title>
+</head>
+<body>
+    {msg_html}
+    <form action="/admin/subscribers" method="get">
+        <input
+            type="text"
+            placeholder="Search by email or name"
+            name="search"
+            value="{search}"
+        >
+        <select name="status">{status_options}</select>
+        <input
+            type="text"
+            placeholder="Attribute key"
+            name="attribute"
+            value="{attribute}"
+        >
+        <input
+            type="text"
+            placeholder="Attribute value"
+            name="attribute_value"
+            value="{attribute_value}"
+        >
+        <button type="submit">Search</button>
+    </form>
+    <table>
+        <tr><th>Email</th><th>Name</th><th>Status</th><th>Subscribed at</th><th></th></tr>
+        {rows_html}
+    </table>
+    {pagination_html}
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        msg_html = msg_html,
+        search = htmlescape::encode_attribute(&params.search),
+        status_options = status_options,
+        attribute = htmlescape::encode_attribute(&params.attribute),
+        attribute_value = htmlescape::encode_attribute(&params.attribute_value),
+        rows_html = rows_html,
+        pagination_html = pagination_html,
+    )))
+}
+
+// Matches the search anywhere in the value, with `%` and `_` in the search taken
+// literally.
+fn like_pattern(search: &str) -> String {
+    let escaped = search
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+fn page_link(params: &SubscriberListParameters, page: i64) -> String {
+    htmlescape::encode_attribute(&format!(
+        "/admin/subscribers?search={}&status={}&attribute={}&attribute_value={}&page={}",
+        urlencoding::encode(&params.search),
+        urlencoding::encode(&params.status),
+        urlencoding::encode(&params.attribute),
+        urlencoding::encode(&params.attribute_value),
+        page
+    ))
+}
+
+fn actions_html(subscriber: &SubscriberRow, csrf_token: &CsrfToken) -> String {
+    let action = |path: &str, label: &str| format!(
+        r#"<form action="/admin/subscribers/{path}" method="post" style="display:inline">
+                {csrf_field}
+                <input hidden type="text" name="subscriber_id" value="{id}">
+                <button type="submit">{label}</button>
+            </form>"#,
+        path = path,
+        csrf_field = csrf_token.form_field(),
+        id = subscriber.id,
+        label = label,
+    );
+    let mut actions = String::new();
+    if let Ok(status) = SubscriptionStatus::parse(&subscriber.status) {
+        if status == SubscriptionStatus::PendingConfirmation {
+            actions.push_str(&action("confirm", "Confirm"));
+            actions.push_str(&action("resend", "Resend confirmation"));
+        }
+        if status != SubscriptionStatus::Unsubscribed && status.can_transition_to(SubscriptionStatus::Unsubscribed) {
+            actions.push_str(&action("unsubscribe", "Unsubscribe"));
+        }
+    }
+    actions.push_str(&action("delete", "Delete"));
+    actions
+}
@@ -0,0 +1,175 @@
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use uuid::Uuid;
+
+use crate::domain::{NewSubscriber, SubscriberAttributes, SubscriberName, SubscriptionStatus};
+use crate::domain::subscriber_email::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::i18n::Locale;
+use crate::routes::subscriptions::{generate_subscription_token, send_confirmation_email, store_token};
+use crate::routes::subscriptions_data::erase_subscriber;
+use crate::routes::subscriptions_preferences::preferences_link;
+use crate::startup::{ApplicationBaseUrl, DbConnectionKind, HmacSecret};
+use crate::subscription_lifecycle::{change_status, StatusChangeError};
+use crate::utils::{e500, see_other};
+
+#[derive(serde::Deserialize)]
+pub struct SubscriberActionFormData {
+    subscriber_id: Uuid,
+}
+
+#[tracing::instrument(
+name = "Manually confirming a subscriber",
+skip(form, database),
+fields(subscriber_id = %form.subscriber_id)
+)]
+pub async fn confirm_subscriber(
+    form: web::Form<SubscriberActionFormData>,
+    database: web::Data<DbConnectionKind>,
+) -> Result<HttpResponse, actix_web::Error> {
+    update_status(&database, form.subscriber_id, SubscriptionStatus::Confirmed, "The subscriber has been confirmed.").await
+}
+
+#[tracing::instrument(
+name = "Resending a confirmation email",
+skip(form, database, email_client, base_url, hmac_secret),
+fields(subscriber_id = %form.subscriber_id)
+)]
+pub async fn resend_confirmation(
+    form: web::Form<SubscriberActionFormData>,
+    database: web::Data<DbConnectionKind>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriber_id = form.subscriber_id;
+
+    let subscriber = sqlx::query!(
+        "SELECT email, name, status, locale FROM subscriptions WHERE id = $1",
+        subscriber_id
+    )
+        .fetch_optional(database.get_ref())
+        .await
+        .context("Failed to retrieve subscriber")
+        .map_err(e500)?
+        .filter(|subscriber| subscriber.status == SubscriptionStatus::PendingConfirmation.as_str());
+    let subscriber = match subscriber {
+        Some(subscriber) => subscriber,
+        None => {
+            FlashMessage::error("Only subscribers pending confirmation can be sent a confirmation email.").send();
+            return Ok(see_other("/admin/subscribers"));
+        }
+    };
+    let new_subscriber = match (SubscriberEmail::parse(subscriber.email), SubscriberName::parse(subscriber.name)) {
+        (Ok(email), Ok(name)) => NewSubscriber {
+            email,
+            name,
+            attributes: SubscriberAttributes::default(),
+            locale: Locale::parse(&subscriber.locale).unwrap_or_default(),
+        },
+        (Err(e), _) | (_, Err(e)) => {
+            FlashMessage::error(format!("The stored details are invalid: {}", e)).send();
+            return Ok(see_other("/admin/subscribers"));
+        }
+    };
+
+    let mut transaction = database
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    let subscription_token = generate_subscription_token();
+    store_token(&mut transaction, &subscriber_id, &subscription_token)
+        .await
+        .context("Failed to store subscription token")
+        .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the new subscription token")
+        .map_err(e500)?;
+
+    send_confirmation_email(
+        &email_client,
+        new_subscriber,
+        &base_url.0,
+        &subscription_token,
+        &preferences_link(&base_url.0, &hmac_secret.0, subscriber_id),
+    )
+        .await
+        .context("Failed to resend confirmation email")
+        .map_err(e500)?;
+
+    FlashMessage::info("The confirmation email has been sent again.").send();
+    Ok(see_other("/admin/subscribers"))
+}
+
+#[tracing::instrument(
+name = "Manually unsubscribing a subscriber",
+skip(form, database),
+fields(subscriber_id = %form.subscriber_id)
+)]
+pub async fn unsubscribe_subscriber(
+    form: web::Form<SubscriberActionFormData>,
+    database: web::Data<DbConnectionKind>,
+) -> Result<HttpResponse, actix_web::Error> {
+    update_status(&database, form.subscriber_id, SubscriptionStatus::Unsubscribed, "The subscriber has been unsubscribed.").await
+}
+
+#[tracing::instrument(
+name = "Deleting a subscriber",
+skip(form, database),
+fields(subscriber_id = %form.subscriber_id)
+)]
+pub async fn delete_subscriber(
+    form: web::Form<SubscriberActionFormData>,
+    database: web::Data<DbConnectionKind>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut transaction = database
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    let deleted = erase_subscriber(&mut transaction, form.subscriber_id)
+        .await
+        .context("Failed to delete subscriber")
+        .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit subscriber deletion")
+        .map_err(e500)?;
+
+    if deleted {
+        FlashMessage::info("The subscriber has been deleted.").send();
+    } else {
+        FlashMessage::error("The subscriber no longer exists.").send();
+    }
+    Ok(see_other("/admin/subscribers"))
+}
+
+async fn update_status(
+    database: &DbConnectionKind,
+    subscriber_id: Uuid,
+    status: SubscriptionStatus,
+    success_message: &str,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut transaction = database
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    match change_status(&mut transaction, subscriber_id, status).await {
+        Ok(_) => FlashMessage::info(success_message).send(),
+        Err(e @ StatusChangeError::SubscriberNotFound) |
+        Err(e @ StatusChangeError::NotAllowed { .. }) => FlashMessage::error(e.to_string()).send(),
+        Err(e) => return Err(e500(e).into()),
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit subscription status change")
+        .map_err(e500)?;
+    Ok(see_other("/admin/subscribers"))
+}
@@ -0,0 +1,99 @@
+use actix_web::{HttpResponse, web};
+use actix_web::http::header::ContentType;
+use actix_web_flash_messages::IncomingFlashMessages;
+use qrcode::QrCode;
+use qrcode::render::svg;
+use std::fmt::Write;
+
+use crate::authentication::{get_two_factor_status, totp, AuthenticatedUser, CsrfToken, TwoFactorStatus};
+use crate::startup::DbConnectionKind;
+use crate::utils::e500;
+
+// The name authenticator apps list the account under.
+const ISSUER: &str = "zero2prod";
+
+pub async fn two_factor_page(
+    user: AuthenticatedUser,
+    csrf_token: CsrfToken,
+    database: web::Data<DbConnectionKind>,
+    flash_messages: IncomingFlashMessages
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for message in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", htmlescape::encode_minimal(message.content())).unwrap();
+    }
+
+    let status = get_two_factor_status(user.user_id, &database).await.map_err(e500)?;
+    let body = match status {
+        TwoFactorStatus::Disabled => format!(
+            r#"<p>Two-factor authentication is off.</p>
+    <form action="/admin/two-factor/enroll" method="post">
+        {csrf_field}
+        <button type="submit">Set up two-factor authentication</button>
+    </form>"#,
+            csrf_field = csrf_token.form_field(),
+        ),
+        TwoFactorStatus::Pending { secret } => {
+            let uri = totp::provisioning_uri(&secret, ISSUER, &user.username);
+            let qr_code = QrCode::new(uri.as_bytes())
+                .map_err(e500)?
+                .render::<svg::Color>()
+                .min_dimensions(200, 200)
+                .build();
+            format!(
+                r#"<p>Scan this code with your authenticator app, then enter the code it shows.</p>
+    {qr_code}
+    <p>If you cannot scan it, enter this key instead: <code>{secret}</code></p>
+    <form action="/admin/two-factor/confirm" method="post">
+        {csrf_field}
+        <label>Code
+            <input
+                type="text"
+                inputmode="numeric"
+                autocomplete="one-time-code"
+                placeholder="123456"
+                name="code"
+            >
+        </label>
+        <button type="submit">Turn on two-factor authentication</button>
+    </form>"#,
+                qr_code = qr_code,
+                secret = secret,
+                csrf_field = csrf_token.form_field(),
+            )
+        }
+        TwoFactorStatus::Enabled { remaining_recovery_codes } => format!(
+            r#"<p>Two-factor authentication is on. You have {remaining_recovery_codes} unused recovery codes.</p>
+    <form action="/admin/two-factor/disable" method="post">
+        {csrf_field}
+        <label>Code from your authenticator app, or a recovery code
+            <input
+                type="text"
+                placeholder="123456"
+                name="code"
+            >
+        </label>
+        <button type="submit">Turn off two-factor authentication</button>
+    </form>"#,
+            remaining_recovery_codes = remaining_recovery_codes,
+            csrf_field = csrf_token.form_field(),
+        ),
+    };
+
+    Ok(HttpResponse::Ok().content_type(ContentType::html()).body(format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Two-factor authentication</title>
+</head>
+<body>
+    {msg_html}
+    {body}
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        msg_html = msg_html,
+        body = body,
+    )))
+}
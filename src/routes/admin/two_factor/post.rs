@@ -0,0 +1,115 @@
+use actix_web::{HttpResponse, web};
+use actix_web::http::header::ContentType;
+use actix_web_flash_messages::FlashMessage;
+use std::fmt::Write;
+
+use crate::authentication::{
+    confirm_two_factor_enrollment, start_two_factor_enrollment, verify_second_factor, AuthenticatedUser,
+};
+use crate::audit_log::RequestOrigin;
+use crate::login_throttle::LoginThrottle;
+use crate::startup::DbConnectionKind;
+use crate::utils::{e500, see_other};
+
+#[derive(serde::Deserialize)]
+pub struct CodeFormData {
+    code: String,
+}
+
+#[tracing::instrument(
+name = "Enrolling in two-factor authentication",
+skip(user, database),
+fields(user_id = %user.user_id)
+)]
+pub async fn enroll_two_factor(
+    user: AuthenticatedUser,
+    database: web::Data<DbConnectionKind>,
+) -> Result<HttpResponse, actix_web::Error> {
+    start_two_factor_enrollment(user.user_id, &database).await.map_err(e500)?;
+    Ok(see_other("/admin/two-factor"))
+}
+
+// The recovery codes are shown on this response only: they are stored hashed.
+#[tracing::instrument(
+name = "Confirming two-factor authentication",
+skip(form, user, database),
+fields(user_id = %user.user_id)
+)]
+pub async fn confirm_two_factor(
+    form: web::Form<CodeFormData>,
+    user: AuthenticatedUser,
+    database: web::Data<DbConnectionKind>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let recovery_codes = match confirm_two_factor_enrollment(user.user_id, &form.code, &database)
+        .await
+        .map_err(e500)?
+    {
+        Some(recovery_codes) => recovery_codes,
+        None => {
+            FlashMessage::error("The code is incorrect. Check the time on your device and try again.").send();
+            return Ok(see_other("/admin/two-factor"));
+        }
+    };
+
+    let mut codes_html = String::new();
+    for code in recovery_codes {
+        writeln!(codes_html, "<li><code>{}</code></li>", code).unwrap();
+    }
+    Ok(HttpResponse::Ok().content_type(ContentType::html()).body(format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Recovery codes</title>
+</head>
+<body>
+    <p>Two-factor authentication is on.</p>
+    <p>If you lose your authenticator app, you can log in with one of these recovery codes.
+    Each of them works once. Store them somewhere safe: they will not be shown again.</p>
+    <ul>
+        {codes_html}
+    </ul>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        codes_html = codes_html,
+    )))
+}
+
+// Asking for a code stops someone with a hijacked session from removing the
+// second factor. Codes are throttled under the same key as at login, so that this
+// form does not give the same guesser another set of attempts.
+#[tracing::instrument(
+name = "Disabling two-factor authentication",
+skip(form, user, origin, database, throttle),
+fields(user_id = %user.user_id)
+)]
+pub async fn disable_two_factor(
+    form: web::Form<CodeFormData>,
+    user: AuthenticatedUser,
+    origin: RequestOrigin,
+    database: web::Data<DbConnectionKind>,
+    throttle: web::Data<LoginThrottle>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let account = user.user_id.to_string();
+    let client_ip = &origin.client_ip;
+    match throttle.check(&account, client_ip) {
+        Ok(delay) => tokio::time::sleep(delay).await,
+        Err(reason) => {
+            tracing::warn!(reason = reason.code(), "Refusing a second factor during a lockout");
+            FlashMessage::error("Too many incorrect codes. Please try again later.").send();
+            return Ok(see_other("/admin/two-factor"));
+        }
+    }
+    if !verify_second_factor(user.user_id, &form.code, &database).await.map_err(e500)? {
+        throttle.record_failure(&account, client_ip);
+        FlashMessage::error("The code is incorrect or has already been used.").send();
+        return Ok(see_other("/admin/two-factor"));
+    }
+    throttle.record_success(&account, client_ip);
+    crate::authentication::disable_two_factor(user.user_id, &database)
+        .await
+        .map_err(e500)?;
+    FlashMessage::info("Two-factor authentication has been turned off.").send();
+    Ok(see_other("/admin/two-factor"))
+}
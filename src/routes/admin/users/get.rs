@@ -0,0 +1,134 @@
+use actix_web::{HttpResponse, web};
+use actix_web::http::header::ContentType;
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use std::fmt::Write;
+
+use crate::domain::UserRole;
+use crate::authentication::{AuthenticatedUser, CsrfToken};
+use crate::startup::DbConnectionKind;
+use crate::utils::e500;
+
+pub async fn users_page(
+    user: AuthenticatedUser,
+    csrf_token: CsrfToken,
+    database: web::Data<DbConnectionKind>,
+    flash_messages: IncomingFlashMessages
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for message in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", htmlescape::encode_minimal(message.content())).unwrap();
+    }
+
+    let users = sqlx::query!(
+        "SELECT user_id, username, email, role, active FROM users ORDER BY username"
+    )
+        .fetch_all(database.get_ref())
+        .await
+        .context("Failed to retrieve users")
+        .map_err(e500)?;
+    let mut users_html = String::new();
+    for listed in users {
+        // Owners cannot lock themselves out.
+        let action = if listed.active && listed.user_id != user.user_id {
+            format!(
+                r#"<form action="/admin/users/deactivate" method="post">
+                    {}
+                    <input hidden type="text" name="user_id" value="{}">
+                    <button type="submit">Deactivate</button>
+                </form>"#,
+                csrf_token.form_field(),
+                listed.user_id
+            )
+        } else {
+            String::new()
+        };
+        writeln!(
+            users_html,
+            r#"<tr>
+            <td>{username}</td>
+            <td>{email}</td>
+            <td>{role}</td>
+            <td>{status}</td>
+            <td>{action}</td>
+        </tr>"#,
+            username = htmlescape::encode_minimal(&listed.username),
+            email = htmlescape::encode_minimal(listed.email.as_deref().unwrap_or_default()),
+            role = htmlescape::encode_minimal(&listed.role),
+            status = if listed.active { "active" } else { "deactivated" },
+            action = action,
+        ).unwrap();
+    }
+
+    let invitations = sqlx::query!(
+        r#"
+        SELECT email, role, invited_at
+        FROM user_invitations
+        WHERE accepted_at IS NULL
+        ORDER BY invited_at DESC
+        "#
+    )
+        .fetch_all(database.get_ref())
+        .await
+        .context("Failed to retrieve pending invitations")
+        .map_err(e500)?;
+    let mut invitations_html = String::new();
+    for invitation in invitations {
+        writeln!(
+            invitations_html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+            htmlescape::encode_minimal(&invitation.email),
+            htmlescape::encode_minimal(&invitation.role),
+            invitation.invited_at.format("%Y-%m-%d %H:%M"),
+        ).unwrap();
+    }
+
+    let mut role_options = String::new();
+    for role in UserRole::ALL {
+        writeln!(role_options, r#"<option value="{role}">{role}</option>"#, role = role).unwrap();
+    }
+
+    Ok(HttpResponse::Ok().content_type(ContentType::html()).body(format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Users</title>
+</head>
+<body>
+    {msg_html}
+    <table>
+        <tr><th>Username</th><th>Email</th><th>Role</th><th>Status</th><th></th></tr>
+        {users_html}
+    </table>
+    <h2>Pending invitations</h2>
+    <table>
+        <tr><th>Email</th><th>Role</th><th>Invited at</th></tr>
+        {invitations_html}
+    </table>
+    <form action="/admin/users/invite" method="post">
+        {csrf_field}
+        <label>Email
+            <input
+                type="email"
+                placeholder="Enter their email"
+                name="email"
+            >
+        </label>
+        <label>Role
+            <select name="role">
+                {role_options}
+            </select>
+        </label>
+        <button type="submit">Send invitation</button>
+    </form>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        msg_html = msg_html,
+        users_html = users_html,
+        invitations_html = invitations_html,
+        role_options = role_options,
+        csrf_field = csrf_token.form_field(),
+    )))
+}
@@ -0,0 +1,148 @@
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::authentication::AuthenticatedUser;
+use crate::domain::UserRole;
+use crate::domain::subscriber_email::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::routes::invitations::invitation_link;
+use crate::startup::{ApplicationBaseUrl, DbConnectionKind, HmacSecret};
+use crate::utils::{e500, see_other};
+
+#[derive(serde::Deserialize)]
+pub struct InviteFormData {
+    email: String,
+    role: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct DeactivateFormData {
+    user_id: Uuid,
+}
+
+#[tracing::instrument(
+name = "Inviting a user",
+skip(form, user, database, email_client, base_url, hmac_secret),
+fields(user_id = %user.user_id)
+)]
+pub async fn invite_user(
+    form: web::Form<InviteFormData>,
+    user: AuthenticatedUser,
+    database: web::Data<DbConnectionKind>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let InviteFormData { email, role } = form.0;
+    let (email, role) = match (SubscriberEmail::parse(email), UserRole::parse(&role)) {
+        (Ok(email), Ok(role)) => (email, role),
+        (Err(e), _) | (_, Err(e)) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/users"));
+        }
+    };
+    let existing = sqlx::query!(
+        "SELECT user_id FROM users WHERE lower(email) = lower($1)",
+        email.as_ref()
+    )
+        .fetch_optional(database.get_ref())
+        .await
+        .context("Failed to look up users by email")
+        .map_err(e500)?;
+    if existing.is_some() {
+        FlashMessage::error(format!("There is already a user with {}.", email.as_ref())).send();
+        return Ok(see_other("/admin/users"));
+    }
+
+    let invitation_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO user_invitations (id, email, role, invited_by, invited_at)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        invitation_id,
+        email.as_ref(),
+        role.as_str(),
+        user.user_id,
+        Utc::now()
+    )
+        .execute(database.get_ref())
+        .await
+        .context("Failed to store the invitation")
+        .map_err(e500)?;
+    send_invitation_email(
+        &email_client,
+        &email,
+        role,
+        &invitation_link(&base_url.0, &hmac_secret.0, invitation_id),
+    )
+        .await
+        .context("Failed to send the invitation email")
+        .map_err(e500)?;
+
+    FlashMessage::info(format!("An invitation has been sent to {}.", email.as_ref())).send();
+    Ok(see_other("/admin/users"))
+}
+
+#[tracing::instrument(
+name = "Send invitation email",
+skip(email_client, email, invitation_link)
+)]
+async fn send_invitation_email(
+    email_client: &EmailClient,
+    email: &SubscriberEmail,
+    role: UserRole,
+    invitation_link: &str,
+) -> Result<(), reqwest::Error> {
+    let html_body = format!(
+        "You have been invited to help run our newsletter as {}.<br/>\
+        Click <a href=\"{}\">here</a> to create your account.<br/>\
+        This link expires in 3 days and can only be used once.",
+        role, invitation_link
+    );
+    let text_body = format!(
+        "You have been invited to help run our newsletter as {}.\n\
+        Visit {} to create your account.\n\
+        This link expires in 3 days and can only be used once.",
+        role, invitation_link
+    );
+    email_client.send_email(email, "You have been invited", &html_body, &text_body)
+        .await
+}
+
+// Deactivated users can no longer log in; their account is kept so that whatever
+// they did stays attributed to them.
+#[tracing::instrument(
+name = "Deactivating a user",
+skip(form, user, database),
+fields(target_user_id = %form.user_id)
+)]
+pub async fn deactivate_user(
+    form: web::Form<DeactivateFormData>,
+    user: AuthenticatedUser,
+    database: web::Data<DbConnectionKind>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if form.user_id == user.user_id {
+        FlashMessage::error("You cannot deactivate yourself.").send();
+        return Ok(see_other("/admin/users"));
+    }
+
+    let updated = sqlx::query!(
+        "UPDATE users SET active = FALSE WHERE user_id = $1",
+        form.user_id
+    )
+        .execute(database.get_ref())
+        .await
+        .context("Failed to deactivate user")
+        .map_err(e500)?
+        .rows_affected();
+    if updated == 0 {
+        FlashMessage::error("The user does not exist.").send();
+    } else {
+        FlashMessage::info("The user has been deactivated.").send();
+    }
+    Ok(see_other("/admin/users"))
+}
@@ -0,0 +1,114 @@
+use actix_web::{HttpResponse, web};
+use actix_web::http::header::ContentType;
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use std::fmt::Write;
+
+use crate::authentication::CsrfToken;
+use crate::startup::DbConnectionKind;
+use crate::utils::e500;
+
+pub async fn welcome_sequence_form(
+    csrf_token: CsrfToken,
+    database: web::Data<DbConnectionKind>,
+    flash_messages: IncomingFlashMessages
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for message in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", htmlescape::encode_minimal(message.content())).unwrap();
+    }
+
+    let steps = sqlx::query!(
+        r#"
+        SELECT st.id, st.delay_days, st.subject, COUNT(d.step_id) AS "sent!"
+        FROM welcome_sequence_steps st
+        LEFT JOIN welcome_sequence_deliveries d ON d.step_id = st.id
+        GROUP BY st.id
+        ORDER BY st.delay_days, st.created_at
+        "#
+    )
+        .fetch_all(database.get_ref())
+        .await
+        .context("Failed to retrieve the welcome sequence")
+        .map_err(e500)?;
+
+    let mut rows_html = String::new();
+    for step in steps {
+        writeln!(
+            rows_html,
+            r#"<tr>
+            <td>Day {delay_days}</td>
+            <td>{subject}</td>
+            <td>{sent}</td>
+            <td>
+                <form action="/admin/welcome-sequence/delete" method="post">
+                    {csrf_field}
+                    <input hidden type="text" name="step_id" value="{id}">
+                    <button type="submit">Remove</button>
+                </form>
+            </td>
+        </tr>"#,
+            delay_days = step.delay_days,
+            subject = htmlescape::encode_minimal(&step.subject),
+            sent = step.sent,
+            id = step.id,
+            csrf_field = csrf_token.form_field(),
+        ).unwrap();
+    }
+
+    Ok(HttpResponse::Ok().content_type(ContentType::html()).body(format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Welcome sequence</title>
+</head>
+<body>
+    {msg_html}
+    <p>New subscribers are sent these emails, in order, once they confirm their subscription.</p>
+    <table>
+        <tr><th>Sent after</th><th>Subject</th><th>Sent</th><th></th></tr>
+        {rows_html}
+    </table>
+    <form action="/admin/welcome-sequence" method="post">
+        {csrf_field}
+        <label>Days after confirmation
+            <input
+                type="number"
+                min="0"
+                value="0"
+                name="delay_days"
+            >
+        </label>
+        <label>Subject
+            <input
+                type="text"
+                placeholder="Enter the subject"
+                name="subject"
+            >
+        </label>
+        <br>
+        <label>HTML content
+            <textarea
+                placeholder="Enter the content as HTML"
+                name="html_content"
+            ></textarea>
+        </label>
+        <br>
+        <label>Text content
+            <textarea
+                placeholder="Enter the content as plain text"
+                name="text_content"
+            ></textarea>
+        </label>
+        <br>
+        <button type="submit">Add email</button>
+    </form>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        msg_html = msg_html,
+        rows_html = rows_html,
+        csrf_field = csrf_token.form_field(),
+    )))
+}
@@ -0,0 +1,101 @@
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::startup::DbConnectionKind;
+use crate::utils::{e500, see_other};
+
+#[derive(serde::Deserialize)]
+pub struct WelcomeStepFormData {
+    delay_days: i32,
+    subject: String,
+    html_content: String,
+    text_content: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct DeleteWelcomeStepFormData {
+    step_id: Uuid,
+}
+
+#[tracing::instrument(
+name = "Adding a welcome sequence step",
+skip(form, database),
+fields(delay_days = form.delay_days)
+)]
+pub async fn add_welcome_step(
+    form: web::Form<WelcomeStepFormData>,
+    database: web::Data<DbConnectionKind>
+) -> Result<HttpResponse, actix_web::Error> {
+    let WelcomeStepFormData { delay_days, subject, html_content, text_content } = form.0;
+    if delay_days < 0 {
+        FlashMessage::error("Welcome emails cannot be sent before confirmation.").send();
+        return Ok(see_other("/admin/welcome-sequence"));
+    }
+    if subject.trim().is_empty() || html_content.trim().is_empty() || text_content.trim().is_empty() {
+        FlashMessage::error("A welcome email needs a subject, HTML content and text content.").send();
+        return Ok(see_other("/admin/welcome-sequence"));
+    }
+
+    sqlx::query!(
+        r#"
+        INSERT INTO welcome_sequence_steps (id, delay_days, subject, html_content, text_content, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+        Uuid::new_v4(),
+        delay_days,
+        subject.trim(),
+        html_content,
+        text_content,
+        Utc::now()
+    )
+        .execute(database.get_ref())
+        .await
+        .context("Failed to store the welcome sequence step")
+        .map_err(e500)?;
+
+    FlashMessage::info(format!("The day {} welcome email has been added.", delay_days)).send();
+    Ok(see_other("/admin/welcome-sequence"))
+}
+
+#[tracing::instrument(
+name = "Removing a welcome sequence step",
+skip(form, database),
+fields(step_id = %form.step_id)
+)]
+pub async fn delete_welcome_step(
+    form: web::Form<DeleteWelcomeStepFormData>,
+    database: web::Data<DbConnectionKind>
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut transaction = database
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    sqlx::query!(
+        "DELETE FROM welcome_sequence_deliveries WHERE step_id = $1",
+        form.step_id
+    )
+        .execute(&mut transaction)
+        .await
+        .context("Failed to delete the deliveries of the welcome sequence step")
+        .map_err(e500)?;
+    sqlx::query!(
+        "DELETE FROM welcome_sequence_steps WHERE id = $1",
+        form.step_id
+    )
+        .execute(&mut transaction)
+        .await
+        .context("Failed to delete the welcome sequence step")
+        .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the removal of the welcome sequence step")
+        .map_err(e500)?;
+
+    FlashMessage::info("The welcome email has been removed.").send();
+    Ok(see_other("/admin/welcome-sequence"))
+}
@@ -1,5 +1,9 @@
-use actix_web::HttpResponse;
+use actix_web::{HttpResponse, web};
+use actix_web::http::header::ContentType;
+use crate::subscription_protection::SubscriptionProtection;
 
-pub async fn home() -> HttpResponse {
-    HttpResponse::Ok().body(include_str!("home.html"))
-}
\ No newline at end of file
+pub async fn home(protection: web::Data<SubscriptionProtection>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(include_str!("home.html").replace("{form_token}", &protection.form_token()))
+}
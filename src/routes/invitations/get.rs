@@ -0,0 +1,81 @@
+use actix_web::{HttpResponse, web};
+use actix_web::http::header::ContentType;
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use std::fmt::Write;
+
+use crate::routes::invitations::{verify_token, InvitationError};
+use crate::startup::{DbConnectionKind, HmacSecret};
+
+#[derive(serde::Deserialize)]
+pub struct Parameters {
+    token: String,
+}
+
+pub async fn accept_invitation_form(
+    params: web::Query<Parameters>,
+    database: web::Data<DbConnectionKind>,
+    hmac_secret: web::Data<HmacSecret>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, InvitationError> {
+    let invitation_id = verify_token(&hmac_secret.0, &params.token)?;
+    let invitation = sqlx::query!(
+        "SELECT email, role FROM user_invitations WHERE id = $1 AND accepted_at IS NULL",
+        invitation_id
+    )
+        .fetch_optional(database.get_ref())
+        .await
+        .context("Failed to retrieve invitation")?
+        .ok_or_else(|| InvitationError::InvalidLink(anyhow::anyhow!("The invitation has already been used")))?;
+
+    let mut msg_html = String::new();
+    for message in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", htmlescape::encode_minimal(message.content())).unwrap();
+    }
+
+    Ok(HttpResponse::Ok().content_type(ContentType::html()).body(format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Accept invitation</title>
+</head>
+<body>
+    {msg_html}
+    <p>You have been invited to join as {role} with {email}. Choose a username and password to get started.</p>
+    <form action="/invitations/accept" method="post">
+        <input hidden type="text" name="token" value="{token}">
+        <label>Username
+            <input
+                type="text"
+                placeholder="Enter username"
+                name="username"
+            >
+        </label>
+        <br>
+        <label>Password
+            <input
+                type="password"
+                placeholder="Enter password"
+                name="password"
+            >
+        </label>
+        <br>
+        <label>Confirm password
+            <input
+                type="password"
+                placeholder="Type the password again"
+                name="password_check"
+            >
+        </label>
+        <br>
+        <button type="submit">Create account</button>
+    </form>
+</body>
+</html>"#,
+        msg_html = msg_html,
+        role = htmlescape::encode_minimal(&invitation.role),
+        email = htmlescape::encode_minimal(&invitation.email),
+        token = htmlescape::encode_attribute(&params.token),
+    )))
+}
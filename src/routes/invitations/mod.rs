@@ -0,0 +1,66 @@
+use actix_web::ResponseError;
+use actix_web::http::StatusCode;
+use chrono::Duration;
+use secrecy::Secret;
+use std::fmt::Formatter;
+use uuid::Uuid;
+
+use crate::routes::error_chain_fmt;
+use crate::signed_token;
+
+mod get;
+mod post;
+
+pub use get::*;
+pub use post::*;
+
+const INVITATION_PURPOSE: &str = "user-invitation";
+
+fn link_time_to_live() -> Duration {
+    Duration::days(3)
+}
+
+pub fn invitation_link(base_url: &str, hmac_secret: &Secret<String>, invitation_id: Uuid) -> String {
+    format!(
+        "{}{}",
+        base_url,
+        invitation_path(&signed_token::generate(
+            hmac_secret,
+            INVITATION_PURPOSE,
+            invitation_id,
+            link_time_to_live(),
+        ))
+    )
+}
+
+fn invitation_path(token: &str) -> String {
+    format!("/invitations/accept?token={}", token)
+}
+
+fn verify_token(hmac_secret: &Secret<String>, token: &str) -> Result<Uuid, InvitationError> {
+    signed_token::verify(hmac_secret, INVITATION_PURPOSE, token)
+        .map_err(InvitationError::InvalidLink)
+}
+
+#[derive(thiserror::Error)]
+pub enum InvitationError {
+    #[error("This invitation is invalid, has expired or has already been used")]
+    InvalidLink(#[source] anyhow::Error),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for InvitationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for InvitationError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            InvitationError::InvalidLink(_) => StatusCode::UNAUTHORIZED,
+            InvitationError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
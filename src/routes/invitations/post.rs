@@ -0,0 +1,114 @@
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use chrono::Utc;
+use secrecy::{ExposeSecret, Secret};
+use uuid::Uuid;
+
+use crate::authentication::{compute_password_hash, PasswordHashing};
+use crate::domain::PasswordPolicy;
+use crate::routes::invitations::{invitation_path, verify_token, InvitationError};
+use crate::startup::{DbConnectionKind, HmacSecret};
+use crate::telemetry::spawn_blocking_with_tracinig;
+use crate::utils::see_other;
+
+#[derive(serde::Deserialize)]
+pub struct AcceptInvitationFormData {
+    token: String,
+    username: String,
+    password: Secret<String>,
+    password_check: Secret<String>,
+}
+
+#[tracing::instrument(
+name = "Accepting an invitation",
+skip(form, database, hmac_secret, hashing, policy),
+fields(invitation_id = tracing::field::Empty, user_id = tracing::field::Empty)
+)]
+pub async fn accept_invitation(
+    form: web::Form<AcceptInvitationFormData>,
+    database: web::Data<DbConnectionKind>,
+    hmac_secret: web::Data<HmacSecret>,
+    hashing: web::Data<PasswordHashing>,
+    policy: web::Data<PasswordPolicy>,
+) -> Result<HttpResponse, InvitationError> {
+    let AcceptInvitationFormData { token, username, password, password_check } = form.0;
+    let invitation_id = verify_token(&hmac_secret.0, &token)?;
+    tracing::Span::current().record("invitation_id", &tracing::field::display(&invitation_id));
+
+    let username = username.trim().to_string();
+    if username.is_empty() {
+        FlashMessage::error("Choose a username.").send();
+        return Ok(see_other(&invitation_path(&token)));
+    }
+    if password.expose_secret() != password_check.expose_secret() {
+        FlashMessage::error("You entered two different passwords - the field values must match").send();
+        return Ok(see_other(&invitation_path(&token)));
+    }
+    if let Err(e) = policy.check(&password).await {
+        FlashMessage::error(e).send();
+        return Ok(see_other(&invitation_path(&token)));
+    }
+
+    let mut transaction = database
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    // Locking the invitation makes sure that it can only be used once, even if the
+    // form is submitted twice at the same time.
+    let invitation = sqlx::query!(
+        r#"
+        SELECT email, role FROM user_invitations
+        WHERE id = $1 AND accepted_at IS NULL
+        FOR UPDATE
+        "#,
+        invitation_id
+    )
+        .fetch_optional(&mut transaction)
+        .await
+        .context("Failed to retrieve invitation")?
+        .ok_or_else(|| InvitationError::InvalidLink(anyhow::anyhow!("The invitation has already been used")))?;
+
+    let hashing = hashing.get_ref().clone();
+    let password_hash = spawn_blocking_with_tracinig(move || compute_password_hash(password, &hashing))
+        .await
+        .context("Failed to spawn blocking password hashing")?
+        .context("Failed to hash password")?;
+    let user_id = Uuid::new_v4();
+    let inserted = sqlx::query!(
+        r#"
+        INSERT INTO users (user_id, username, password_hash, role, active, email)
+        VALUES ($1, $2, $3, $4, TRUE, $5)
+        ON CONFLICT DO NOTHING
+        "#,
+        user_id,
+        username,
+        password_hash.expose_secret(),
+        invitation.role,
+        invitation.email
+    )
+        .execute(&mut transaction)
+        .await
+        .context("Failed to create the invited user")?
+        .rows_affected();
+    if inserted == 0 {
+        FlashMessage::error("That username is already taken, or there is already a user with your email.").send();
+        return Ok(see_other(&invitation_path(&token)));
+    }
+    sqlx::query!(
+        "UPDATE user_invitations SET accepted_at = $1 WHERE id = $2",
+        Utc::now(),
+        invitation_id
+    )
+        .execute(&mut transaction)
+        .await
+        .context("Failed to mark the invitation as accepted")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the accepted invitation")?;
+    tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
+
+    FlashMessage::info("Your account has been created. You can now log in.").send();
+    Ok(see_other("/login"))
+}
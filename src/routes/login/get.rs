@@ -6,7 +6,7 @@ use std::fmt::Write;
 pub async fn login_form(flash_messages: IncomingFlashMessages) -> HttpResponse {
     let mut error_html = String::new();
     for m in flash_messages.iter() {
-        writeln!(error_html, "<p><i>{}</i></p>", m.content()).unwrap();
+        writeln!(error_html, "<p><i>{}</i></p>", htmlescape::encode_minimal(m.content())).unwrap();
     }
     HttpResponse::Ok()
         .content_type(ContentType::html())
@@ -36,6 +36,7 @@ pub async fn login_form(flash_messages: IncomingFlashMessages) -> HttpResponse {
         </label>
         <button type="submit">Login</button>
     </form>
+    <p><a href="/password-reset">Forgot your password?</a></p>
 </body>
 </html>"#,
             error_html
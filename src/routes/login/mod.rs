@@ -1,2 +1,3 @@
 pub mod get;
 pub mod post;
+pub mod two_factor;
@@ -1,13 +1,15 @@
 use actix_web::{HttpResponse, web};
 use actix_web::http::header::LOCATION;
 use secrecy::{Secret};
-use crate::authentication::{Credentials, validate_credentials, AuthError};
+use crate::authentication::{Credentials, validate_credentials, AuthError, has_two_factor, start_user_session, PasswordHashing};
 use crate::startup::{DbConnectionKind};
 use std::fmt::Formatter;
 use crate::routes::error_chain_fmt;
 use actix_web::error::InternalError;
 use actix_web_flash_messages::FlashMessage;
 use crate::session_state::TypedSession;
+use crate::login_throttle::LoginThrottle;
+use crate::audit_log::{record_audit_event, AuditAction, RequestOrigin};
 
 
 #[derive(serde::Deserialize)]
@@ -20,6 +22,9 @@ pub struct FormData {
 pub enum LoginError {
     #[error("Authentication failed")]
     AuthError(#[source] anyhow::Error),
+    // Deliberately the same whether or not the username exists.
+    #[error("Too many failed login attempts. Please try again later.")]
+    TooManyAttempts(#[source] anyhow::Error),
     #[error("Something went wrong")]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -31,36 +36,78 @@ impl std::fmt::Debug for LoginError {
 }
 
 #[tracing::instrument(
-skip(form, database, session),
-fields(username = tracing::field::Empty, user_id = tracing::field::Empty)
+skip(form, origin, database, session, throttle, hashing),
+fields(username = tracing::field::Empty, user_id = tracing::field::Empty, client_ip = tracing::field::Empty)
 )]
 pub async fn login(
     form: web::Form<FormData>,
+    origin: RequestOrigin,
     database: web::Data<DbConnectionKind>,
-    session: TypedSession
+    session: TypedSession,
+    throttle: web::Data<LoginThrottle>,
+    hashing: web::Data<PasswordHashing>,
 ) -> Result<HttpResponse, InternalError<LoginError>> {
     let credentials = Credentials {
         username: form.0.username,
         password: form.0.password,
     };
+    let username = credentials.username.clone();
+    let client_ip = origin.client_ip.clone();
 
     tracing::Span::current()
-        .record("username", &tracing::field::display(&credentials.username));
+        .record("username", &tracing::field::display(&username))
+        .record("client_ip", &tracing::field::display(&client_ip));
 
-    match validate_credentials(credentials, &database).await {
+    // Locked out attempts are refused before the password is hashed, so that
+    // guessing costs us as little as possible.
+    match throttle.check(&username, &client_ip) {
+        Ok(delay) => tokio::time::sleep(delay).await,
+        Err(reason) => {
+            tracing::warn!(reason = reason.code(), "Refusing a login attempt during a lockout");
+            record_audit_event(&database, None, AuditAction::LoginFailed, Some(&username), &origin)
+                .await
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+            return Err(login_redirect(LoginError::TooManyAttempts(anyhow::anyhow!(reason.code()))));
+        }
+    }
+
+    match validate_credentials(credentials, &hashing, &database).await {
         Ok(user_id) => {
+            throttle.record_success(&username, &client_ip);
             tracing::Span::current()
                 .record("user_id", &tracing::field::display(&user_id));
+            let two_factor = has_two_factor(user_id, &database)
+                .await
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
             session.renew();
-            session.insert_user_id(user_id)
+            if two_factor {
+                session.insert_pending_login(user_id)
+                    .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+                return Ok(HttpResponse::SeeOther()
+                    .insert_header((LOCATION, "/login/two-factor"))
+                    .finish());
+            }
+            let session_id = start_user_session(user_id, &origin, &database)
+                .await
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+            session.insert_user_id(user_id, session_id)
                 .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+            record_audit_event(&database, Some(user_id), AuditAction::Login, None, &origin)
+                .await
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
             Ok(HttpResponse::SeeOther()
                 .insert_header((LOCATION, "/admin/dashboard"))
                 .finish())
         }
         Err(e) => {
             let login_error = match e {
-                AuthError::InvalidCredentials(_) => LoginError::AuthError(e.into()),
+                AuthError::InvalidCredentials(_) => {
+                    throttle.record_failure(&username, &client_ip);
+                    record_audit_event(&database, None, AuditAction::LoginFailed, Some(&username), &origin)
+                        .await
+                        .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+                    LoginError::AuthError(e.into())
+                }
                 AuthError::UnexpectedError(_) => LoginError::UnexpectedError(e.into())
             };
             Err(login_redirect(login_error))
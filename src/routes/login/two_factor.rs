@@ -0,0 +1,111 @@
+use actix_web::{HttpResponse, web};
+use actix_web::http::header::ContentType;
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use std::fmt::Write;
+
+use crate::audit_log::{record_audit_event, AuditAction, RequestOrigin};
+use crate::authentication::{start_user_session, verify_second_factor};
+use crate::login_throttle::LoginThrottle;
+use crate::session_state::TypedSession;
+use crate::startup::DbConnectionKind;
+use crate::utils::{e500, see_other};
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    code: String,
+}
+
+pub async fn two_factor_form(
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    if session.get_pending_login().map_err(e500)?.is_none() {
+        return Ok(see_other("/login"));
+    }
+    let mut error_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(error_html, "<p><i>{}</i></p>", htmlescape::encode_minimal(m.content())).unwrap();
+    }
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Two-factor authentication</title>
+</head>
+<body>
+    {}
+    <form action="/login/two-factor" method="post">
+        <label>Code from your authenticator app, or a recovery code
+            <input
+                type="text"
+                inputmode="numeric"
+                autocomplete="one-time-code"
+                placeholder="123456"
+                name="code"
+            >
+        </label>
+        <button type="submit">Login</button>
+    </form>
+</body>
+</html>"#,
+            error_html
+        )))
+}
+
+#[tracing::instrument(
+name = "Verifying the second login step",
+skip(form, origin, database, session, throttle),
+fields(user_id = tracing::field::Empty)
+)]
+pub async fn verify_two_factor(
+    form: web::Form<FormData>,
+    origin: RequestOrigin,
+    database: web::Data<DbConnectionKind>,
+    session: TypedSession,
+    throttle: web::Data<LoginThrottle>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = match session.get_pending_login().map_err(e500)? {
+        Some(user_id) => user_id,
+        None => {
+            FlashMessage::error("Your login has expired. Please log in again.").send();
+            return Ok(see_other("/login"));
+        }
+    };
+    tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
+
+    // Six digits are quick to guess, so codes are throttled like passwords.
+    let account = user_id.to_string();
+    let client_ip = &origin.client_ip;
+    match throttle.check(&account, client_ip) {
+        Ok(delay) => tokio::time::sleep(delay).await,
+        Err(reason) => {
+            tracing::warn!(reason = reason.code(), "Refusing a second factor during a lockout");
+            record_audit_event(&database, Some(user_id), AuditAction::LoginFailed, Some("second factor"), &origin)
+                .await
+                .map_err(e500)?;
+            FlashMessage::error("Too many failed login attempts. Please try again later.").send();
+            return Ok(see_other("/login/two-factor"));
+        }
+    }
+
+    if !verify_second_factor(user_id, &form.code, &database).await.map_err(e500)? {
+        throttle.record_failure(&account, client_ip);
+        record_audit_event(&database, Some(user_id), AuditAction::LoginFailed, Some("second factor"), &origin)
+            .await
+            .map_err(e500)?;
+        FlashMessage::error("The code is incorrect or has already been used.").send();
+        return Ok(see_other("/login/two-factor"));
+    }
+    throttle.record_success(&account, client_ip);
+    session.remove_pending_login();
+    session.renew();
+    let session_id = start_user_session(user_id, &origin, &database).await.map_err(e500)?;
+    session.insert_user_id(user_id, session_id).map_err(e500)?;
+    record_audit_event(&database, Some(user_id), AuditAction::Login, None, &origin)
+        .await
+        .map_err(e500)?;
+    Ok(see_other("/admin/dashboard"))
+}
@@ -3,7 +3,11 @@ pub use subscriptions::error_chain_fmt;
 pub mod health_check;
 pub mod subscriptions;
 pub mod subscriptions_confirm;
+pub mod subscriptions_data;
+pub mod subscriptions_preferences;
 pub mod home;
 pub mod login;
+pub mod invitations;
+pub mod password_reset;
 pub mod admin;
 
@@ -0,0 +1,107 @@
+use actix_web::{HttpResponse, web};
+use actix_web::http::header::ContentType;
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use std::fmt::Write;
+
+use crate::routes::password_reset::{verify_token, PasswordResetError};
+use crate::startup::{DbConnectionKind, HmacSecret};
+
+#[derive(serde::Deserialize)]
+pub struct Parameters {
+    token: String,
+}
+
+pub async fn password_reset_request_form(flash_messages: IncomingFlashMessages) -> HttpResponse {
+    let mut msg_html = String::new();
+    for message in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", htmlescape::encode_minimal(message.content())).unwrap();
+    }
+
+    HttpResponse::Ok().content_type(ContentType::html()).body(format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Reset your password</title>
+</head>
+<body>
+    {msg_html}
+    <p>Enter the email address of your account and we will send you a link to choose a new password.</p>
+    <form action="/password-reset" method="post">
+        <label>Email
+            <input
+                type="email"
+                placeholder="Enter your email"
+                name="email"
+            >
+        </label>
+        <button type="submit">Send reset link</button>
+    </form>
+    <p><a href="/login">&lt;- Back to login</a></p>
+</body>
+</html>"#,
+        msg_html = msg_html,
+    ))
+}
+
+pub async fn password_reset_form(
+    params: web::Query<Parameters>,
+    database: web::Data<DbConnectionKind>,
+    hmac_secret: web::Data<HmacSecret>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, PasswordResetError> {
+    let reset_id = verify_token(&hmac_secret.0, &params.token)?;
+    sqlx::query!(
+        r#"
+        SELECT r.id FROM password_resets r
+        JOIN users u ON u.user_id = r.user_id
+        WHERE r.id = $1 AND r.used_at IS NULL AND u.active
+        "#,
+        reset_id
+    )
+        .fetch_optional(database.get_ref())
+        .await
+        .context("Failed to retrieve the password reset")?
+        .ok_or_else(|| PasswordResetError::InvalidLink(anyhow::anyhow!("The password reset has already been used")))?;
+
+    let mut msg_html = String::new();
+    for message in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", htmlescape::encode_minimal(message.content())).unwrap();
+    }
+
+    Ok(HttpResponse::Ok().content_type(ContentType::html()).body(format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Choose a new password</title>
+</head>
+<body>
+    {msg_html}
+    <form action="/password-reset/confirm" method="post">
+        <input hidden type="text" name="token" value="{token}">
+        <label>New password
+            <input
+                type="password"
+                placeholder="Enter new password"
+                name="new_password"
+            >
+        </label>
+        <br>
+        <label>Confirm new password
+            <input
+                type="password"
+                placeholder="Type the new password again"
+                name="new_password_check"
+            >
+        </label>
+        <br>
+        <button type="submit">Reset password</button>
+    </form>
+</body>
+</html>"#,
+        msg_html = msg_html,
+        token = htmlescape::encode_attribute(&params.token),
+    )))
+}
@@ -0,0 +1,66 @@
+use actix_web::ResponseError;
+use actix_web::http::StatusCode;
+use chrono::Duration;
+use secrecy::Secret;
+use std::fmt::Formatter;
+use uuid::Uuid;
+
+use crate::routes::error_chain_fmt;
+use crate::signed_token;
+
+mod get;
+mod post;
+
+pub use get::*;
+pub use post::*;
+
+const PASSWORD_RESET_PURPOSE: &str = "password-reset";
+
+fn link_time_to_live() -> Duration {
+    Duration::hours(1)
+}
+
+pub fn password_reset_link(base_url: &str, hmac_secret: &Secret<String>, reset_id: Uuid) -> String {
+    format!(
+        "{}{}",
+        base_url,
+        password_reset_path(&signed_token::generate(
+            hmac_secret,
+            PASSWORD_RESET_PURPOSE,
+            reset_id,
+            link_time_to_live(),
+        ))
+    )
+}
+
+fn password_reset_path(token: &str) -> String {
+    format!("/password-reset/confirm?token={}", token)
+}
+
+fn verify_token(hmac_secret: &Secret<String>, token: &str) -> Result<Uuid, PasswordResetError> {
+    signed_token::verify(hmac_secret, PASSWORD_RESET_PURPOSE, token)
+        .map_err(PasswordResetError::InvalidLink)
+}
+
+#[derive(thiserror::Error)]
+pub enum PasswordResetError {
+    #[error("This password reset link is invalid, has expired or has already been used")]
+    InvalidLink(#[source] anyhow::Error),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for PasswordResetError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for PasswordResetError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            PasswordResetError::InvalidLink(_) => StatusCode::UNAUTHORIZED,
+            PasswordResetError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
@@ -0,0 +1,254 @@
+use actix_web::{HttpRequest, HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use chrono::Utc;
+use secrecy::{ExposeSecret, Secret};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::audit_log::{record_audit_event, AuditAction, RequestOrigin};
+use crate::authentication::{compute_password_hash, PasswordHashing};
+use crate::domain::subscriber_email::SubscriberEmail;
+use crate::domain::PasswordPolicy;
+use crate::email_client::EmailClient;
+use crate::password_reset_throttle::PasswordResetThrottle;
+use crate::routes::password_reset::{password_reset_link, password_reset_path, verify_token, PasswordResetError};
+use crate::startup::{ApplicationBaseUrl, DbConnectionKind, HmacSecret};
+use crate::telemetry::spawn_blocking_with_tracinig;
+use crate::utils::{client_ip, e500, see_other};
+
+#[derive(serde::Deserialize)]
+pub struct RequestFormData {
+    email: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct ResetFormData {
+    token: String,
+    new_password: Secret<String>,
+    new_password_check: Secret<String>,
+}
+
+// The response is the same whether or not there is an account with that email, so
+// that the form cannot be used to find out who the admins are. Looking the account
+// up and emailing it happen after the response is sent, so that it does not take
+// longer to answer for a real account either.
+#[tracing::instrument(
+name = "Requesting a password reset",
+skip(form, request, database, email_client, base_url, hmac_secret, throttle),
+fields(client_ip = tracing::field::Empty)
+)]
+pub async fn request_password_reset(
+    form: web::Form<RequestFormData>,
+    request: HttpRequest,
+    database: web::Data<DbConnectionKind>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    hmac_secret: web::Data<HmacSecret>,
+    throttle: web::Data<PasswordResetThrottle>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let client_ip = client_ip(&request);
+    tracing::Span::current().record("client_ip", &tracing::field::display(&client_ip));
+    let email = form.0.email.trim().to_string();
+    if throttle.check(&email, &client_ip) {
+        actix_web::rt::spawn(send_password_reset(
+            email,
+            database.into_inner(),
+            email_client.into_inner(),
+            base_url.into_inner(),
+            hmac_secret.into_inner(),
+        ));
+    } else {
+        tracing::warn!("Too many password reset requests, no email is sent");
+    }
+
+    FlashMessage::info(
+        "If there is an account with that email, we have sent it a link to reset the password."
+    ).send();
+    Ok(see_other("/login"))
+}
+
+#[tracing::instrument(
+name = "Sending a password reset",
+skip(email, database, email_client, base_url, hmac_secret),
+fields(user_id = tracing::field::Empty)
+)]
+async fn send_password_reset(
+    email: String,
+    database: Arc<DbConnectionKind>,
+    email_client: Arc<EmailClient>,
+    base_url: Arc<ApplicationBaseUrl>,
+    hmac_secret: Arc<HmacSecret>,
+) {
+    let reset = match start_password_reset(&email, &database).await {
+        Ok(Some(reset)) => reset,
+        Ok(None) => return,
+        Err(e) => {
+            tracing::error!(error.cause_chain = ?e, "Failed to start a password reset");
+            return;
+        }
+    };
+    tracing::Span::current().record("user_id", &tracing::field::display(&reset.user_id));
+    if let Err(e) = send_password_reset_email(
+        &email_client,
+        &reset.email,
+        &password_reset_link(&base_url.0, &hmac_secret.0, reset.reset_id),
+    ).await {
+        tracing::error!(error.cause_chain = ?e, "Failed to send a password reset email");
+    }
+}
+
+struct PendingReset {
+    user_id: Uuid,
+    reset_id: Uuid,
+    email: SubscriberEmail,
+}
+
+// Returns `None` if there is no active account with that email.
+async fn start_password_reset(
+    email: &str,
+    database: &DbConnectionKind,
+) -> Result<Option<PendingReset>, anyhow::Error> {
+    let user = sqlx::query!(
+        r#"SELECT user_id, email AS "email!" FROM users WHERE lower(email) = lower($1) AND active"#,
+        email
+    )
+        .fetch_optional(database)
+        .await
+        .context("Failed to look up users by email")?;
+    let user = match user {
+        Some(user) => user,
+        None => return Ok(None),
+    };
+    let reset_id = Uuid::new_v4();
+    sqlx::query!(
+        "INSERT INTO password_resets (id, user_id, requested_at) VALUES ($1, $2, $3)",
+        reset_id,
+        user.user_id,
+        Utc::now()
+    )
+        .execute(database)
+        .await
+        .context("Failed to store the password reset")?;
+    let email = SubscriberEmail::parse(user.email).map_err(anyhow::Error::msg)?;
+    Ok(Some(PendingReset { user_id: user.user_id, reset_id, email }))
+}
+
+#[tracing::instrument(
+name = "Send password reset email",
+skip(email_client, email, reset_link)
+)]
+async fn send_password_reset_email(
+    email_client: &EmailClient,
+    email: &SubscriberEmail,
+    reset_link: &str,
+) -> Result<(), reqwest::Error> {
+    let html_body = format!(
+        "Someone asked to reset the password of your newsletter account.<br/>\
+        Click <a href=\"{}\">here</a> to choose a new password.<br/>\
+        This link expires in 1 hour and can only be used once. \
+        If you did not ask for it, you can ignore this email.",
+        reset_link
+    );
+    let text_body = format!(
+        "Someone asked to reset the password of your newsletter account.\n\
+        Visit {} to choose a new password.\n\
+        This link expires in 1 hour and can only be used once. \
+        If you did not ask for it, you can ignore this email.",
+        reset_link
+    );
+    email_client.send_email(email, "Reset your password", &html_body, &text_body)
+        .await
+}
+
+// Resetting the password also uses up every other reset link of the user and logs
+// them out everywhere, in case the reset is needed because someone else got in.
+#[tracing::instrument(
+name = "Resetting a password",
+skip(form, origin, database, hmac_secret, hashing, policy),
+fields(reset_id = tracing::field::Empty, user_id = tracing::field::Empty)
+)]
+pub async fn reset_password(
+    form: web::Form<ResetFormData>,
+    origin: RequestOrigin,
+    database: web::Data<DbConnectionKind>,
+    hmac_secret: web::Data<HmacSecret>,
+    hashing: web::Data<PasswordHashing>,
+    policy: web::Data<PasswordPolicy>,
+) -> Result<HttpResponse, PasswordResetError> {
+    let ResetFormData { token, new_password, new_password_check } = form.0;
+    let reset_id = verify_token(&hmac_secret.0, &token)?;
+    tracing::Span::current().record("reset_id", &tracing::field::display(&reset_id));
+
+    if new_password.expose_secret() != new_password_check.expose_secret() {
+        FlashMessage::error("You entered two different new passwords - the field values must match").send();
+        return Ok(see_other(&password_reset_path(&token)));
+    }
+    if let Err(e) = policy.check(&new_password).await {
+        FlashMessage::error(e).send();
+        return Ok(see_other(&password_reset_path(&token)));
+    }
+
+    let mut transaction = database
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    // Locking the reset makes sure that it can only be used once, even if the form
+    // is submitted twice at the same time.
+    let user_id = sqlx::query!(
+        r#"
+        SELECT r.user_id FROM password_resets r
+        JOIN users u ON u.user_id = r.user_id
+        WHERE r.id = $1 AND r.used_at IS NULL AND u.active
+        FOR UPDATE OF r
+        "#,
+        reset_id
+    )
+        .fetch_optional(&mut transaction)
+        .await
+        .context("Failed to retrieve the password reset")?
+        .ok_or_else(|| PasswordResetError::InvalidLink(anyhow::anyhow!("The password reset has already been used")))?
+        .user_id;
+    tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
+
+    let hashing = hashing.get_ref().clone();
+    let password_hash = spawn_blocking_with_tracinig(move || compute_password_hash(new_password, &hashing))
+        .await
+        .context("Failed to spawn blocking password hashing")?
+        .context("Failed to hash password")?;
+    let now = Utc::now();
+    sqlx::query!(
+        "UPDATE users SET password_hash = $1, sessions_revoked_at = $2 WHERE user_id = $3",
+        password_hash.expose_secret(),
+        now,
+        user_id
+    )
+        .execute(&mut transaction)
+        .await
+        .context("Failed to reset the password")?;
+    sqlx::query!(
+        "UPDATE user_sessions SET revoked_at = $1 WHERE user_id = $2 AND revoked_at IS NULL",
+        now,
+        user_id
+    )
+        .execute(&mut transaction)
+        .await
+        .context("Failed to revoke the sessions")?;
+    sqlx::query!(
+        "UPDATE password_resets SET used_at = $1 WHERE user_id = $2 AND used_at IS NULL",
+        now,
+        user_id
+    )
+        .execute(&mut transaction)
+        .await
+        .context("Failed to mark the password resets as used")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the password reset")?;
+    record_audit_event(&database, Some(user_id), AuditAction::PasswordChanged, Some("reset by email"), &origin)
+        .await?;
+
+    FlashMessage::info("Your password has been reset. You can now log in.").send();
+    Ok(see_other("/login"))
+}
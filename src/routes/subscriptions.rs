@@ -1,18 +1,27 @@
-use actix_web::{HttpResponse, web, ResponseError};
+use actix_web::{HttpResponse, HttpRequest, web, ResponseError};
+use actix_web::error::{JsonPayloadError, UrlencodedError};
+use actix_web::web::Either;
+use anyhow::Context;
 use chrono::Utc;
 use uuid::Uuid;
 
 use crate::FormData;
-use crate::startup::{DbConnectionKind, ApplicationBaseUrl};
-use crate::domain::{NewSubscriber};
-use std::convert::{TryInto};
+use crate::startup::{DbConnectionKind, ApplicationBaseUrl, HmacSecret};
+use crate::routes::subscriptions_preferences::preferences_link;
+use crate::routes::admin::attributes::get_attribute_schema;
+use crate::domain::{NewSubscriber, ValidationError, EmailDomainPolicy, SubscriptionStatus};
 use crate::email_client::EmailClient;
+use crate::subscription_protection::{SubscriptionProtection, RejectionReason};
+use crate::subscription_lifecycle::{change_status, record_initial_status, StatusChangeError};
+use crate::utils::{client_ip, message_page};
+use crate::i18n::Locale;
 use rand::{thread_rng, Rng};
 use rand::distributions::Alphanumeric;
 use sqlx::{Transaction, Postgres};
 use std::fmt::{Display, Formatter};
 use std::error::Error;
 use actix_web::http::StatusCode;
+use actix_web::http::header::ACCEPT_LANGUAGE;
 
 pub struct StoreTokenError(sqlx::Error);
 
@@ -53,16 +62,19 @@ impl std::fmt::Debug for StoreTokenError {
 // Error that can occur when creating a new subscriber
 #[derive(Debug)]
 pub enum SubscribeError {
-    ValidationError(String),
+    ValidationError(ValidationError),
+    InvalidPayload(actix_web::Error),
+    Rejected(RejectionReason),
+    AttributeSchemaError(sqlx::Error),
     PoolError(sqlx::Error),
-    InsertSubscriberError(sqlx::Error),
+    InsertSubscriberError(anyhow::Error),
     TransactionCommitError(sqlx::Error),
     StoreTokenError(StoreTokenError),
     SendEmailError(reqwest::Error)
 }
 
-impl From<String> for SubscribeError {
-    fn from(e: String) -> Self {
+impl From<ValidationError> for SubscribeError {
+    fn from(e: ValidationError) -> Self {
         Self::ValidationError(e)
     }
 }
@@ -83,9 +95,20 @@ impl std::fmt::Display for SubscribeError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             SubscribeError::ValidationError(e) => write!(f, "{}", e),
+            SubscribeError::InvalidPayload(e) => write!(f, "{}", e),
+            SubscribeError::Rejected(reason) => match reason {
+                RejectionReason::HoneypotFilled |
+                RejectionReason::FilledTooQuickly => write!(f, "The form was submitted too quickly, please try again"),
+                RejectionReason::MissingFormToken |
+                RejectionReason::InvalidFormToken |
+                RejectionReason::ReusedFormToken => write!(f, "The form has expired, please reload the page and try again"),
+                RejectionReason::IpRateLimited |
+                RejectionReason::EmailDomainRateLimited => write!(f, "Too many subscription attempts, please try again later"),
+            },
             SubscribeError::StoreTokenError(_) => write!(f, "Failed to store confirmation token for new subscriber"),
             SubscribeError::SendEmailError(_) => write!(f, "Failed to send confirmation email"),
             SubscribeError::PoolError(_) => write!(f, "Failed to acquire a postgress connection"),
+            SubscribeError::AttributeSchemaError(_) => write!(f, "Failed to load the subscriber attribute schema"),
             SubscribeError::InsertSubscriberError(_) => write!(f, "Failed to insert new subscriber in the database"),
             SubscribeError::TransactionCommitError(_) => write!(f, "Failed to commit SQL transaction to store a new subscriber")
         }
@@ -96,10 +119,13 @@ impl std::error::Error for SubscribeError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
         match self {
             SubscribeError::ValidationError(_) => None,
+            SubscribeError::InvalidPayload(_) => None,
+            SubscribeError::Rejected(_) => None,
             SubscribeError::StoreTokenError(e) =>Some(e),
             SubscribeError::SendEmailError(e) =>Some(e),
             SubscribeError::PoolError(e) => Some(e),
-            SubscribeError::InsertSubscriberError(e) => Some(e),
+            SubscribeError::AttributeSchemaError(e) => Some(e),
+            SubscribeError::InsertSubscriberError(e) => Some(&**e),
             SubscribeError::TransactionCommitError(e) => Some(e),
         }
     }
@@ -108,30 +134,112 @@ impl ResponseError for SubscribeError {
     fn status_code(&self) -> StatusCode {
         match self {
             SubscribeError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            SubscribeError::InvalidPayload(e) => e.as_response_error().status_code(),
+            SubscribeError::Rejected(RejectionReason::IpRateLimited) |
+            SubscribeError::Rejected(RejectionReason::EmailDomainRateLimited) => StatusCode::TOO_MANY_REQUESTS,
+            SubscribeError::Rejected(_) => StatusCode::BAD_REQUEST,
             SubscribeError::PoolError(_) |
+            SubscribeError::AttributeSchemaError(_) |
             SubscribeError::InsertSubscriberError(_) |
             SubscribeError::TransactionCommitError(_) |
             SubscribeError::StoreTokenError(_) |
             SubscribeError::SendEmailError(_) => StatusCode::INTERNAL_SERVER_ERROR
         }
     }
+
+    fn error_response(&self) -> HttpResponse {
+        let field = match self {
+            SubscribeError::ValidationError(e) => Some(e.field.as_str()),
+            _ => None,
+        };
+        HttpResponse::build(self.status_code()).json(ErrorBody {
+            error: ErrorDetails {
+                message: self.to_string(),
+                field,
+            }
+        })
+    }
+}
+
+#[derive(serde::Serialize)]
+struct ErrorBody<'a> {
+    error: ErrorDetails<'a>,
+}
+
+#[derive(serde::Serialize)]
+struct ErrorDetails<'a> {
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    field: Option<&'a str>,
+}
+
+// Body extraction errors are routed through `SubscribeError` so that callers get
+// the same JSON error body whether the payload failed to parse or to validate.
+// The marketing site and the mobile app render their own sign up forms, so they
+// fetch a form token here when showing one and send it back with the subscription.
+pub async fn form_token(protection: web::Data<SubscriptionProtection>) -> HttpResponse {
+    HttpResponse::Ok().json(serde_json::json!({ "form_token": protection.form_token() }))
+}
+
+pub fn form_error_handler(e: UrlencodedError, _req: &HttpRequest) -> actix_web::Error {
+    SubscribeError::InvalidPayload(e.into()).into()
+}
+
+pub fn json_error_handler(e: JsonPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    SubscribeError::InvalidPayload(e.into()).into()
 }
 
 #[tracing::instrument(
 name = "Adding a new subscriber",
-skip(form, connection, email_client, base_url),
+skip(body, request, connection, email_client, base_url, protection, domain_policy, hmac_secret),
 fields(
-subscriber_email = % form.email,
-subscriber_name = % form.name
+subscriber_email = tracing::field::Empty,
+subscriber_name = tracing::field::Empty,
+client_ip = tracing::field::Empty
 )
 )]
 pub async fn subscribe(
-    form: web::Form<FormData>,
+    body: Either<web::Form<FormData>, web::Json<FormData>>,
+    request: HttpRequest,
     connection: web::Data<DbConnectionKind>, // connection is passed from application state
     email_client: web::Data<EmailClient>,
     base_url: web::Data<ApplicationBaseUrl>,
+    protection: web::Data<SubscriptionProtection>,
+    domain_policy: web::Data<EmailDomainPolicy>,
+    hmac_secret: web::Data<HmacSecret>,
 ) -> Result<HttpResponse, SubscribeError> {
-    let new_subscriber: NewSubscriber = form.0.try_into()?;
+    let (form, is_html_form) = match body {
+        Either::Left(form) => (form.0, true),
+        Either::Right(json) => (json.0, false),
+    };
+    let client_ip = client_ip(&request);
+    let accept_language = request
+        .headers()
+        .get(ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok());
+    let locale = Locale::negotiate(form.locale.as_deref(), accept_language);
+    tracing::Span::current()
+        .record("subscriber_email", &tracing::field::display(&form.email))
+        .record("subscriber_name", &tracing::field::display(&form.name))
+        .record("client_ip", &tracing::field::display(&client_ip));
+
+    if let Err(reason) = protection.check_honeypot(&form.website) {
+        // Bots are told they succeeded so that they have no reason to adapt.
+        log_rejection(reason);
+        return Ok(subscribed_response(is_html_form, locale));
+    }
+    // JSON bodies need a form token too, or bots would simply post JSON instead.
+    let form_token = protection
+        .check_form_token(form.form_token.as_deref())
+        .map_err(reject)?;
+    protection.check_ip(&client_ip).map_err(reject)?;
+
+    let attribute_schema = get_attribute_schema(&connection)
+        .await
+        .map_err(SubscribeError::AttributeSchemaError)?;
+    let new_subscriber = NewSubscriber::parse(form, &domain_policy, &attribute_schema, locale)?;
+    protection.check_email_domain(&new_subscriber.email).map_err(reject)?;
+    protection.spend_form_token(form_token).map_err(reject)?;
 
     // We create a transaction at the endpoint level so that all of the DB updates
     // that is made below will all be committed/rollbacked together (handled atomically)
@@ -149,54 +257,95 @@ pub async fn subscribe(
 
     let _request_span_guard = request_span.enter();
 
-    let subscriber_id = insert_subscriber(&mut transaction, &new_subscriber)
+    let subscriber_id = match insert_subscriber(&mut transaction, &new_subscriber)
         .await
-        .map_err(SubscribeError::InsertSubscriberError)?;
+        .map_err(SubscribeError::InsertSubscriberError)?
+    {
+        Some(subscriber_id) => subscriber_id,
+        // Answered like any other sign up, so that the form does not tell who is
+        // subscribed.
+        None => return Ok(subscribed_response(is_html_form, locale)),
+    };
 
     let subscription_token = generate_subscription_token();
 
     store_token(&mut transaction, &subscriber_id, &subscription_token).await?;
     transaction.commit().await.map_err(SubscribeError::TransactionCommitError)?;
 
-   send_confirmation_email(
+    send_confirmation_email(
         &email_client,
         new_subscriber,
         &base_url.0,
         &subscription_token,
+        &preferences_link(&base_url.0, &hmac_secret.0, subscriber_id),
     ).await?;
 
-    Ok(HttpResponse::Ok().finish())
+    Ok(subscribed_response(is_html_form, locale))
+}
+
+// People submitting our HTML form are shown a page; API clients only need the status.
+fn subscribed_response(is_html_form: bool, locale: Locale) -> HttpResponse {
+    if is_html_form {
+        message_page(locale, "subscribed_page_title", "subscribed_page_body")
+    } else {
+        HttpResponse::Ok().finish()
+    }
+}
+
+fn log_rejection(reason: RejectionReason) {
+    tracing::warn!(reason = reason.code(), "Rejected a subscription attempt");
+}
+
+fn reject(reason: RejectionReason) -> SubscribeError {
+    log_rejection(reason);
+    SubscribeError::Rejected(reason)
 }
 
 #[tracing::instrument(
 name = "Send a confirmation email to a new subscriber",
-skip(email_client, new_subscriber, base_url)
+skip(email_client, new_subscriber, base_url, preferences_link)
 )]
 pub async fn send_confirmation_email(
     email_client: &EmailClient,
     new_subscriber: NewSubscriber,
     base_url: &str,
     subscription_token: &str,
+    preferences_link: &str,
 ) -> Result<(), reqwest::Error> {
     let confirmation_link = format!(
         "{base_url}/subscriptions/confirm?subscription_token={token}",
         base_url = base_url,
         token = subscription_token
     );
+    let locale = new_subscriber.locale;
     let html_body = &format!(
-        "<h1>Welcome</h1><br/>Welcome to our newsletter! Click <a href=\"{url}\">here</a> to confirm your subscription.",
-        url = confirmation_link
+        "{welcome}<br/><a href=\"{preferences_link}\">{manage_preferences}</a>",
+        welcome = locale.message("confirmation_email_html").replace("{confirmation_link}", &confirmation_link),
+        preferences_link = preferences_link,
+        manage_preferences = locale.message("manage_preferences"),
+    );
+    let text_body = &format!(
+        "{welcome}\n\n{manage_preferences}: {preferences_link}",
+        welcome = locale.message("confirmation_email_text").replace("{confirmation_link}", &confirmation_link),
+        manage_preferences = locale.message("manage_preferences"),
+        preferences_link = preferences_link
     );
-    let text_body = &format!("Welcome to our newsletter!\nVisit {url} to confirm your subscription", url = confirmation_link);
     email_client.send_email(
         &new_subscriber.email,
-        "Welcome!",
+        locale.message("confirmation_email_subject"),
         html_body,
         text_body,
     )
         .await
 }
 
+// Someone who unsubscribed, or whose address bounced, can sign up again with the
+// same email: the subscription goes back to pending confirmation, with the details
+// they entered this time, and its old confirmation links stop working. It counts
+// as subscribed from now, so that the pending subscription cleanup gives it the
+// full time to be confirmed. Signing up again while still pending sends a new
+// confirmation email. Returns `None` for emails that are confirmed or complained
+// about us, which are left alone.
 #[tracing::instrument(
 name = "Saving new subscriber in DB",
 skip(new_subscriber, transaction),
@@ -204,24 +353,68 @@ skip(new_subscriber, transaction),
 pub async fn insert_subscriber(
     transaction: &mut Transaction<'_, Postgres>,
     new_subscriber: &NewSubscriber,
-) -> Result<Uuid, sqlx::Error> {
+) -> Result<Option<Uuid>, anyhow::Error> {
     let subscriber_id = Uuid::new_v4();
-    sqlx::query!(
+    let inserted = sqlx::query!(
         r#"
-        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
-        VALUES ($1, $2, $3, $4, 'pending_confirmation')
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status, attributes, locale)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        ON CONFLICT ((lower(email))) DO NOTHING
         "#,
         subscriber_id,
         new_subscriber.email.as_ref(),
         new_subscriber.name.as_ref(),
-        Utc::now()
+        Utc::now(),
+        SubscriptionStatus::PendingConfirmation.as_str(),
+        new_subscriber.attributes.to_json(),
+        new_subscriber.locale.as_str()
     )
-        .execute(transaction)
+        .execute(&mut *transaction)
         .await
-        .map_err(|e| {
-            e
-        })?;
-    Ok(subscriber_id)
+        .context("Failed to insert the new subscriber")?
+        .rows_affected();
+    if inserted == 1 {
+        record_initial_status(transaction, subscriber_id, SubscriptionStatus::PendingConfirmation)
+            .await
+            .context("Failed to record the initial subscription status")?;
+        return Ok(Some(subscriber_id));
+    }
+
+    let subscriber_id = sqlx::query!(
+        "SELECT id FROM subscriptions WHERE lower(email) = lower($1)",
+        new_subscriber.email.as_ref()
+    )
+        .fetch_one(&mut *transaction)
+        .await
+        .context("Failed to retrieve the existing subscriber")?
+        .id;
+    match change_status(transaction, subscriber_id, SubscriptionStatus::PendingConfirmation).await {
+        Ok(_) => {}
+        Err(StatusChangeError::NotAllowed { from, .. }) => {
+            tracing::info!(status = %from, "The email is already subscribed, nothing to do");
+            return Ok(None);
+        }
+        Err(e) => return Err(e.into()),
+    }
+    sqlx::query!(
+        "UPDATE subscriptions SET name = $1, attributes = $2, locale = $3, subscribed_at = $4 WHERE id = $5",
+        new_subscriber.name.as_ref(),
+        new_subscriber.attributes.to_json(),
+        new_subscriber.locale.as_str(),
+        Utc::now(),
+        subscriber_id
+    )
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to update the returning subscriber")?;
+    sqlx::query!(
+        "DELETE FROM subscription_tokens WHERE subscriber_id = $1",
+        subscriber_id
+    )
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to remove the old subscription tokens")?;
+    Ok(Some(subscriber_id))
 }
 
 #[tracing::instrument(
@@ -250,7 +443,7 @@ pub async fn store_token(
     Ok(())
 }
 
-fn generate_subscription_token() -> String {
+pub fn generate_subscription_token() -> String {
     let mut rng = thread_rng();
     std::iter::repeat_with(|| rng.sample(Alphanumeric))
         .map(char::from)
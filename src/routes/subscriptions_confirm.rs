@@ -5,6 +5,10 @@ use sqlx::{Transaction, Postgres};
 use std::fmt::Formatter;
 use std::error::Error;
 use actix_web::http::StatusCode;
+use crate::domain::SubscriptionStatus;
+use crate::subscription_lifecycle::{change_status, StatusChangeError};
+use crate::i18n::Locale;
+use crate::utils::message_page;
 
 #[derive(serde::Deserialize)]
 pub struct Parameters {
@@ -16,7 +20,8 @@ pub enum ConfirmError {
     PoolError(sqlx::Error),
     ResourceNotFound(sqlx::Error),
     TransactionCommitError(sqlx::Error),
-    StatusUpdateError(sqlx::Error),
+    StatusUpdateError(StatusChangeError),
+    LocaleLookupError(sqlx::Error),
 }
 
 impl std::fmt::Display for ConfirmError {
@@ -25,7 +30,8 @@ impl std::fmt::Display for ConfirmError {
             ConfirmError::PoolError(_) => write!(f, "Failed to acquire a postgress connection"),
             ConfirmError::ResourceNotFound(_) => write!(f, "Resource does not exist"),
             ConfirmError::TransactionCommitError(_) => write!(f, "Failed to commit SQL transaction to store a new subscriber"),
-            ConfirmError::StatusUpdateError(_) => write!(f, "Failed to set status of subscriber")
+            ConfirmError::StatusUpdateError(_) => write!(f, "Failed to set status of subscriber"),
+            ConfirmError::LocaleLookupError(_) => write!(f, "Failed to retrieve the locale of subscriber")
         }
     }
 }
@@ -37,6 +43,7 @@ impl std::error::Error for ConfirmError {
             ConfirmError::ResourceNotFound(e) => Some(e),
             ConfirmError::TransactionCommitError(e) => Some(e),
             ConfirmError::StatusUpdateError(e) => Some(e),
+            ConfirmError::LocaleLookupError(e) => Some(e),
         }
     }
 }
@@ -45,8 +52,11 @@ impl ResponseError for ConfirmError {
     fn status_code(&self) -> StatusCode {
         match self {
             ConfirmError::ResourceNotFound(_) => StatusCode::BAD_REQUEST,
+            // e.g. an old confirmation link used after unsubscribing
+            ConfirmError::StatusUpdateError(StatusChangeError::NotAllowed { .. }) => StatusCode::BAD_REQUEST,
             ConfirmError::PoolError(_) |
             ConfirmError::TransactionCommitError(_) |
+            ConfirmError::LocaleLookupError(_) |
             ConfirmError::StatusUpdateError(_)=> StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
@@ -79,8 +89,11 @@ pub async fn confirm(
             confirm_subscriber(&mut transaction, id)
                 .await
                 .map_err(ConfirmError::StatusUpdateError)?;
+            let locale = get_subscriber_locale(&mut transaction, id)
+                .await
+                .map_err(ConfirmError::LocaleLookupError)?;
             transaction.commit().await.map_err(ConfirmError::TransactionCommitError)?;
-            Ok(HttpResponse::Ok().finish())
+            Ok(message_page(locale, "confirmed_page_title", "confirmed_page_body"))
         }
     }
 }
@@ -92,24 +105,33 @@ skip(transaction, subscriber_id)
 pub async fn confirm_subscriber(
     transaction: &mut Transaction<'_, Postgres>,
     subscriber_id: Uuid,
-) -> Result<(), sqlx::Error> {
-    sqlx::query!(
-        r#"
-        UPDATE subscriptions
-        SET status = 'confirmed'
-        WHERE id = $1
-        "#,
-        subscriber_id
-    )
-        .execute(transaction)
+) -> Result<(), StatusChangeError> {
+    change_status(transaction, subscriber_id, SubscriptionStatus::Confirmed)
         .await
         .map_err(|e| {
-            tracing::error!("Failed to execute query: {:?}", e);
+            tracing::error!("Failed to confirm subscriber: {:?}", e);
             e
         })?;
     Ok(())
 }
 
+#[tracing::instrument(
+name = "Retrieving subscriber locale",
+skip(transaction)
+)]
+pub async fn get_subscriber_locale(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<Locale, sqlx::Error> {
+    let row = sqlx::query!(
+        "SELECT locale FROM subscriptions WHERE id = $1",
+        subscriber_id
+    )
+        .fetch_one(transaction)
+        .await?;
+    Ok(Locale::parse(&row.locale).unwrap_or_default())
+}
+
 #[tracing::instrument(
 name = "Retrieving subscriber id from token",
 skip(transaction, subscription_token)
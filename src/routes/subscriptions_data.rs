@@ -0,0 +1,416 @@
+use actix_web::{HttpRequest, HttpResponse, web, ResponseError};
+use actix_web::http::StatusCode;
+use actix_web::http::header::ContentType;
+use anyhow::Context;
+use chrono::{DateTime, Duration, Utc};
+use sqlx::{Transaction, Postgres};
+use std::fmt::Formatter;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::domain::subscriber_email::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::routes::error_chain_fmt;
+use crate::routes::subscriptions_preferences::preferences_link;
+use crate::signed_token;
+use crate::startup::{DbConnectionKind, ApplicationBaseUrl, HmacSecret};
+use crate::subscriber_data_throttle::SubscriberDataThrottle;
+use crate::subscription_lifecycle::delete_subscribers;
+use crate::utils::client_ip;
+
+const EXPORT_PURPOSE: &str = "subscriber-data-export";
+const ERASURE_PURPOSE: &str = "subscriber-data-erasure";
+
+fn link_time_to_live() -> Duration {
+    Duration::hours(24)
+}
+
+#[derive(serde::Deserialize)]
+pub struct RequestFormData {
+    email: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct Parameters {
+    token: String,
+}
+
+#[derive(thiserror::Error)]
+pub enum SubscriberDataError {
+    #[error("{0}")]
+    ValidationError(String),
+    #[error("This link is invalid or has expired")]
+    InvalidLink(#[source] anyhow::Error),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for SubscriberDataError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for SubscriberDataError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            SubscriberDataError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            SubscriberDataError::InvalidLink(_) => StatusCode::UNAUTHORIZED,
+            SubscriberDataError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+pub async fn data_request_form() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Your data</title>
+</head>
+<body>
+    <p>Enter the email you subscribed with and we will send you a link to download or erase your data.</p>
+    <form action="/subscriptions/data" method="post">
+        <label>Email
+            <input
+                type="text"
+                placeholder="Enter your email"
+                name="email"
+            >
+        </label>
+        <button type="submit">Send me a link</button>
+    </form>
+</body>
+</html>"#)
+}
+
+// The response is the same whether or not the email is subscribed, so that this
+// endpoint cannot be used to find out who is on the list. Looking the subscriber
+// up and emailing them happen after the response is sent, so that it does not
+// take longer to answer, or fail, only for subscribed emails.
+#[tracing::instrument(
+name = "Requesting subscriber data links",
+skip(form, request, database, email_client, base_url, hmac_secret, throttle),
+fields(client_ip = tracing::field::Empty)
+)]
+pub async fn request_subscriber_data(
+    form: web::Form<RequestFormData>,
+    request: HttpRequest,
+    database: web::Data<DbConnectionKind>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    hmac_secret: web::Data<HmacSecret>,
+    throttle: web::Data<SubscriberDataThrottle>,
+) -> Result<HttpResponse, SubscriberDataError> {
+    let email = SubscriberEmail::parse(form.0.email)
+        .map_err(SubscriberDataError::ValidationError)?;
+
+    let client_ip = client_ip(&request);
+    tracing::Span::current().record("client_ip", &tracing::field::display(&client_ip));
+    if throttle.check(email.as_ref(), &client_ip) {
+        actix_web::rt::spawn(send_subscriber_data_links(
+            email,
+            database.into_inner(),
+            email_client.into_inner(),
+            base_url.into_inner(),
+            hmac_secret.into_inner(),
+        ));
+    } else {
+        tracing::warn!("Too many subscriber data requests, no email is sent");
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body("<p>If that email is subscribed, we have sent it a link to manage your data.</p>"))
+}
+
+#[tracing::instrument(
+name = "Sending subscriber data links",
+skip(email, database, email_client, base_url, hmac_secret),
+fields(subscriber_id = tracing::field::Empty)
+)]
+async fn send_subscriber_data_links(
+    email: SubscriberEmail,
+    database: Arc<DbConnectionKind>,
+    email_client: Arc<EmailClient>,
+    base_url: Arc<ApplicationBaseUrl>,
+    hmac_secret: Arc<HmacSecret>,
+) {
+    let subscriber_id = match get_subscriber_id_from_email(&database, &email).await {
+        Ok(Some(subscriber_id)) => subscriber_id,
+        Ok(None) => return,
+        Err(e) => {
+            tracing::error!(error.cause_chain = ?e, "Failed to look up the subscriber");
+            return;
+        }
+    };
+    tracing::Span::current().record("subscriber_id", &tracing::field::display(&subscriber_id));
+    if let Err(e) = send_data_links_email(
+        &email_client,
+        &email,
+        &base_url.0,
+        &hmac_secret.0,
+        subscriber_id,
+    ).await {
+        tracing::error!(error.cause_chain = ?e, "Failed to send subscriber data links");
+    }
+}
+
+#[tracing::instrument(
+name = "Send subscriber data links",
+skip(email_client, email, base_url, hmac_secret)
+)]
+async fn send_data_links_email(
+    email_client: &EmailClient,
+    email: &SubscriberEmail,
+    base_url: &str,
+    hmac_secret: &secrecy::Secret<String>,
+    subscriber_id: Uuid,
+) -> Result<(), reqwest::Error> {
+    let export_link = format!(
+        "{}/subscriptions/data/export?token={}",
+        base_url,
+        signed_token::generate(hmac_secret, EXPORT_PURPOSE, subscriber_id, link_time_to_live())
+    );
+    let erasure_link = format!(
+        "{}/subscriptions/data/erase?token={}",
+        base_url,
+        signed_token::generate(hmac_secret, ERASURE_PURPOSE, subscriber_id, link_time_to_live())
+    );
+    let preferences_link = preferences_link(base_url, hmac_secret, subscriber_id);
+    let html_body = format!(
+        "You asked for the data we hold about you.<br/>\
+        Click <a href=\"{}\">here</a> to download it, or \
+        <a href=\"{}\">here</a> to erase it.<br/>\
+        These links expire in 24 hours.<br/>\
+        <a href=\"{}\">Manage your preferences</a>",
+        export_link, erasure_link, preferences_link
+    );
+    let text_body = format!(
+        "You asked for the data we hold about you.\n\
+        Visit {} to download it.\n\
+        Visit {} to erase it.\n\
+        These links expire in 24 hours.\n\n\
+        Manage your preferences: {}",
+        export_link, erasure_link, preferences_link
+    );
+    email_client.send_email(email, "Your subscriber data", &html_body, &text_body)
+        .await
+}
+
+#[derive(serde::Serialize)]
+pub struct SubscriberDataExport {
+    subscription: SubscriptionRecord,
+    subscription_tokens: Vec<String>,
+    deliveries: Vec<DeliveryRecord>,
+    status_changes: Vec<StatusChangeRecord>,
+}
+
+#[derive(serde::Serialize)]
+struct SubscriptionRecord {
+    id: Uuid,
+    email: String,
+    name: String,
+    subscribed_at: DateTime<Utc>,
+    status: String,
+    locale: String,
+}
+
+#[derive(serde::Serialize)]
+struct DeliveryRecord {
+    title: String,
+    delivered_at: DateTime<Utc>,
+}
+
+#[derive(serde::Serialize)]
+struct StatusChangeRecord {
+    from_status: Option<String>,
+    to_status: String,
+    changed_at: DateTime<Utc>,
+}
+
+#[tracing::instrument(
+name = "Exporting subscriber data",
+skip(params, database, hmac_secret)
+)]
+pub async fn export_subscriber_data(
+    params: web::Query<Parameters>,
+    database: web::Data<DbConnectionKind>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, SubscriberDataError> {
+    let subscriber_id = signed_token::verify(&hmac_secret.0, EXPORT_PURPOSE, &params.token)
+        .map_err(SubscriberDataError::InvalidLink)?;
+
+    let subscription = sqlx::query_as!(
+        SubscriptionRecord,
+        r#"
+        SELECT id, email, name, subscribed_at, status, locale
+        FROM subscriptions
+        WHERE id = $1
+        "#,
+        subscriber_id
+    )
+        .fetch_optional(database.get_ref())
+        .await
+        .context("Failed to retrieve subscription")?
+        .ok_or_else(|| SubscriberDataError::InvalidLink(anyhow::anyhow!("The subscriber no longer exists")))?;
+
+    let subscription_tokens = sqlx::query!(
+        r#"
+        SELECT subscription_token
+        FROM subscription_tokens
+        WHERE subscriber_id = $1
+        "#,
+        subscriber_id
+    )
+        .fetch_all(database.get_ref())
+        .await
+        .context("Failed to retrieve subscription tokens")?
+        .into_iter()
+        .map(|row| row.subscription_token)
+        .collect();
+
+    let deliveries = sqlx::query_as!(
+        DeliveryRecord,
+        r#"
+        SELECT title, delivered_at
+        FROM newsletter_deliveries
+        WHERE subscriber_id = $1
+        ORDER BY delivered_at
+        "#,
+        subscriber_id
+    )
+        .fetch_all(database.get_ref())
+        .await
+        .context("Failed to retrieve newsletter deliveries")?;
+
+    let status_changes = sqlx::query_as!(
+        StatusChangeRecord,
+        r#"
+        SELECT from_status, to_status, changed_at
+        FROM subscription_status_changes
+        WHERE subscriber_id = $1
+        ORDER BY changed_at
+        "#,
+        subscriber_id
+    )
+        .fetch_all(database.get_ref())
+        .await
+        .context("Failed to retrieve subscription status changes")?;
+
+    Ok(HttpResponse::Ok()
+        .insert_header(("Content-Disposition", "attachment; filename=\"subscriber-data.json\""))
+        .json(SubscriberDataExport {
+            subscription,
+            subscription_tokens,
+            deliveries,
+            status_changes,
+        }))
+}
+
+pub async fn erasure_form(
+    params: web::Query<Parameters>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, SubscriberDataError> {
+    signed_token::verify(&hmac_secret.0, ERASURE_PURPOSE, &params.token)
+        .map_err(SubscriberDataError::InvalidLink)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Erase your data</title>
+</head>
+<body>
+    <p>This will unsubscribe you and permanently erase everything we store about you.</p>
+    <form action="/subscriptions/data/erase" method="post">
+        <input hidden type="text" name="token" value="{}">
+        <button type="submit">Erase my data</button>
+    </form>
+</body>
+</html>"#,
+            htmlescape::encode_attribute(&params.token)
+        )))
+}
+
+#[tracing::instrument(
+name = "Erasing subscriber data",
+skip(form, database, hmac_secret)
+)]
+pub async fn erase_subscriber_data(
+    form: web::Form<Parameters>,
+    database: web::Data<DbConnectionKind>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, SubscriberDataError> {
+    let subscriber_id = signed_token::verify(&hmac_secret.0, ERASURE_PURPOSE, &form.token)
+        .map_err(SubscriberDataError::InvalidLink)?;
+
+    let mut transaction = database
+        .begin()
+        .await
+        .context("Failed to acquire a postgres connection")?;
+    let erased = erase_subscriber(&mut transaction, subscriber_id)
+        .await
+        .context("Failed to erase subscriber data")?;
+    if !erased {
+        return Err(SubscriberDataError::InvalidLink(anyhow::anyhow!("The subscriber no longer exists")));
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to erase a subscriber")?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body("<p>Your data has been erased.</p>"))
+}
+
+#[tracing::instrument(
+name = "Deleting every row referencing a subscriber",
+skip(transaction)
+)]
+pub async fn erase_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    // Earlier sign-ups that were never confirmed may have been archived by the
+    // pending subscription cleanup, with the same email and name.
+    sqlx::query!(
+        r#"
+        DELETE FROM archived_pending_subscriptions
+        WHERE id = $1
+            OR lower(email) IN (SELECT lower(email) FROM subscriptions WHERE id = $1)
+        "#,
+        subscriber_id
+    )
+        .execute(&mut *transaction)
+        .await?;
+    let deleted = delete_subscribers(transaction, &[subscriber_id]).await?;
+    Ok(deleted.subscriptions > 0)
+}
+
+#[tracing::instrument(
+name = "Retrieving subscriber id from email",
+skip(database, email)
+)]
+async fn get_subscriber_id_from_email(
+    database: &DbConnectionKind,
+    email: &SubscriberEmail,
+) -> Result<Option<Uuid>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT id FROM subscriptions
+        WHERE lower(email) = lower($1)
+        "#,
+        email.as_ref()
+    )
+        .fetch_optional(database)
+        .await
+        .context("Failed to retrieve subscriber by email")?;
+    Ok(row.map(|r| r.id))
+}
@@ -0,0 +1,112 @@
+use actix_web::{HttpResponse, web};
+use actix_web::http::header::ContentType;
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use std::fmt::Write;
+
+use crate::domain::SubscriptionStatus;
+use crate::routes::subscriptions_preferences::{verify_token, PreferencesError};
+use crate::startup::{DbConnectionKind, HmacSecret};
+
+#[derive(serde::Deserialize)]
+pub struct Parameters {
+    token: String,
+}
+
+pub async fn preferences_form(
+    params: web::Query<Parameters>,
+    database: web::Data<DbConnectionKind>,
+    hmac_secret: web::Data<HmacSecret>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, PreferencesError> {
+    let subscriber_id = verify_token(&hmac_secret.0, &params.token)?;
+    let subscriber = sqlx::query!(
+        r#"
+        SELECT name, status, paused_until
+        FROM subscriptions
+        WHERE id = $1
+        "#,
+        subscriber_id
+    )
+        .fetch_optional(database.get_ref())
+        .await
+        .context("Failed to retrieve subscriber preferences")?
+        .ok_or_else(|| PreferencesError::InvalidLink(anyhow::anyhow!("The subscriber no longer exists")))?;
+
+    let mut msg_html = String::new();
+    for message in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", htmlescape::encode_minimal(message.content())).unwrap();
+    }
+
+    let token = htmlescape::encode_attribute(&params.token);
+    let has_left = matches!(
+        SubscriptionStatus::parse(&subscriber.status),
+        Ok(SubscriptionStatus::Unsubscribed | SubscriptionStatus::Complained)
+    );
+    let body = if has_left {
+        "<p>You are unsubscribed and will not receive any more emails from us.</p>".to_string()
+    } else {
+        format!(
+            r#"<p>{delivery}</p>
+    <form action="/subscriptions/preferences/name" method="post">
+        <input hidden type="text" name="token" value="{token}">
+        <label>Name
+            <input
+                type="text"
+                name="name"
+                value="{name}"
+            >
+        </label>
+        <button type="submit">Update name</button>
+    </form>
+    <form action="/subscriptions/preferences/pause" method="post">
+        <input hidden type="text" name="token" value="{token}">
+        <label>Pause emails for
+            <select name="days">
+                <option value="7">1 week</option>
+                <option value="30">1 month</option>
+                <option value="90">3 months</option>
+                <option value="0">Resume now</option>
+            </select>
+        </label>
+        <button type="submit">Save</button>
+    </form>
+    <form action="/subscriptions/preferences/unsubscribe" method="post">
+        <input hidden type="text" name="token" value="{token}">
+        <button type="submit">Unsubscribe</button>
+    </form>"#,
+            delivery = describe_delivery(subscriber.paused_until),
+            token = token,
+            name = htmlescape::encode_attribute(&subscriber.name),
+        )
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Your preferences</title>
+</head>
+<body>
+    {msg_html}
+    {body}
+</body>
+</html>"#,
+            msg_html = msg_html,
+            body = body,
+        )))
+}
+
+fn describe_delivery(paused_until: Option<DateTime<Utc>>) -> String {
+    match paused_until {
+        Some(paused_until) if paused_until > Utc::now() => format!(
+            "Your emails are paused until {}.",
+            paused_until.format("%d %B %Y")
+        ),
+        _ => "You are receiving our emails.".to_string(),
+    }
+}
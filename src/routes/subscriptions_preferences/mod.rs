@@ -0,0 +1,68 @@
+use actix_web::ResponseError;
+use actix_web::http::StatusCode;
+use chrono::Duration;
+use secrecy::Secret;
+use std::fmt::Formatter;
+use uuid::Uuid;
+
+use crate::routes::error_chain_fmt;
+use crate::signed_token;
+
+mod get;
+mod post;
+
+pub use get::*;
+pub use post::*;
+
+const PREFERENCES_PURPOSE: &str = "subscriber-preferences";
+
+fn link_time_to_live() -> Duration {
+    Duration::days(30)
+}
+
+// Every email we send carries one of these, so that subscribers can manage their
+// subscription without an account.
+pub fn preferences_link(base_url: &str, hmac_secret: &Secret<String>, subscriber_id: Uuid) -> String {
+    format!(
+        "{}{}",
+        base_url,
+        preferences_path(&signed_token::generate(
+            hmac_secret,
+            PREFERENCES_PURPOSE,
+            subscriber_id,
+            link_time_to_live(),
+        ))
+    )
+}
+
+fn preferences_path(token: &str) -> String {
+    format!("/subscriptions/preferences?token={}", token)
+}
+
+fn verify_token(hmac_secret: &Secret<String>, token: &str) -> Result<Uuid, PreferencesError> {
+    signed_token::verify(hmac_secret, PREFERENCES_PURPOSE, token)
+        .map_err(PreferencesError::InvalidLink)
+}
+
+#[derive(thiserror::Error)]
+pub enum PreferencesError {
+    #[error("This link is invalid or has expired")]
+    InvalidLink(#[source] anyhow::Error),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for PreferencesError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for PreferencesError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            PreferencesError::InvalidLink(_) => StatusCode::UNAUTHORIZED,
+            PreferencesError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
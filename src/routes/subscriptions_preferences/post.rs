@@ -0,0 +1,130 @@
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use chrono::{Duration, Utc};
+
+use crate::domain::{SubscriberName, SubscriptionStatus};
+use crate::routes::subscriptions_preferences::{preferences_path, verify_token, PreferencesError};
+use crate::startup::{DbConnectionKind, HmacSecret};
+use crate::subscription_lifecycle::{change_status, StatusChangeError};
+use crate::utils::see_other;
+
+const MAXIMUM_PAUSE_DAYS: u32 = 365;
+
+#[derive(serde::Deserialize)]
+pub struct NameFormData {
+    token: String,
+    name: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct PauseFormData {
+    token: String,
+    days: u32,
+}
+
+#[derive(serde::Deserialize)]
+pub struct UnsubscribeFormData {
+    token: String,
+}
+
+#[tracing::instrument(
+name = "Updating subscriber name",
+skip(form, database, hmac_secret)
+)]
+pub async fn update_name(
+    form: web::Form<NameFormData>,
+    database: web::Data<DbConnectionKind>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, PreferencesError> {
+    let NameFormData { token, name } = form.0;
+    let subscriber_id = verify_token(&hmac_secret.0, &token)?;
+
+    match SubscriberName::parse(name) {
+        Ok(name) => {
+            sqlx::query!(
+                "UPDATE subscriptions SET name = $1 WHERE id = $2",
+                name.as_ref(),
+                subscriber_id
+            )
+                .execute(database.get_ref())
+                .await
+                .context("Failed to update subscriber name")?;
+            FlashMessage::info("Your name has been updated.").send();
+        }
+        Err(e) => FlashMessage::error(e).send(),
+    }
+    Ok(see_other(&preferences_path(&token)))
+}
+
+#[tracing::instrument(
+name = "Pausing subscriber deliveries",
+skip(form, database, hmac_secret)
+)]
+pub async fn pause_delivery(
+    form: web::Form<PauseFormData>,
+    database: web::Data<DbConnectionKind>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, PreferencesError> {
+    let PauseFormData { token, days } = form.0;
+    let subscriber_id = verify_token(&hmac_secret.0, &token)?;
+
+    if days > MAXIMUM_PAUSE_DAYS {
+        FlashMessage::error(format!("Emails can be paused for at most {} days.", MAXIMUM_PAUSE_DAYS)).send();
+        return Ok(see_other(&preferences_path(&token)));
+    }
+
+    let paused_until = if days == 0 {
+        None
+    } else {
+        Some(Utc::now() + Duration::days(days as i64))
+    };
+    sqlx::query!(
+        "UPDATE subscriptions SET paused_until = $1 WHERE id = $2",
+        paused_until,
+        subscriber_id
+    )
+        .execute(database.get_ref())
+        .await
+        .context("Failed to pause subscriber deliveries")?;
+
+    if paused_until.is_some() {
+        FlashMessage::info("Your emails have been paused.").send();
+    } else {
+        FlashMessage::info("Your emails have been resumed.").send();
+    }
+    Ok(see_other(&preferences_path(&token)))
+}
+
+#[tracing::instrument(
+name = "Unsubscribing a subscriber",
+skip(form, database, hmac_secret)
+)]
+pub async fn unsubscribe(
+    form: web::Form<UnsubscribeFormData>,
+    database: web::Data<DbConnectionKind>,
+    hmac_secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, PreferencesError> {
+    let token = form.0.token;
+    let subscriber_id = verify_token(&hmac_secret.0, &token)?;
+
+    let mut transaction = database
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    match change_status(&mut transaction, subscriber_id, SubscriptionStatus::Unsubscribed).await {
+        Ok(_) => FlashMessage::info("You have been unsubscribed.").send(),
+        Err(StatusChangeError::SubscriberNotFound) => {
+            return Err(PreferencesError::InvalidLink(anyhow::anyhow!("The subscriber no longer exists")));
+        }
+        // Nothing is sent to subscribers who complained, so there is nothing to stop.
+        Err(StatusChangeError::NotAllowed { .. }) => FlashMessage::info("You will not receive any more emails from us.").send(),
+        Err(e) => return Err(anyhow::Error::new(e).context("Failed to unsubscribe subscriber").into()),
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit unsubscription")?;
+
+    Ok(see_other(&preferences_path(&token)))
+}
@@ -3,17 +3,29 @@ use uuid::Uuid;
 use actix_web::{FromRequest, HttpRequest};
 use actix_web::dev::Payload;
 use std::future::{Ready, ready};
+use chrono::{DateTime, Utc};
 
 pub struct TypedSession(Session);
 
 impl TypedSession {
     const USER_ID_KEY: &'static str = "user_id";
+    const LOGGED_IN_AT_KEY: &'static str = "logged_in_at";
+    const PENDING_LOGIN_KEY: &'static str = "pending_login";
+    const CSRF_TOKEN_KEY: &'static str = "csrf_token";
+    const SESSION_ID_KEY: &'static str = "session_id";
+    // How long a user who entered their password has to enter their second factor.
+    const PENDING_LOGIN_SECONDS: i64 = 5 * 60;
 
     pub fn renew(&self) {
         self.0.renew();
     }
 
-    pub fn insert_user_id(&self, user_id: Uuid) -> Result<(), serde_json::Error> {
+    // A new login also gets a new CSRF token. `session_id` is the login's entry in
+    // the session index, see `start_user_session`.
+    pub fn insert_user_id(&self, user_id: Uuid, session_id: Uuid) -> Result<(), serde_json::Error> {
+        self.0.remove(Self::CSRF_TOKEN_KEY);
+        self.0.insert(Self::LOGGED_IN_AT_KEY, Utc::now())?;
+        self.0.insert(Self::SESSION_ID_KEY, session_id)?;
         self.0.insert(Self::USER_ID_KEY, user_id)
     }
 
@@ -21,11 +33,55 @@ impl TypedSession {
         self.0.get(Self::USER_ID_KEY)
     }
 
+    pub fn get_session_id(&self) -> Result<Option<Uuid>, serde_json::Error> {
+        self.0.get(Self::SESSION_ID_KEY)
+    }
+
+    // Used to tell whether the session predates the user's sessions being revoked.
+    pub fn get_logged_in_at(&self) -> Result<Option<DateTime<Utc>>, serde_json::Error> {
+        self.0.get(Self::LOGGED_IN_AT_KEY)
+    }
+
+    // Remembers a user who has entered their password but not their second factor.
+    // They are not logged in until `insert_user_id` is called.
+    pub fn insert_pending_login(&self, user_id: Uuid) -> Result<(), serde_json::Error> {
+        let pending_login = PendingLogin {
+            user_id,
+            expires_at: Utc::now().timestamp() + Self::PENDING_LOGIN_SECONDS,
+        };
+        self.0.insert(Self::PENDING_LOGIN_KEY, pending_login)
+    }
+
+    pub fn get_pending_login(&self) -> Result<Option<Uuid>, serde_json::Error> {
+        let pending_login: Option<PendingLogin> = self.0.get(Self::PENDING_LOGIN_KEY)?;
+        Ok(pending_login
+            .filter(|pending_login| pending_login.expires_at > Utc::now().timestamp())
+            .map(|pending_login| pending_login.user_id))
+    }
+
+    pub fn remove_pending_login(&self) {
+        self.0.remove(Self::PENDING_LOGIN_KEY);
+    }
+
+    pub fn get_csrf_token(&self) -> Result<Option<String>, serde_json::Error> {
+        self.0.get(Self::CSRF_TOKEN_KEY)
+    }
+
+    pub fn insert_csrf_token(&self, token: &str) -> Result<(), serde_json::Error> {
+        self.0.insert(Self::CSRF_TOKEN_KEY, token)
+    }
+
     pub fn log_out(self) {
         self.0.purge()
     }
 }
 
+#[derive(serde::Serialize, serde::Deserialize)]
+struct PendingLogin {
+    user_id: Uuid,
+    expires_at: i64,
+}
+
 impl FromRequest for TypedSession {
     type Error = <Session as FromRequest>::Error;
     type Future = Ready<Result<TypedSession, Self::Error>>;
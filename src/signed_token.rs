@@ -0,0 +1,112 @@
+use anyhow::Context;
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use uuid::Uuid;
+
+// Tokens have the shape `{subject}.{expires_at}.{tag}` so they can be embedded in
+// a query string as-is. The purpose is part of the signed payload but not of the
+// token itself: a token minted for one flow fails verification in any other flow.
+pub fn generate(
+    secret: &Secret<String>,
+    purpose: &str,
+    subject: Uuid,
+    time_to_live: Duration,
+) -> String {
+    let expires_at = (Utc::now() + time_to_live).timestamp();
+    let tag = hex::encode(sign(secret, purpose, subject, expires_at));
+    format!("{}.{}.{}", subject, expires_at, tag)
+}
+
+pub fn verify(
+    secret: &Secret<String>,
+    purpose: &str,
+    token: &str,
+) -> Result<Uuid, anyhow::Error> {
+    verify_with_expiry(secret, purpose, token).map(|(subject, _)| subject)
+}
+
+pub fn verify_with_expiry(
+    secret: &Secret<String>,
+    purpose: &str,
+    token: &str,
+) -> Result<(Uuid, DateTime<Utc>), anyhow::Error> {
+    let mut parts = token.splitn(3, '.');
+    let (subject, expires_at, tag) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(subject), Some(expires_at), Some(tag)) => (subject, expires_at, tag),
+        _ => anyhow::bail!("The token is malformed"),
+    };
+    let subject = Uuid::parse_str(subject).context("The token subject is not a valid id")?;
+    let expires_at: i64 = expires_at.parse().context("The token expiry is not a timestamp")?;
+    let tag = hex::decode(tag).context("The token signature is not valid hex")?;
+
+    mac(secret, purpose, subject, expires_at)
+        .verify_slice(&tag)
+        .context("The token signature does not match")?;
+
+    if Utc::now().timestamp() > expires_at {
+        anyhow::bail!("The token has expired");
+    }
+    Ok((subject, Utc.timestamp(expires_at, 0)))
+}
+
+fn sign(secret: &Secret<String>, purpose: &str, subject: Uuid, expires_at: i64) -> Vec<u8> {
+    mac(secret, purpose, subject, expires_at)
+        .finalize()
+        .into_bytes()
+        .to_vec()
+}
+
+fn mac(secret: &Secret<String>, purpose: &str, subject: Uuid, expires_at: i64) -> Hmac<sha2::Sha256> {
+    let mut mac = Hmac::<sha2::Sha256>::new_from_slice(secret.expose_secret().as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(format!("{}.{}.{}", purpose, subject, expires_at).as_bytes());
+    mac
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate, verify};
+    use chrono::Duration;
+    use claim::{assert_err, assert_ok_eq};
+    use secrecy::Secret;
+    use uuid::Uuid;
+
+    fn secret() -> Secret<String> {
+        Secret::new("a-secret-that-is-only-used-in-tests".to_string())
+    }
+
+    #[test]
+    fn a_freshly_generated_token_is_valid() {
+        let subject = Uuid::new_v4();
+        let token = generate(&secret(), "purpose", subject, Duration::hours(1));
+        assert_ok_eq!(verify(&secret(), "purpose", &token), subject);
+    }
+
+    #[test]
+    fn an_expired_token_is_rejected() {
+        let token = generate(&secret(), "purpose", Uuid::new_v4(), Duration::hours(-1));
+        assert_err!(verify(&secret(), "purpose", &token));
+    }
+
+    #[test]
+    fn a_token_is_rejected_for_a_different_purpose() {
+        let token = generate(&secret(), "purpose", Uuid::new_v4(), Duration::hours(1));
+        assert_err!(verify(&secret(), "another-purpose", &token));
+    }
+
+    #[test]
+    fn a_token_signed_with_a_different_secret_is_rejected() {
+        let token = generate(&secret(), "purpose", Uuid::new_v4(), Duration::hours(1));
+        let other_secret = Secret::new("another-secret".to_string());
+        assert_err!(verify(&other_secret, "purpose", &token));
+    }
+
+    #[test]
+    fn a_tampered_token_is_rejected() {
+        let token = generate(&secret(), "purpose", Uuid::new_v4(), Duration::hours(1));
+        let tampered = format!("{}.{}", Uuid::new_v4(), token.splitn(2, '.').nth(1).unwrap());
+        assert_err!(verify(&secret(), "purpose", &tampered));
+        assert_err!(verify(&secret(), "purpose", "garbage"));
+    }
+}
@@ -1,16 +1,21 @@
-use std::net::TcpListener;
+use std::net::{IpAddr, TcpListener};
 
 use actix_web::{App, HttpServer, web, cookie};
 use actix_web::dev::Server;
 use sqlx::{PgPool};
 
 use crate::routes;
+use crate::authentication::{PasswordHashing, RejectUnauthorizedUsers, MAXIMUM_FORM_BYTES};
 use tracing_actix_web::TracingLogger;
-use crate::email_client::EmailClient;
 use actix_web::web::Data;
 use crate::configuration::{Settings, DatabaseSettings};
+use crate::subscription_protection::SubscriptionProtection;
+use crate::login_throttle::LoginThrottle;
+use crate::password_reset_throttle::PasswordResetThrottle;
+use crate::subscriber_data_throttle::SubscriberDataThrottle;
+use crate::domain::{EmailDomainPolicy, PasswordPolicy};
+use anyhow::Context;
 use sqlx::postgres::PgPoolOptions;
-use crate::domain::subscriber_email::SubscriberEmail;
 use secrecy::{Secret, ExposeSecret};
 use actix_web_flash_messages::FlashMessagesFramework;
 use actix_web_flash_messages::storage::CookieMessageStore;
@@ -22,6 +27,8 @@ pub type DbConnectionKind = PgPool;
 #[derive(Clone)]
 pub struct HmacSecret(pub Secret<String>);
 
+pub struct TrustedProxies(pub Vec<IpAddr>);
+
 pub struct Application {
     port: u16,
     server: Server
@@ -29,19 +36,6 @@ pub struct Application {
 
 impl Application {
     pub async fn build(config: Settings) -> Result<Self, anyhow::Error> {
-        let db_connection_pool: DbConnectionKind = get_database_connection(&config.database);
-
-        let sender_email: SubscriberEmail = config.email_client.sender()
-            .expect("Invalid email found in config");
-
-        let timeout = std::time::Duration::from_millis(config.email_client.timeout_milliseconds);
-        let email_client = EmailClient::new(
-            config.email_client.base_url,
-            sender_email,
-            config.email_client.authorization_token,
-            timeout
-        );
-
         let address = format!(
             "{address}:{port}",
             address = config.application.host,
@@ -49,14 +43,7 @@ impl Application {
         );
         let listener = TcpListener::bind(address)?;
         let port = listener.local_addr().unwrap().port();
-        let server = run(
-            listener,
-            db_connection_pool,
-            email_client,
-            config.application.base_url,
-            config.application.hmac_secret,
-            config.redis_uri
-        ).await?;
+        let server = run(listener, &config).await?;
 
         Ok( Self { port, server })
     }
@@ -80,19 +67,34 @@ pub fn get_database_connection(config: &DatabaseSettings) -> DbConnectionKind {
 
 pub async fn run(
     listener: TcpListener,
-    connection: DbConnectionKind,
-    email_client: EmailClient,
-    base_url: String,
-    hmac_secret: Secret<String>,
-    redis_uri: Secret<String>,
+    config: &Settings,
 ) -> Result<Server, anyhow::Error> {
-    let connection = web::Data::new(connection);
-    let email_client = Data::new(email_client);
-    let base_url = Data::new(ApplicationBaseUrl(base_url));
+    let email_domain_policy = EmailDomainPolicy::load(&config.email_domain_policy)
+        .context("Failed to load email domain policy")?;
+    let password_hashing = PasswordHashing::new(&config.password_hashing)
+        .context("Failed to configure password hashing")?;
+    let password_policy = PasswordPolicy::load(&config.password_policy)
+        .context("Failed to load password policy")?;
+    let hmac_secret = config.application.hmac_secret.clone();
+
+    let connection = web::Data::new(get_database_connection(&config.database));
+    let email_client = Data::new(config.email_client.clone().client());
+    let base_url = Data::new(ApplicationBaseUrl(config.application.base_url.clone()));
+    let trusted_proxies = Data::new(TrustedProxies(config.application.trusted_proxies.clone()));
+    let subscription_protection = Data::new(SubscriptionProtection::new(
+        &config.subscription_protection,
+        hmac_secret.clone(),
+    ));
+    let email_domain_policy = Data::new(email_domain_policy);
+    let login_throttle = Data::new(LoginThrottle::new(&config.login_protection));
+    let password_reset_throttle = Data::new(PasswordResetThrottle::new(&config.password_reset_protection));
+    let subscriber_data_throttle = Data::new(SubscriberDataThrottle::new(&config.subscriber_data_protection));
+    let password_hashing = Data::new(password_hashing);
+    let password_policy = Data::new(password_policy);
     let secret_key = cookie::Key::from(hmac_secret.expose_secret().as_bytes());
     let message_store = CookieMessageStore::builder(secret_key.clone()).build();
     let message_framework = FlashMessagesFramework::builder(message_store).build();
-    let redis_store = RedisSessionStore::new(redis_uri.expose_secret()).await?;
+    let redis_store = RedisSessionStore::new(config.redis_uri.expose_secret()).await?;
     let server = HttpServer::new(move || {
         App::new()
             .wrap(message_framework.clone())
@@ -100,19 +102,86 @@ pub async fn run(
             .wrap(TracingLogger::default())
             .route("/health", web::get().to(routes::health_check::health_check))
             .route("/", web::get().to(routes::home::home))
-            .route("/subscriptions", web::post().to(routes::subscriptions::subscribe))
+            .service(
+                web::resource("/subscriptions")
+                    .app_data(web::FormConfig::default().error_handler(routes::subscriptions::form_error_handler))
+                    .app_data(web::JsonConfig::default().error_handler(routes::subscriptions::json_error_handler))
+                    .route(web::post().to(routes::subscriptions::subscribe))
+            )
+            .route("/subscriptions/form-token", web::get().to(routes::subscriptions::form_token))
             .route("/subscriptions/confirm", web::get().to(routes::subscriptions_confirm::confirm))
+            .route("/subscriptions/data", web::get().to(routes::subscriptions_data::data_request_form))
+            .route("/subscriptions/data", web::post().to(routes::subscriptions_data::request_subscriber_data))
+            .route("/subscriptions/data/export", web::get().to(routes::subscriptions_data::export_subscriber_data))
+            .route("/subscriptions/data/erase", web::get().to(routes::subscriptions_data::erasure_form))
+            .route("/subscriptions/data/erase", web::post().to(routes::subscriptions_data::erase_subscriber_data))
+            .route("/subscriptions/preferences", web::get().to(routes::subscriptions_preferences::preferences_form))
+            .route("/subscriptions/preferences/name", web::post().to(routes::subscriptions_preferences::update_name))
+            .route("/subscriptions/preferences/pause", web::post().to(routes::subscriptions_preferences::pause_delivery))
+            .route("/subscriptions/preferences/unsubscribe", web::post().to(routes::subscriptions_preferences::unsubscribe))
             .route("/login", web::get().to(routes::login::get::login_form))
             .route("/login", web::post().to(routes::login::post::login))
-            .route("/admin/dashboard", web::get().to(routes::admin::dashboard::admin_dashboard))
-            .route("/admin/password", web::get().to(routes::admin::password::change_password_form))
-            .route("/admin/password", web::post().to(routes::admin::password::change_password))
-            .route("/admin/logout", web::post().to(routes::admin::logout::logout))
-            .route("/admin/newsletter", web::get().to(routes::admin::newsletter::newsletter_form))
-            .route("/admin/newsletter", web::post().to(routes::admin::newsletter::publish_newsletter))
+            .route("/login/two-factor", web::get().to(routes::login::two_factor::two_factor_form))
+            .route("/login/two-factor", web::post().to(routes::login::two_factor::verify_two_factor))
+            .route("/password-reset", web::get().to(routes::password_reset::password_reset_request_form))
+            .route("/password-reset", web::post().to(routes::password_reset::request_password_reset))
+            .route("/password-reset/confirm", web::get().to(routes::password_reset::password_reset_form))
+            .route("/password-reset/confirm", web::post().to(routes::password_reset::reset_password))
+            .route("/invitations/accept", web::get().to(routes::invitations::accept_invitation_form))
+            .route("/invitations/accept", web::post().to(routes::invitations::accept_invitation))
+            .service(
+                web::scope("/admin")
+                    .wrap(RejectUnauthorizedUsers)
+                    .route("/dashboard", web::get().to(routes::admin::dashboard::admin_dashboard))
+                    .route("/password", web::get().to(routes::admin::password::change_password_form))
+                    .route("/password", web::post().to(routes::admin::password::change_password))
+                    .route("/logout", web::post().to(routes::admin::logout::logout))
+                    .route("/newsletter", web::get().to(routes::admin::newsletter::newsletter_form))
+                    .route("/newsletter", web::post().to(routes::admin::newsletter::publish_newsletter))
+                    .route("/attributes", web::get().to(routes::admin::attributes::attributes_form))
+                    .route("/attributes", web::post().to(routes::admin::attributes::add_attribute))
+                    .route("/attributes/delete", web::post().to(routes::admin::attributes::delete_attribute))
+                    .route("/subscribers", web::get().to(routes::admin::subscribers::list_subscribers))
+                    .route("/subscribers/confirm", web::post().to(routes::admin::subscribers::confirm_subscriber))
+                    .route("/subscribers/resend", web::post().to(routes::admin::subscribers::resend_confirmation))
+                    .route("/subscribers/unsubscribe", web::post().to(routes::admin::subscribers::unsubscribe_subscriber))
+                    .route("/subscribers/delete", web::post().to(routes::admin::subscribers::delete_subscriber))
+                    .service(
+                        web::resource("/subscribers/import")
+                            .app_data(web::FormConfig::default().limit(MAXIMUM_FORM_BYTES))
+                            .route(web::get().to(routes::admin::import::import_form))
+                            .route(web::post().to(routes::admin::import::import_subscribers))
+                    )
+                    .route("/subscribers/export", web::get().to(routes::admin::export::export_subscribers))
+                    .route("/welcome-sequence", web::get().to(routes::admin::welcome_sequence::welcome_sequence_form))
+                    .route("/welcome-sequence", web::post().to(routes::admin::welcome_sequence::add_welcome_step))
+                    .route("/welcome-sequence/delete", web::post().to(routes::admin::welcome_sequence::delete_welcome_step))
+                    .route("/two-factor", web::get().to(routes::admin::two_factor::two_factor_page))
+                    .route("/two-factor/enroll", web::post().to(routes::admin::two_factor::enroll_two_factor))
+                    .route("/two-factor/confirm", web::post().to(routes::admin::two_factor::confirm_two_factor))
+                    .route("/two-factor/disable", web::post().to(routes::admin::two_factor::disable_two_factor))
+                    .route("/api-tokens", web::get().to(routes::admin::api_tokens::api_tokens_page))
+                    .route("/api-tokens", web::post().to(routes::admin::api_tokens::create_token))
+                    .route("/api-tokens/revoke", web::post().to(routes::admin::api_tokens::revoke_token))
+                    .route("/sessions", web::get().to(routes::admin::sessions::sessions_page))
+                    .route("/sessions/revoke", web::post().to(routes::admin::sessions::revoke_session))
+                    .route("/sessions/revoke-others", web::post().to(routes::admin::sessions::revoke_other_sessions))
+                    .route("/audit-log", web::get().to(routes::admin::audit_log::audit_log))
+                    .route("/users", web::get().to(routes::admin::users::users_page))
+                    .route("/users/invite", web::post().to(routes::admin::users::invite_user))
+                    .route("/users/deactivate", web::post().to(routes::admin::users::deactivate_user))
+            )
             .app_data(connection.clone())
             .app_data(email_client.clone())
             .app_data(base_url.clone())
+            .app_data(trusted_proxies.clone())
+            .app_data(subscription_protection.clone())
+            .app_data(email_domain_policy.clone())
+            .app_data(login_throttle.clone())
+            .app_data(password_reset_throttle.clone())
+            .app_data(subscriber_data_throttle.clone())
+            .app_data(password_hashing.clone())
+            .app_data(password_policy.clone())
             .app_data(Data::new(HmacSecret(hmac_secret.clone())))
     })
         .listen(listener)?
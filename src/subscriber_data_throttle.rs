@@ -0,0 +1,24 @@
+use crate::configuration::SubscriberDataProtectionSettings;
+use crate::rate_limit::{email_key, RateLimiter};
+
+// Limits how many data links can be asked for, so that the form cannot be used to
+// flood a subscriber's inbox or to probe the list faster than by hand.
+pub struct SubscriberDataThrottle {
+    by_email: RateLimiter,
+    by_ip: RateLimiter,
+}
+
+impl SubscriberDataThrottle {
+    pub fn new(settings: &SubscriberDataProtectionSettings) -> Self {
+        Self {
+            by_email: RateLimiter::new(settings.email_rate_limit.max_requests, settings.email_rate_limit.window()),
+            by_ip: RateLimiter::new(settings.ip_rate_limit.max_requests, settings.ip_rate_limit.window()),
+        }
+    }
+
+    // Records a request and returns whether an email may be sent for it. Requests
+    // refused by the IP limit are not counted against the email.
+    pub fn check(&self, email: &str, client_ip: &str) -> bool {
+        self.by_ip.check(client_ip) && self.by_email.check(&email_key(email))
+    }
+}
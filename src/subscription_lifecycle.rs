@@ -0,0 +1,202 @@
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{Postgres, Transaction};
+use std::fmt::Formatter;
+use uuid::Uuid;
+
+use crate::domain::SubscriptionStatus;
+use crate::routes::error_chain_fmt;
+
+#[derive(thiserror::Error)]
+pub enum StatusChangeError {
+    #[error("The subscriber does not exist")]
+    SubscriberNotFound,
+    #[error("A subscription that is {from} cannot become {to}")]
+    NotAllowed {
+        from: SubscriptionStatus,
+        to: SubscriptionStatus,
+    },
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for StatusChangeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+#[tracing::instrument(
+name = "Recording the initial status of a subscription",
+skip(transaction)
+)]
+pub async fn record_initial_status(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    status: SubscriptionStatus,
+) -> Result<(), sqlx::Error> {
+    let changed_at = Utc::now();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscription_status_changes (subscriber_id, from_status, to_status, changed_at)
+        VALUES ($1, NULL, $2, $3)
+        "#,
+        subscriber_id,
+        status.as_str(),
+        changed_at
+    )
+        .execute(&mut *transaction)
+        .await?;
+    // Subscriptions imported as confirmed start the welcome sequence straight away.
+    if status == SubscriptionStatus::Confirmed {
+        enroll_in_welcome_sequence(transaction, subscriber_id, changed_at).await?;
+    }
+    Ok(())
+}
+
+async fn enroll_in_welcome_sequence(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    enrolled_at: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO welcome_sequence_enrollments (subscriber_id, enrolled_at)
+        VALUES ($1, $2)
+        ON CONFLICT DO NOTHING
+        "#,
+        subscriber_id,
+        enrolled_at
+    )
+        .execute(transaction)
+        .await?;
+    Ok(())
+}
+
+// Every status change after a subscription is created goes through here, so that
+// the allowed transitions are enforced and each change is recorded. Returns the
+// status the subscription had before.
+#[tracing::instrument(
+name = "Changing subscription status",
+skip(transaction)
+)]
+pub async fn change_status(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    to: SubscriptionStatus,
+) -> Result<SubscriptionStatus, StatusChangeError> {
+    let row = sqlx::query!(
+        "SELECT status FROM subscriptions WHERE id = $1 FOR UPDATE",
+        subscriber_id
+    )
+        .fetch_optional(&mut *transaction)
+        .await
+        .context("Failed to retrieve subscription status")?
+        .ok_or(StatusChangeError::SubscriberNotFound)?;
+    let from = SubscriptionStatus::parse(&row.status)
+        .map_err(anyhow::Error::msg)?;
+
+    if !from.can_transition_to(to) {
+        return Err(StatusChangeError::NotAllowed { from, to });
+    }
+    if from == to {
+        return Ok(from);
+    }
+
+    let changed_at = Utc::now();
+    sqlx::query!(
+        "UPDATE subscriptions SET status = $1 WHERE id = $2",
+        to.as_str(),
+        subscriber_id
+    )
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to update subscription status")?;
+    sqlx::query!(
+        r#"
+        INSERT INTO subscription_status_changes (subscriber_id, from_status, to_status, changed_at)
+        VALUES ($1, $2, $3, $4)
+        "#,
+        subscriber_id,
+        from.as_str(),
+        to.as_str(),
+        changed_at
+    )
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to record subscription status change")?;
+
+    // Confirming starts the welcome sequence and leaving stops it straight away.
+    if to == SubscriptionStatus::Confirmed {
+        enroll_in_welcome_sequence(transaction, subscriber_id, changed_at)
+            .await
+            .context("Failed to enroll subscriber in the welcome sequence")?;
+    } else if from == SubscriptionStatus::Confirmed {
+        sqlx::query!(
+            "DELETE FROM welcome_sequence_enrollments WHERE subscriber_id = $1",
+            subscriber_id
+        )
+            .execute(&mut *transaction)
+            .await
+            .context("Failed to remove subscriber from the welcome sequence")?;
+    }
+    Ok(from)
+}
+
+#[derive(Debug, PartialEq)]
+pub struct DeletedSubscribers {
+    pub subscriptions: u64,
+    pub tokens: u64,
+}
+
+// Deletes subscriptions together with every row that references them. Both erasure
+// and the pending subscription cleanup go through here, so that a table added for
+// subscribers only needs to be listed once.
+#[tracing::instrument(
+name = "Deleting subscribers and every row referencing them",
+skip(transaction)
+)]
+pub async fn delete_subscribers(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_ids: &[Uuid],
+) -> Result<DeletedSubscribers, sqlx::Error> {
+    let tokens = sqlx::query!(
+        "DELETE FROM subscription_tokens WHERE subscriber_id = ANY($1)",
+        subscriber_ids
+    )
+        .execute(&mut *transaction)
+        .await?
+        .rows_affected();
+    sqlx::query!(
+        "DELETE FROM newsletter_deliveries WHERE subscriber_id = ANY($1)",
+        subscriber_ids
+    )
+        .execute(&mut *transaction)
+        .await?;
+    sqlx::query!(
+        "DELETE FROM subscription_status_changes WHERE subscriber_id = ANY($1)",
+        subscriber_ids
+    )
+        .execute(&mut *transaction)
+        .await?;
+    sqlx::query!(
+        "DELETE FROM welcome_sequence_enrollments WHERE subscriber_id = ANY($1)",
+        subscriber_ids
+    )
+        .execute(&mut *transaction)
+        .await?;
+    sqlx::query!(
+        "DELETE FROM welcome_sequence_deliveries WHERE subscriber_id = ANY($1)",
+        subscriber_ids
+    )
+        .execute(&mut *transaction)
+        .await?;
+    let subscriptions = sqlx::query!(
+        "DELETE FROM subscriptions WHERE id = ANY($1)",
+        subscriber_ids
+    )
+        .execute(&mut *transaction)
+        .await?
+        .rows_affected();
+    Ok(DeletedSubscribers { subscriptions, tokens })
+}
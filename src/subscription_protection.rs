@@ -0,0 +1,206 @@
+use chrono::{DateTime, Duration, Utc};
+use secrecy::Secret;
+use std::collections::{BTreeSet, HashSet};
+use std::sync::Mutex;
+use uuid::Uuid;
+
+use crate::configuration::SubscriptionProtectionSettings;
+use crate::domain::subscriber_email::SubscriberEmail;
+use crate::rate_limit::RateLimiter;
+use crate::signed_token;
+
+const FORM_TOKEN_PURPOSE: &str = "subscribe-form";
+
+// Spent form tokens are remembered until they expire, so only this many can be
+// rejected as reused at a time. Once there are more, the ones closest to expiring
+// are forgotten first.
+const MAXIMUM_SPENT_FORM_TOKENS: usize = 100_000;
+
+fn form_token_time_to_live() -> Duration {
+    Duration::hours(1)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RejectionReason {
+    HoneypotFilled,
+    MissingFormToken,
+    InvalidFormToken,
+    ReusedFormToken,
+    FilledTooQuickly,
+    IpRateLimited,
+    EmailDomainRateLimited,
+}
+
+impl RejectionReason {
+    // Stable identifier used in logs, so that rejections can be counted by reason.
+    pub fn code(&self) -> &'static str {
+        match self {
+            RejectionReason::HoneypotFilled => "honeypot_filled",
+            RejectionReason::MissingFormToken => "missing_form_token",
+            RejectionReason::InvalidFormToken => "invalid_form_token",
+            RejectionReason::ReusedFormToken => "reused_form_token",
+            RejectionReason::FilledTooQuickly => "filled_too_quickly",
+            RejectionReason::IpRateLimited => "ip_rate_limited",
+            RejectionReason::EmailDomainRateLimited => "email_domain_rate_limited",
+        }
+    }
+}
+
+// A form token that passed `check_form_token`, to be spent once the rest of the
+// submission has been accepted.
+pub struct FormToken {
+    id: Uuid,
+    expires_at: DateTime<Utc>,
+}
+
+pub struct SubscriptionProtection {
+    hmac_secret: Secret<String>,
+    minimum_form_fill_time: Duration,
+    ip_limiter: RateLimiter,
+    email_domain_limiter: RateLimiter,
+    spent_form_tokens: Mutex<SpentFormTokens>,
+}
+
+impl SubscriptionProtection {
+    pub fn new(settings: &SubscriptionProtectionSettings, hmac_secret: Secret<String>) -> Self {
+        Self {
+            hmac_secret,
+            minimum_form_fill_time: Duration::seconds(settings.minimum_form_fill_seconds as i64),
+            ip_limiter: RateLimiter::new(
+                settings.ip_rate_limit.max_requests,
+                settings.ip_rate_limit.window(),
+            ),
+            email_domain_limiter: RateLimiter::new(
+                settings.email_domain_rate_limit.max_requests,
+                settings.email_domain_rate_limit.window(),
+            ),
+            spent_form_tokens: Mutex::new(SpentFormTokens::default()),
+        }
+    }
+
+    // Embedded in the subscribe form when it is rendered, so that we can tell how
+    // long it took to fill it in.
+    pub fn form_token(&self) -> String {
+        signed_token::generate(
+            &self.hmac_secret,
+            FORM_TOKEN_PURPOSE,
+            Uuid::new_v4(),
+            form_token_time_to_live(),
+        )
+    }
+
+    pub fn check_honeypot(&self, honeypot: &str) -> Result<(), RejectionReason> {
+        if honeypot.is_empty() {
+            Ok(())
+        } else {
+            Err(RejectionReason::HoneypotFilled)
+        }
+    }
+
+    // Our own HTML form embeds the token, API clients get theirs from
+    // `/subscriptions/form-token` when they show their form. There is nothing to
+    // spend when there is no minimum fill time.
+    pub fn check_form_token(&self, form_token: Option<&str>) -> Result<Option<FormToken>, RejectionReason> {
+        if self.minimum_form_fill_time <= Duration::zero() {
+            return Ok(None);
+        }
+        let form_token = form_token
+            .filter(|t| !t.is_empty())
+            .ok_or(RejectionReason::MissingFormToken)?;
+        let (id, expires_at) = signed_token::verify_with_expiry(&self.hmac_secret, FORM_TOKEN_PURPOSE, form_token)
+            .map_err(|_| RejectionReason::InvalidFormToken)?;
+        let rendered_at = expires_at - form_token_time_to_live();
+        if Utc::now() - rendered_at < self.minimum_form_fill_time {
+            return Err(RejectionReason::FilledTooQuickly);
+        }
+        Ok(Some(FormToken { id, expires_at }))
+    }
+
+    // Each rendered form can only be submitted once. Tokens are only spent once the
+    // submission passed every other check, so that someone fixing a typo can send
+    // the same form again.
+    pub fn spend_form_token(&self, form_token: Option<FormToken>) -> Result<(), RejectionReason> {
+        let form_token = match form_token {
+            Some(form_token) => form_token,
+            None => return Ok(()),
+        };
+        if self.spent_form_tokens.lock().unwrap().spend(&form_token, Utc::now()) {
+            Ok(())
+        } else {
+            Err(RejectionReason::ReusedFormToken)
+        }
+    }
+
+    pub fn check_ip(&self, client_ip: &str) -> Result<(), RejectionReason> {
+        if self.ip_limiter.check(client_ip) {
+            Ok(())
+        } else {
+            Err(RejectionReason::IpRateLimited)
+        }
+    }
+
+    pub fn check_email_domain(&self, email: &SubscriberEmail) -> Result<(), RejectionReason> {
+        if self.email_domain_limiter.check(email.domain()) {
+            Ok(())
+        } else {
+            Err(RejectionReason::EmailDomainRateLimited)
+        }
+    }
+}
+
+#[derive(Default)]
+struct SpentFormTokens {
+    ids: HashSet<Uuid>,
+    // The same ids in the order they expire in.
+    by_expiry: BTreeSet<(DateTime<Utc>, Uuid)>,
+}
+
+impl SpentFormTokens {
+    // Returns whether the token had not been spent yet.
+    fn spend(&mut self, form_token: &FormToken, now: DateTime<Utc>) -> bool {
+        while let Some(&(expires_at, id)) = self.by_expiry.iter().next() {
+            if expires_at > now && self.ids.len() < MAXIMUM_SPENT_FORM_TOKENS {
+                break;
+            }
+            self.by_expiry.remove(&(expires_at, id));
+            self.ids.remove(&id);
+        }
+        if !self.ids.insert(form_token.id) {
+            return false;
+        }
+        self.by_expiry.insert((form_token.expires_at, form_token.id));
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FormToken, SpentFormTokens};
+    use chrono::{Duration, Utc};
+    use uuid::Uuid;
+
+    fn form_token(expires_in: Duration) -> FormToken {
+        FormToken { id: Uuid::new_v4(), expires_at: Utc::now() + expires_in }
+    }
+
+    #[test]
+    fn a_form_token_can_only_be_spent_once() {
+        let mut spent = SpentFormTokens::default();
+        let token = form_token(Duration::hours(1));
+        assert!(spent.spend(&token, Utc::now()));
+        assert!(!spent.spend(&token, Utc::now()));
+        assert!(spent.spend(&form_token(Duration::hours(1)), Utc::now()));
+    }
+
+    #[test]
+    fn expired_form_tokens_are_forgotten() {
+        let mut spent = SpentFormTokens::default();
+        spent.spend(&form_token(Duration::minutes(1)), Utc::now());
+        spent.spend(&form_token(Duration::hours(1)), Utc::now());
+
+        spent.spend(&form_token(Duration::hours(1)), Utc::now() + Duration::minutes(2));
+
+        assert_eq!(spent.ids.len(), 2);
+        assert_eq!(spent.by_expiry.len(), 2);
+    }
+}
@@ -1,5 +1,11 @@
-use actix_web::HttpResponse;
-use actix_web::http::header::LOCATION;
+use actix_web::{HttpRequest, HttpResponse, web};
+use actix_web::http::header::{ContentType, LOCATION};
+use std::net::IpAddr;
+
+use crate::i18n::Locale;
+use crate::startup::TrustedProxies;
+
+const X_FORWARDED_FOR: &str = "X-Forwarded-For";
 
 pub fn e500<T>(e: T) -> actix_web::error::InternalError<T> {
     actix_web::error::InternalError::from_response(
@@ -19,4 +25,113 @@ pub fn see_other(location: &str) -> HttpResponse {
     HttpResponse::SeeOther()
         .insert_header((LOCATION, location))
         .finish()
-}
\ No newline at end of file
+}
+
+// Compares secrets without leaking through timing how much of them matched.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |difference, (x, y)| difference | (x ^ y)) == 0
+}
+
+// The address of the client, used as its identity for rate limiting and logging.
+// Forwarding headers are written by whoever sends the request, so they are only
+// believed when the connection comes from one of the `TrustedProxies`: the client
+// is then the last address in `X-Forwarded-For` that is not one of the proxies.
+pub fn client_ip(request: &HttpRequest) -> String {
+    let peer = match request.peer_addr() {
+        Some(peer) => peer.ip(),
+        None => return "unknown".to_string(),
+    };
+    let trusted = match request.app_data::<web::Data<TrustedProxies>>() {
+        Some(trusted) if trusted.0.contains(&peer) => &trusted.0,
+        _ => return peer.to_string(),
+    };
+    let forwarded: Vec<IpAddr> = request
+        .headers()
+        .get_all(X_FORWARDED_FOR)
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|address| address.trim().parse().ok())
+        .collect();
+    forwarded
+        .into_iter()
+        .rev()
+        .find(|address| !trusted.contains(address))
+        .unwrap_or(peer)
+        .to_string()
+}
+
+// A page with a single localised message, e.g. the result of a form submission.
+pub fn message_page(locale: Locale, title_key: &str, body_key: &str) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="{lang}">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>{title}</title>
+</head>
+<body>
+    <h1>{title}</h1>
+    <p>{body}</p>
+</body>
+</html>"#,
+            lang = locale.as_str(),
+            title = locale.message(title_key),
+            body = locale.message(body_key),
+        ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::client_ip;
+    use crate::startup::TrustedProxies;
+    use actix_web::test::TestRequest;
+    use actix_web::web;
+
+    fn request_from(peer: &str) -> TestRequest {
+        TestRequest::default().peer_addr(format!("{}:4242", peer).parse().unwrap())
+    }
+
+    fn trusting(request: TestRequest, proxies: &[&str]) -> TestRequest {
+        let proxies = proxies.iter().map(|proxy| proxy.parse().unwrap()).collect();
+        request.app_data(web::Data::new(TrustedProxies(proxies)))
+    }
+
+    #[test]
+    fn the_peer_address_is_used_without_a_trusted_proxy() {
+        let request = request_from("203.0.113.7")
+            .insert_header(("X-Forwarded-For", "198.51.100.1"))
+            .insert_header(("Forwarded", "for=198.51.100.2"))
+            .to_http_request();
+        assert_eq!(client_ip(&request), "203.0.113.7");
+
+        let request = trusting(request_from("203.0.113.7"), &["10.0.0.1"])
+            .insert_header(("X-Forwarded-For", "198.51.100.1"))
+            .to_http_request();
+        assert_eq!(client_ip(&request), "203.0.113.7");
+    }
+
+    #[test]
+    fn a_trusted_proxy_is_believed_about_the_address_it_added() {
+        // The client made up the first address, the proxy appended the second.
+        let request = trusting(request_from("10.0.0.1"), &["10.0.0.1"])
+            .insert_header(("X-Forwarded-For", "198.51.100.1, 203.0.113.7"))
+            .to_http_request();
+        assert_eq!(client_ip(&request), "203.0.113.7");
+    }
+
+    #[test]
+    fn chained_trusted_proxies_are_skipped() {
+        let request = trusting(request_from("10.0.0.1"), &["10.0.0.1", "10.0.0.2"])
+            .insert_header(("X-Forwarded-For", "203.0.113.7, 10.0.0.2"))
+            .to_http_request();
+        assert_eq!(client_ip(&request), "203.0.113.7");
+    }
+
+    #[test]
+    fn a_trusted_proxy_without_the_header_is_the_client() {
+        let request = trusting(request_from("10.0.0.1"), &["10.0.0.1"]).to_http_request();
+        assert_eq!(client_ip(&request), "10.0.0.1");
+    }
+}
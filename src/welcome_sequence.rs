@@ -0,0 +1,173 @@
+use anyhow::Context;
+use chrono::Utc;
+use secrecy::Secret;
+use uuid::Uuid;
+
+use crate::configuration::Settings;
+use crate::domain::SubscriptionStatus;
+use crate::domain::subscriber_email::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::i18n::Locale;
+use crate::routes::subscriptions_preferences::preferences_link;
+use crate::startup::{get_database_connection, DbConnectionKind};
+
+struct DueWelcomeEmail {
+    subscriber_id: Uuid,
+    email: String,
+    locale: String,
+    step_id: Uuid,
+    subject: String,
+    html_content: String,
+    text_content: String,
+}
+
+// Runs the welcome sequence forever. A failed run is logged and retried on the
+// next tick, so a database or email outage does not take the application down.
+pub async fn run_welcome_sequence_until_stopped(config: Settings) -> Result<(), anyhow::Error> {
+    let database = get_database_connection(&config.database);
+    let email_client = config.email_client.client();
+    let mut interval = tokio::time::interval(config.welcome_sequence.interval());
+    loop {
+        interval.tick().await;
+        let result = send_due_welcome_emails(
+            &database,
+            &email_client,
+            &config.application.base_url,
+            &config.application.hmac_secret,
+        ).await;
+        if let Err(e) = result {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to send welcome emails"
+            );
+        }
+    }
+}
+
+// Sends every welcome step whose delay has passed since the subscriber confirmed.
+// Steps added after that moment had already passed are skipped, so a new step is
+// not sent to everyone who confirmed long ago. Returns the number of emails sent.
+#[tracing::instrument(
+name = "Sending due welcome emails",
+skip(database, email_client, base_url, hmac_secret)
+)]
+pub async fn send_due_welcome_emails(
+    database: &DbConnectionKind,
+    email_client: &EmailClient,
+    base_url: &str,
+    hmac_secret: &Secret<String>,
+) -> Result<u64, anyhow::Error> {
+    let now = Utc::now();
+    let due = sqlx::query_as!(
+        DueWelcomeEmail,
+        r#"
+        SELECT
+            e.subscriber_id, s.email, s.locale,
+            st.id AS step_id, st.subject, st.html_content, st.text_content
+        FROM welcome_sequence_enrollments e
+        JOIN subscriptions s ON s.id = e.subscriber_id
+        JOIN welcome_sequence_steps st
+            ON e.enrolled_at + make_interval(days => st.delay_days) <= $1
+            AND e.enrolled_at + make_interval(days => st.delay_days) >= st.created_at
+        WHERE s.status = $2
+        AND (s.paused_until IS NULL OR s.paused_until <= $1)
+        AND NOT EXISTS (
+            SELECT 1 FROM welcome_sequence_deliveries d
+            WHERE d.subscriber_id = e.subscriber_id AND d.step_id = st.id
+        )
+        ORDER BY e.enrolled_at, st.delay_days, st.created_at
+        "#,
+        now,
+        SubscriptionStatus::Confirmed.as_str()
+    )
+        .fetch_all(database)
+        .await
+        .context("Failed to retrieve due welcome emails")?;
+
+    let mut sent = 0;
+    for email in due {
+        let preferences_link = preferences_link(base_url, hmac_secret, email.subscriber_id);
+        match send_welcome_email(database, email_client, &email, &preferences_link).await {
+            Ok(true) => sent += 1,
+            Ok(false) => {}
+            // One bad address must not hold up everybody else's welcome.
+            Err(e) => tracing::warn!(
+                error.cause_chain = ?e,
+                subscriber_id = %email.subscriber_id,
+                step_id = %email.step_id,
+                "Failed to send a welcome email, it will be retried"
+            ),
+        }
+    }
+    Ok(sent)
+}
+
+// The delivery is recorded in the same transaction that sends the email, so that a
+// failed send is retried and concurrent runs do not send the same step twice.
+// Returns `false` if the step was sent in the meantime or the subscriber left.
+async fn send_welcome_email(
+    database: &DbConnectionKind,
+    email_client: &EmailClient,
+    email: &DueWelcomeEmail,
+    preferences_link: &str,
+) -> Result<bool, anyhow::Error> {
+    let mut transaction = database
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let still_enrolled = sqlx::query!(
+        "SELECT subscriber_id FROM welcome_sequence_enrollments WHERE subscriber_id = $1 FOR SHARE",
+        email.subscriber_id
+    )
+        .fetch_optional(&mut transaction)
+        .await
+        .context("Failed to check the welcome sequence enrollment")?
+        .is_some();
+    if !still_enrolled {
+        return Ok(false);
+    }
+    let recorded = sqlx::query!(
+        r#"
+        INSERT INTO welcome_sequence_deliveries (subscriber_id, step_id, sent_at)
+        VALUES ($1, $2, $3)
+        ON CONFLICT DO NOTHING
+        "#,
+        email.subscriber_id,
+        email.step_id,
+        Utc::now()
+    )
+        .execute(&mut transaction)
+        .await
+        .context("Failed to record the welcome email delivery")?
+        .rows_affected();
+    if recorded == 0 {
+        return Ok(false);
+    }
+
+    let recipient = SubscriberEmail::parse(email.email.clone())
+        .map_err(anyhow::Error::msg)?;
+    // Subscribers who picked a locale we no longer support get the default one.
+    let locale = Locale::parse(&email.locale).unwrap_or_default();
+    let html_body = format!(
+        "{content}<p><a href=\"{preferences_link}\">{manage_preferences}</a></p>",
+        content = email.html_content,
+        preferences_link = preferences_link,
+        manage_preferences = locale.message("manage_preferences"),
+    );
+    let text_body = format!(
+        "{content}\n\n{manage_preferences}: {preferences_link}",
+        content = email.text_content,
+        manage_preferences = locale.message("manage_preferences"),
+        preferences_link = preferences_link
+    );
+    email_client
+        .send_email(&recipient, &email.subject, &html_body, &text_body)
+        .await
+        .context("Failed to send the welcome email")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the welcome email delivery")?;
+    Ok(true)
+}
@@ -0,0 +1,98 @@
+use crate::helpers::spawn_app;
+
+#[tokio::test]
+async fn anonymous_users_are_sent_to_the_login_page_from_every_admin_page() {
+    let app = spawn_app().await;
+
+    for page in [
+        "/admin/dashboard",
+        "/admin/newsletter",
+        "/admin/password",
+        "/admin/subscribers",
+        "/admin/attributes",
+        "/admin/welcome-sequence",
+        "/admin/users",
+    ] {
+        let response = app.api_client
+            .get(format!("{}{}", &app.address, page))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 303, "{} is not protected", page);
+        assert_eq!(response.headers().get("Location").unwrap(), "/login");
+    }
+}
+
+#[tokio::test]
+async fn logging_out_ends_the_session() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+
+    app.post_logout().await;
+
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(response.headers().get("Location").unwrap(), "/login");
+}
+
+#[tokio::test]
+async fn a_deactivated_user_is_logged_out_of_their_current_session() {
+    let app = spawn_app().await;
+    let editor = app.add_user("editor").await;
+    app.login_as(&editor).await;
+    assert_eq!(app.get_admin_dashboard().await.status().as_u16(), 200);
+
+    sqlx::query!("UPDATE users SET active = FALSE WHERE user_id = $1", editor.user_id)
+        .execute(&app.connection)
+        .await
+        .unwrap();
+
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(response.headers().get("Location").unwrap(), "/login");
+}
+
+#[tokio::test]
+async fn viewers_can_look_around_but_not_change_anything() {
+    let app = spawn_app().await;
+    let viewer = app.add_user("viewer").await;
+    app.login_as(&viewer).await;
+
+    assert_eq!(app.get_subscribers("").await.status().as_u16(), 200);
+    assert_eq!(app.get_attributes().await.status().as_u16(), 200);
+    let response = app.post_attributes(&serde_json::json!({
+        "key": "company",
+        "value_type": "string",
+    })).await;
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn viewers_can_change_their_own_password() {
+    let app = spawn_app().await;
+    let viewer = app.add_user("viewer").await;
+    app.login_as(&viewer).await;
+
+    let response = app.post_change_password(&serde_json::json!({
+        "current_password": &viewer.password,
+        "new_password": "a-brand-new-password",
+        "new_password_check": "a-brand-new-password",
+    })).await;
+
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(response.headers().get("Location").unwrap(), "/admin/password");
+}
+
+#[tokio::test]
+async fn only_owners_see_the_link_to_manage_users() {
+    let app = spawn_app().await;
+    let editor = app.add_user("editor").await;
+
+    app.login_with_test_user().await;
+    let html_page = app.get_admin_dashboard().await.text().await.unwrap();
+    assert!(html_page.contains(r#"href="/admin/users""#));
+
+    app.login_as(&editor).await;
+    let html_page = app.get_admin_dashboard().await.text().await.unwrap();
+    assert!(!html_page.contains(r#"href="/admin/users""#));
+}
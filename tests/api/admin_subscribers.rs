@@ -0,0 +1,156 @@
+use crate::helpers::{spawn_app, TestApp};
+use uuid::Uuid;
+use wiremock::{Mock, ResponseTemplate};
+use wiremock::matchers::{path, method};
+
+async fn create_pending_subscriber(app: &TestApp) -> Uuid {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriptions("name=Dione&email=dione%40email.com".into()).await;
+    sqlx::query!("SELECT id FROM subscriptions WHERE email = 'dione@email.com'")
+        .fetch_one(&app.connection)
+        .await
+        .unwrap()
+        .id
+}
+
+async fn saved_status(app: &TestApp) -> String {
+    sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.connection)
+        .await
+        .unwrap()
+        .status
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_subscribers() {
+    let app = spawn_app().await;
+
+    let response = app.get_subscribers("").await;
+
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(response.headers().get("Location").unwrap(), "/login");
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_act_on_subscribers() {
+    let app = spawn_app().await;
+    let subscriber_id = create_pending_subscriber(&app).await;
+
+    for action in ["confirm", "resend", "unsubscribe", "delete"] {
+        let response = app.post_subscriber_action(action, &serde_json::json!({
+            "subscriber_id": subscriber_id
+        })).await;
+        assert_eq!(response.status().as_u16(), 303);
+        assert_eq!(response.headers().get("Location").unwrap(), "/login");
+    }
+    assert_eq!(saved_status(&app).await, "pending_confirmation");
+}
+
+#[tokio::test]
+async fn subscribers_can_be_searched_and_filtered_by_status() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+    create_pending_subscriber(&app).await;
+    app.post_subscriptions("name=Titan&email=titan%40email.com".into()).await;
+
+    let html_page = app.get_subscribers("search=DION").await.text().await.unwrap();
+    assert!(html_page.contains("dione@email.com"));
+    assert!(!html_page.contains("titan@email.com"));
+
+    let html_page = app.get_subscribers("status=confirmed").await.text().await.unwrap();
+    assert!(!html_page.contains("dione@email.com"));
+    assert!(html_page.contains("(0 subscribers)"));
+}
+
+#[tokio::test]
+async fn admins_can_confirm_a_pending_subscriber() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+    let subscriber_id = create_pending_subscriber(&app).await;
+
+    let response = app.post_subscriber_action("confirm", &serde_json::json!({
+        "subscriber_id": subscriber_id
+    })).await;
+
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(saved_status(&app).await, "confirmed");
+}
+
+#[tokio::test]
+async fn admins_can_resend_a_working_confirmation_email() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+    let subscriber_id = create_pending_subscriber(&app).await;
+
+    app.post_subscriber_action("resend", &serde_json::json!({
+        "subscriber_id": subscriber_id
+    })).await;
+
+    let email_requests = app.email_server.received_requests().await.unwrap();
+    assert_eq!(email_requests.len(), 2);
+    let confirmation_links = app.get_confirmation_links(&email_requests[1]);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    assert_eq!(saved_status(&app).await, "confirmed");
+}
+
+#[tokio::test]
+async fn admins_can_unsubscribe_a_subscriber() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+    let subscriber_id = create_pending_subscriber(&app).await;
+
+    app.post_subscriber_action("unsubscribe", &serde_json::json!({
+        "subscriber_id": subscriber_id
+    })).await;
+
+    assert_eq!(saved_status(&app).await, "unsubscribed");
+}
+
+#[tokio::test]
+async fn admins_can_delete_a_subscriber() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+    let subscriber_id = create_pending_subscriber(&app).await;
+
+    app.post_subscriber_action("delete", &serde_json::json!({
+        "subscriber_id": subscriber_id
+    })).await;
+
+    let html_page = app.get_subscribers("").await.text().await.unwrap();
+    assert!(html_page.contains("The subscriber has been deleted."));
+    let saved = sqlx::query!("SELECT COUNT(*) AS count FROM subscriptions")
+        .fetch_one(&app.connection)
+        .await
+        .unwrap();
+    assert_eq!(saved.count, Some(0));
+}
+
+#[tokio::test]
+async fn stored_details_are_escaped_in_flash_messages() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+    let subscriber_id = create_pending_subscriber(&app).await;
+    sqlx::query!(
+        "UPDATE subscriptions SET email = '<script>alert(1)</script>' WHERE id = $1",
+        subscriber_id
+    )
+        .execute(&app.connection)
+        .await
+        .unwrap();
+
+    app.post_subscriber_action("resend", &serde_json::json!({
+        "subscriber_id": subscriber_id
+    })).await;
+
+    let html_page = app.get_subscribers("").await.text().await.unwrap();
+    assert!(html_page.contains("&lt;script&gt;alert(1)&lt;/script&gt; is not a valid subscriber email"));
+    assert!(!html_page.contains("<script>"));
+}
@@ -0,0 +1,207 @@
+use crate::helpers::{spawn_app, TestApp};
+use wiremock::{Mock, ResponseTemplate};
+use wiremock::matchers::{any, path, method};
+
+async fn invite(app: &TestApp, email: &str, role: &str) -> reqwest::Url {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let response = app.post_user_action("invite", &serde_json::json!({
+        "email": email,
+        "role": role,
+    })).await;
+    assert_eq!(response.status().as_u16(), 303);
+
+    let email_request = app.email_server.received_requests().await.unwrap().pop().unwrap();
+    app.get_email_link(&email_request, "/invitations/accept")
+}
+
+fn accept_form(invitation_link: &reqwest::Url, username: &str, password: &str) -> serde_json::Value {
+    let token = invitation_link
+        .query_pairs()
+        .find(|(key, _)| key == "token")
+        .unwrap()
+        .1
+        .into_owned();
+    serde_json::json!({
+        "token": token,
+        "username": username,
+        "password": password,
+        "password_check": password,
+    })
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_users() {
+    let app = spawn_app().await;
+
+    let response = app.get_users().await;
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(response.headers().get("Location").unwrap(), "/login");
+}
+
+#[tokio::test]
+async fn only_owners_can_manage_users() {
+    let app = spawn_app().await;
+    let editor = app.add_user("editor").await;
+    app.login_as(&editor).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    assert_eq!(app.get_users().await.status().as_u16(), 403);
+    let response = app.post_user_action("invite", &serde_json::json!({
+        "email": "rhea@email.com",
+        "role": "owner",
+    })).await;
+    assert_eq!(response.status().as_u16(), 403);
+    let response = app.post_user_action("deactivate", &serde_json::json!({
+        "user_id": app.test_user.user_id,
+    })).await;
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn an_invited_user_can_set_a_password_and_log_in() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+    let invitation_link = invite(&app, "rhea@email.com", "editor").await;
+    app.post_logout().await;
+
+    let html_page = reqwest::get(invitation_link.clone()).await.unwrap().text().await.unwrap();
+    assert!(html_page.contains("rhea@email.com"));
+    let response = app.post_accept_invitation(&accept_form(&invitation_link, "rhea", "a-long-enough-password")).await;
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(response.headers().get("Location").unwrap(), "/login");
+
+    let response = app.post_login(&serde_json::json!({
+        "username": "rhea",
+        "password": "a-long-enough-password",
+    })).await;
+    assert_eq!(response.headers().get("Location").unwrap(), "/admin/dashboard");
+    let saved = sqlx::query!("SELECT role, email FROM users WHERE username = 'rhea'")
+        .fetch_one(&app.connection)
+        .await
+        .unwrap();
+    assert_eq!(saved.role, "editor");
+    assert_eq!(saved.email.as_deref(), Some("rhea@email.com"));
+}
+
+#[tokio::test]
+async fn an_invited_user_cannot_pick_a_breached_password() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+    let invitation_link = invite(&app, "rhea@email.com", "editor").await;
+    app.post_logout().await;
+
+    let response = app.post_accept_invitation(&accept_form(&invitation_link, "rhea", "password1234")).await;
+    assert_eq!(response.status().as_u16(), 303);
+    assert_ne!(response.headers().get("Location").unwrap(), "/login");
+
+    let html_page = app.api_client.get(invitation_link).send().await.unwrap().text().await.unwrap();
+    assert!(html_page.contains("This password has appeared in a data breach."));
+    let saved = sqlx::query!("SELECT username FROM users WHERE username = 'rhea'")
+        .fetch_optional(&app.connection)
+        .await
+        .unwrap();
+    assert!(saved.is_none());
+}
+
+#[tokio::test]
+async fn an_invitation_can_only_be_used_once() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+    let invitation_link = invite(&app, "rhea@email.com", "viewer").await;
+
+    app.post_accept_invitation(&accept_form(&invitation_link, "rhea", "a-long-enough-password")).await;
+    let response = app.post_accept_invitation(&accept_form(&invitation_link, "phoebe", "a-long-enough-password")).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(reqwest::get(invitation_link).await.unwrap().status().as_u16(), 401);
+    let users = sqlx::query!("SELECT COUNT(*) AS count FROM users")
+        .fetch_one(&app.connection)
+        .await
+        .unwrap();
+    assert_eq!(users.count, Some(2));
+}
+
+#[tokio::test]
+async fn a_tampered_invitation_is_rejected() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+    let invitation_link = invite(&app, "rhea@email.com", "owner").await;
+    let mut form = accept_form(&invitation_link, "rhea", "a-long-enough-password");
+    form["token"] = serde_json::json!(format!("{}0", form["token"].as_str().unwrap()));
+
+    let response = app.post_accept_invitation(&form).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn invitations_need_a_valid_role() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+
+    app.post_user_action("invite", &serde_json::json!({
+        "email": "rhea@email.com",
+        "role": "superuser",
+    })).await;
+
+    let html_page = app.get_users().await.text().await.unwrap();
+    assert!(html_page.contains("superuser is not a user role"));
+}
+
+#[tokio::test]
+async fn deactivated_users_cannot_log_in() {
+    let app = spawn_app().await;
+    let editor = app.add_user("editor").await;
+    app.login_with_test_user().await;
+
+    let response = app.post_user_action("deactivate", &serde_json::json!({
+        "user_id": editor.user_id,
+    })).await;
+    assert_eq!(response.status().as_u16(), 303);
+    app.post_logout().await;
+
+    let response = app.login_as(&editor).await;
+    assert_eq!(response.headers().get("Location").unwrap(), "/login");
+}
+
+#[tokio::test]
+async fn owners_cannot_deactivate_themselves() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+
+    app.post_user_action("deactivate", &serde_json::json!({
+        "user_id": app.test_user.user_id,
+    })).await;
+
+    let html_page = app.get_users().await.text().await.unwrap();
+    assert!(html_page.contains("You cannot deactivate yourself."));
+}
+
+#[tokio::test]
+async fn viewers_cannot_publish_newsletters() {
+    let app = spawn_app().await;
+    let viewer = app.add_user("viewer").await;
+    app.login_as(&viewer).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app.post_newsletters(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    })).await;
+
+    assert_eq!(response.status().as_u16(), 403);
+}
@@ -0,0 +1,235 @@
+use uuid::Uuid;
+use wiremock::{Mock, ResponseTemplate};
+use wiremock::matchers::{method, path};
+
+use crate::helpers::{spawn_app, TestApp};
+
+// Creates a token through the admin page, as the logged in user, and returns it.
+async fn create_token(app: &TestApp, scopes: &[&str]) -> String {
+    let mut body = "name=Script".to_string();
+    for scope in scopes {
+        body.push_str(&format!("&scope={}", scope));
+    }
+    let response = app.post_api_tokens(&body).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let html = response.text().await.unwrap();
+    html.split("<code>z2p_")
+        .nth(1)
+        .and_then(|rest| rest.split("</code>").next())
+        .map(|token| format!("z2p_{}", token))
+        .expect("The token was not shown")
+}
+
+// A client without the session cookie, like a script would use.
+fn bearer_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap()
+}
+
+async fn publish_with_token(app: &TestApp, token: &str) -> reqwest::Response {
+    publish_with_token_and_key(app, token, &Uuid::new_v4().to_string()).await
+}
+
+async fn publish_with_token_and_key(app: &TestApp, token: &str, idempotency_key: &str) -> reqwest::Response {
+    bearer_client()
+        .post(format!("{}/admin/newsletter", &app.address))
+        .bearer_auth(token)
+        .form(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body in plain text",
+            "html_content": "<p>Newsletter body in HTML</p>",
+            "idempotency_key": idempotency_key
+        }))
+        .send()
+        .await
+        .expect("Failed to POST /admin/newsletter endpoint")
+}
+
+async fn stored_token_id(app: &TestApp) -> Uuid {
+    sqlx::query!("SELECT id FROM api_tokens")
+        .fetch_one(&app.connection)
+        .await
+        .expect("Failed to retrieve the API token")
+        .id
+}
+
+#[tokio::test]
+async fn a_token_with_the_publish_scope_can_publish_a_newsletter() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+    let token = create_token(&app, &["publish_newsletters"]).await;
+
+    let response = publish_with_token(&app, &token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.headers().get("Set-Cookie").is_none());
+    let outcome: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(outcome, serde_json::json!({"delivered": 0, "failed": 0}));
+
+    let actor_id = sqlx::query!("SELECT actor_id FROM audit_log WHERE action = 'newsletter_published'")
+        .fetch_one(&app.connection)
+        .await
+        .unwrap()
+        .actor_id;
+    assert_eq!(actor_id, Some(app.test_user.user_id));
+    assert!(app.get_api_tokens().await.text().await.unwrap().contains("Script"));
+}
+
+#[tokio::test]
+async fn only_a_hash_of_the_token_is_stored() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+    let token = create_token(&app, &["publish_newsletters"]).await;
+
+    let token_hash = sqlx::query!("SELECT token_hash FROM api_tokens")
+        .fetch_one(&app.connection)
+        .await
+        .unwrap()
+        .token_hash;
+    assert_ne!(token_hash, token);
+    assert!(!app.get_api_tokens().await.text().await.unwrap().contains(&token));
+}
+
+#[tokio::test]
+async fn unknown_tokens_are_rejected_with_a_401() {
+    let app = spawn_app().await;
+
+    let response = publish_with_token(&app, "z2p_not-a-real-token").await;
+    assert_eq!(response.status().as_u16(), 401);
+    assert!(response.headers().get("WWW-Authenticate").is_some());
+}
+
+#[tokio::test]
+async fn revoked_tokens_are_rejected_with_a_401() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+    let token = create_token(&app, &["publish_newsletters"]).await;
+
+    let response = app.post_revoke_api_token(stored_token_id(&app).await).await;
+    assert_eq!(response.headers().get("Location").unwrap(), "/admin/api-tokens");
+    assert!(app.get_api_tokens().await.text().await.unwrap().contains("Revoked on"));
+
+    let response = publish_with_token(&app, &token).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn a_token_cannot_be_used_outside_its_scopes() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+    let token = create_token(&app, &["read_subscribers"]).await;
+
+    let response = publish_with_token(&app, &token).await;
+    assert_eq!(response.status().as_u16(), 403);
+
+    let response = bearer_client()
+        .get(format!("{}/admin/subscribers", &app.address))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn tokens_cannot_manage_accounts_or_other_tokens() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+    let token = create_token(&app, &["publish_newsletters", "read_subscribers", "manage_subscribers"]).await;
+
+    for path in ["/admin/password", "/admin/api-tokens", "/admin/users", "/admin/dashboard"] {
+        let response = bearer_client()
+            .get(format!("{}{}", &app.address, path))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 403, "{} was allowed", path);
+    }
+}
+
+#[tokio::test]
+async fn a_token_cannot_do_more_than_its_owner() {
+    let app = spawn_app().await;
+    let viewer = app.add_user("viewer").await;
+    app.login_as(&viewer).await;
+    let token = create_token(&app, &["publish_newsletters"]).await;
+
+    let response = publish_with_token(&app, &token).await;
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn a_token_needs_a_name_and_a_scope() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+
+    for body in ["name=Script", "name=&scope=publish_newsletters"] {
+        let response = app.post_api_tokens(body).await;
+        assert_eq!(response.status().as_u16(), 303);
+    }
+    let html = app.get_api_tokens().await.text().await.unwrap();
+    assert!(html.contains("Give the token a name and pick at least one scope."));
+
+    let response = app.post_api_tokens("name=Script&scope=launch_missiles").await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn publishing_with_a_token_still_needs_an_idempotency_key() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+    let token = create_token(&app, &["publish_newsletters"]).await;
+
+    let response = bearer_client()
+        .post(format!("{}/admin/newsletter", &app.address))
+        .bearer_auth(&token)
+        .form(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body in plain text",
+            "html_content": "<p>Newsletter body in HTML</p>",
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn publishing_twice_with_the_same_key_sends_the_issue_once() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+    let token = create_token(&app, &["publish_newsletters"]).await;
+    let confirmation_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.post_subscriptions("name=Dione&email=dione%40email.com".into()).await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    reqwest::get(app.get_confirmation_links(email_request).html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    drop(confirmation_guard);
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let idempotency_key = Uuid::new_v4().to_string();
+    let first = publish_with_token_and_key(&app, &token, &idempotency_key).await;
+    let second = publish_with_token_and_key(&app, &token, &idempotency_key).await;
+
+    assert_eq!(first.status().as_u16(), 200);
+    assert_eq!(second.status().as_u16(), 200);
+    let first: serde_json::Value = first.json().await.unwrap();
+    let second: serde_json::Value = second.json().await.unwrap();
+    assert_eq!(first, serde_json::json!({"delivered": 1, "failed": 0}));
+    assert_eq!(first, second);
+}
@@ -0,0 +1,159 @@
+use uuid::Uuid;
+use wiremock::{Mock, ResponseTemplate};
+use wiremock::matchers::any;
+
+use crate::helpers::{spawn_app, TestApp};
+
+struct AuditEvent {
+    actor_id: Option<Uuid>,
+    action: String,
+    target: Option<String>,
+    client_ip: String,
+    user_agent: Option<String>,
+}
+
+async fn audit_events(app: &TestApp) -> Vec<AuditEvent> {
+    sqlx::query_as!(
+        AuditEvent,
+        "SELECT actor_id, action, target, client_ip, user_agent FROM audit_log ORDER BY occurred_at"
+    )
+        .fetch_all(&app.connection)
+        .await
+        .expect("Failed to retrieve the audit log")
+}
+
+#[tokio::test]
+async fn logins_and_failed_logins_are_recorded() {
+    let app = spawn_app().await;
+    app.post_login(&serde_json::json!({
+        "username": "mallory",
+        "password": "guess",
+    })).await;
+    app.login_with_test_user().await;
+
+    let events = audit_events(&app).await;
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].action, "login_failed");
+    assert_eq!(events[0].actor_id, None);
+    assert_eq!(events[0].target.as_deref(), Some("mallory"));
+    assert_eq!(events[0].client_ip, "127.0.0.1");
+    assert_eq!(events[1].action, "login");
+    assert_eq!(events[1].actor_id, Some(app.test_user.user_id));
+}
+
+#[tokio::test]
+async fn the_user_agent_is_recorded() {
+    let app = spawn_app().await;
+
+    app.api_client
+        .post(format!("{}/login", &app.address))
+        .header("User-Agent", "audit-test/1.0")
+        .form(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    let events = audit_events(&app).await;
+    assert_eq!(events[0].user_agent.as_deref(), Some("audit-test/1.0"));
+}
+
+#[tokio::test]
+async fn password_changes_and_logouts_are_recorded() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+    let new_password = Uuid::new_v4().to_string();
+
+    app.post_change_password(&serde_json::json!({
+        "current_password": &app.test_user.password,
+        "new_password": &new_password,
+        "new_password_check": &new_password,
+    })).await;
+    app.post_logout().await;
+
+    let actions: Vec<_> = audit_events(&app).await.into_iter().map(|event| event.action).collect();
+    assert_eq!(actions, ["login", "password_changed", "logout"]);
+}
+
+#[tokio::test]
+async fn publishing_a_newsletter_is_recorded_with_its_title() {
+    let app = spawn_app().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.login_with_test_user().await;
+
+    app.post_newsletters(&serde_json::json!({
+        "title": "Issue #1",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string(),
+    })).await;
+
+    let event = audit_events(&app).await.pop().unwrap();
+    assert_eq!(event.action, "newsletter_published");
+    assert_eq!(event.actor_id, Some(app.test_user.user_id));
+    assert_eq!(event.target.as_deref(), Some("Issue #1"));
+}
+
+#[tokio::test]
+async fn the_audit_log_page_can_be_filtered_by_action() {
+    let app = spawn_app().await;
+    app.post_login(&serde_json::json!({
+        "username": "mallory",
+        "password": "guess",
+    })).await;
+    app.login_with_test_user().await;
+
+    let html_page = app.get_audit_log("").await.text().await.unwrap();
+    assert!(html_page.contains("mallory"));
+    assert!(html_page.contains(&app.test_user.username));
+
+    let html_page = app.get_audit_log("action=login").await.text().await.unwrap();
+    assert!(!html_page.contains("mallory"));
+    assert!(html_page.contains("(1 events)"));
+}
+
+#[tokio::test]
+async fn filtering_by_actor_includes_failed_logins_for_that_username() {
+    let app = spawn_app().await;
+    for username in [app.test_user.username.as_str(), "mallory"] {
+        app.post_login(&serde_json::json!({
+            "username": username,
+            "password": "guess",
+        })).await;
+    }
+    app.login_with_test_user().await;
+
+    let query = format!("actor={}", app.test_user.username);
+    let html_page = app.get_audit_log(&query).await.text().await.unwrap();
+    assert!(!html_page.contains("mallory"));
+    assert!(html_page.contains("(2 events)"));
+
+    let html_page = app.get_audit_log("actor=mallory").await.text().await.unwrap();
+    assert!(html_page.contains("(1 events)"));
+}
+
+#[tokio::test]
+async fn unknown_actions_are_rejected_by_the_filter() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+
+    let response = app.get_audit_log("action=launch_missiles").await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn only_owners_can_read_the_audit_log() {
+    let app = spawn_app().await;
+    let editor = app.add_user("editor").await;
+    app.login_as(&editor).await;
+
+    let response = app.get_audit_log("").await;
+
+    assert_eq!(response.status().as_u16(), 403);
+}
@@ -1,4 +1,4 @@
-use crate::helpers::spawn_app;
+use crate::helpers::{spawn_app, spawn_app_with_configuration};
 use uuid::Uuid;
 use reqwest::Response;
 
@@ -102,11 +102,89 @@ async fn new_password_cannot_be_too_short() {
     let html_page = response.text().await.unwrap();
 
     assert!(html_page.contains(
-        "<p><i>The new password must be between 12 and 128 characters long</i></p>"
+        "<p><i>The password must be between 12 and 128 characters long</i></p>"
     ));
 
 }
 
+#[tokio::test]
+async fn new_password_cannot_be_too_long() {
+    let app = spawn_app().await;
+    // 264 characters, which the length check used to count as 8.
+    let new_password = "a-b".repeat(88);
+
+    app.login_with_test_user().await;
+
+    app.post_change_password(&serde_json::json!({
+        "current_password": &app.test_user.password,
+        "new_password": &new_password,
+        "new_password_check": &new_password,
+    })).await;
+
+    let html_page = app.get_change_password().await.text().await.unwrap();
+    assert!(html_page.contains(
+        "<p><i>The password must be between 12 and 128 characters long</i></p>"
+    ));
+}
+
+#[tokio::test]
+async fn new_password_cannot_be_a_breached_password() {
+    let app = spawn_app().await;
+    let new_password = String::from("password1234");
+
+    app.login_with_test_user().await;
+
+    let response = app.post_change_password(&serde_json::json!({
+        "current_password": &app.test_user.password,
+        "new_password": &new_password,
+        "new_password_check": &new_password,
+    })).await;
+    assert_eq!(response.headers().get("Location").unwrap(), "/admin/password");
+
+    let html_page = app.get_change_password().await.text().await.unwrap();
+    assert!(html_page.contains(
+        "<p><i>This password has appeared in a data breach. Please choose a different one.</i></p>"
+    ));
+}
+
+#[tokio::test]
+async fn new_password_cannot_be_easy_to_guess() {
+    let app = spawn_app().await;
+    let new_password = String::from("aaaaaaaaaaaaaaaa");
+
+    app.login_with_test_user().await;
+
+    app.post_change_password(&serde_json::json!({
+        "current_password": &app.test_user.password,
+        "new_password": &new_password,
+        "new_password_check": &new_password,
+    })).await;
+
+    let html_page = app.get_change_password().await.text().await.unwrap();
+    assert!(html_page.contains("This password is too easy to guess."));
+}
+
+#[tokio::test]
+async fn the_password_policy_is_configurable() {
+    let app = spawn_app_with_configuration(|config| {
+        config.password_policy.minimum_length = 40;
+    }).await;
+    let new_password = Uuid::new_v4().to_string();
+
+    app.login_with_test_user().await;
+
+    app.post_change_password(&serde_json::json!({
+        "current_password": &app.test_user.password,
+        "new_password": &new_password,
+        "new_password_check": &new_password,
+    })).await;
+
+    let html_page = app.get_change_password().await.text().await.unwrap();
+    assert!(html_page.contains(
+        "<p><i>The password must be between 40 and 128 characters long</i></p>"
+    ));
+}
+
 #[tokio::test]
 async fn changing_password_works() {
     let app = spawn_app().await;
@@ -0,0 +1,102 @@
+use crate::helpers::{spawn_app, TestApp};
+
+async fn post_attribute_form(app: &TestApp, body: serde_json::Value) -> reqwest::Response {
+    app.api_client
+        .post(format!("{}/admin/attributes", &app.address))
+        .form(&body)
+        .send()
+        .await
+        .expect("Failed to POST /admin/attributes endpoint")
+}
+
+#[tokio::test]
+async fn admin_forms_carry_the_session_csrf_token() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+    let token = app.csrf_token().expect("The dashboard has no CSRF token");
+
+    for html in [
+        app.get_change_password().await.text().await.unwrap(),
+        app.get_newsletter().await.text().await.unwrap(),
+        app.get_attributes().await.text().await.unwrap(),
+    ] {
+        assert!(html.contains(&format!(r#"name="csrf_token" value="{}""#, token)));
+    }
+}
+
+#[tokio::test]
+async fn a_form_with_the_token_is_accepted() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+
+    let response = post_attribute_form(&app, serde_json::json!({
+        "key": "company",
+        "value_type": "string",
+        "csrf_token": app.csrf_token().unwrap(),
+    })).await;
+
+    assert_eq!(response.status().as_u16(), 303);
+    assert!(app.get_attributes().await.text().await.unwrap().contains("<td>company</td>"));
+}
+
+#[tokio::test]
+async fn a_form_without_the_token_is_rejected_with_a_friendly_error() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+
+    let response = post_attribute_form(&app, serde_json::json!({
+        "key": "company",
+        "value_type": "string",
+    })).await;
+
+    assert_eq!(response.status().as_u16(), 403);
+    assert!(response.text().await.unwrap().contains("This form has expired"));
+    assert!(!app.get_attributes().await.text().await.unwrap().contains("<td>company</td>"));
+}
+
+#[tokio::test]
+async fn a_form_with_a_wrong_token_is_rejected() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+
+    let response = post_attribute_form(&app, serde_json::json!({
+        "key": "company",
+        "value_type": "string",
+        "csrf_token": "forged",
+    })).await;
+
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn logging_out_needs_the_token() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+
+    let response = app.api_client
+        .post(format!("{}/admin/logout", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 403);
+    assert_eq!(app.get_admin_dashboard().await.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn every_login_gets_a_new_token() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+    let first_token = app.csrf_token().unwrap();
+    app.post_logout().await;
+
+    app.login_with_test_user().await;
+    assert_ne!(app.csrf_token().unwrap(), first_token);
+
+    let response = post_attribute_form(&app, serde_json::json!({
+        "key": "company",
+        "value_type": "string",
+        "csrf_token": first_token,
+    })).await;
+    assert_eq!(response.status().as_u16(), 403);
+}
@@ -1,5 +1,5 @@
 use once_cell::sync::Lazy;
-use zero2prod::configuration::{get_configuration, DatabaseSettings};
+use zero2prod::configuration::{get_configuration, DatabaseSettings, Settings};
 use uuid::Uuid;
 use zero2prod::startup::{DbConnectionKind, Application, get_database_connection};
 use sqlx::{Connection, Executor, PgConnection, PgPool};
@@ -8,6 +8,9 @@ use wiremock::MockServer;
 use argon2::password_hash::SaltString;
 use argon2::{Argon2, PasswordHasher};
 use reqwest::Client;
+use secrecy::Secret;
+use std::sync::Mutex;
+use zero2prod::email_client::EmailClient;
 
 static TRACING: Lazy<()> = Lazy::new(|| {
     let default_filter_level = "info".to_string();
@@ -42,6 +45,11 @@ pub struct TestApp {
     pub port: u16,
     pub test_user: TestUser,
     pub api_client: Client,
+    pub email_client: EmailClient,
+    pub base_url: String,
+    pub hmac_secret: Secret<String>,
+    // The CSRF token of the session the last login started.
+    csrf_token: Mutex<Option<String>>,
 }
 
 impl TestApp {
@@ -55,6 +63,7 @@ impl TestApp {
             let links: Vec<_> = linkify::LinkFinder::new()
                 .links(s)
                 .filter(|link| { *link.kind() == linkify::LinkKind::Url })
+                .filter(|link| link.as_str().contains("/subscriptions/confirm"))
                 .collect();
             assert_eq!(links.len(), 1);
 
@@ -74,6 +83,71 @@ impl TestApp {
         }
     }
 
+    // For emails sent in the background: waits until `count` of them have arrived.
+    pub async fn wait_for_emails(&self, count: usize) -> Vec<wiremock::Request> {
+        for _ in 0..100 {
+            let requests = self.email_server.received_requests().await.unwrap();
+            if requests.len() >= count {
+                return requests;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+        panic!("Timed out waiting for {} emails", count);
+    }
+
+    pub fn get_email_links(&self, email_request: &wiremock::Request) -> Vec<reqwest::Url> {
+        let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+        linkify::LinkFinder::new()
+            .links(body["TextBody"].as_str().unwrap())
+            .filter(|link| *link.kind() == linkify::LinkKind::Url)
+            .map(|link| {
+                let mut url = reqwest::Url::parse(link.as_str()).unwrap();
+                assert_eq!(url.host_str().unwrap(), "127.0.0.1");
+                url.set_port(Some(self.port)).unwrap();
+                url
+            })
+            .collect()
+    }
+
+    pub fn get_email_link(&self, email_request: &wiremock::Request, path: &str) -> reqwest::Url {
+        let links: Vec<_> = self.get_email_links(email_request)
+            .into_iter()
+            .filter(|link| link.path() == path)
+            .collect();
+        assert_eq!(links.len(), 1);
+        links[0].clone()
+    }
+
+    pub async fn post_subscriptions(&self, body: String) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/subscriptions", &self.address))
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(body)
+            .send()
+            .await
+            .expect("Failed to POST /subscriptions endpoint")
+    }
+
+    pub async fn post_subscriber_data_request<Body>(&self, body: &Body) -> reqwest::Response
+        where
+            Body: serde::Serialize
+    {
+        self.api_client
+            .post(format!("{}/subscriptions/data", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to POST /subscriptions/data endpoint")
+    }
+
+    pub async fn get_home(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/", &self.address))
+            .send()
+            .await
+            .expect("Failed to GET home page")
+    }
+
     pub async fn get_newsletter(&self) -> reqwest::Response {
         self.api_client
             .get(format!("{}/admin/newsletter", &self.address))
@@ -86,8 +160,7 @@ impl TestApp {
         where
             Body: serde::Serialize
     {
-        self.api_client
-            .post(format!("{}/admin/newsletter", &self.address))
+        self.post_admin(format!("{}/admin/newsletter", &self.address))
             .form(body)
             .send()
             .await
@@ -98,12 +171,43 @@ impl TestApp {
         where
             Body: serde::Serialize
     {
-        self.api_client
+        let response = self.api_client
             .post(format!("{}/login", &self.address))
             .form(body)
             .send()
             .await
-            .expect("Failed to POST login endpoint")
+            .expect("Failed to POST login endpoint");
+        self.remember_csrf_token(&response).await;
+        response
+    }
+
+    // Admin forms carry the session's CSRF token. The helpers send it as a header
+    // instead, so that tests can keep posting the fields they care about.
+    fn post_admin(&self, url: String) -> reqwest::RequestBuilder {
+        let request = self.api_client.post(url);
+        match self.csrf_token.lock().unwrap().clone() {
+            Some(token) => request.header("X-CSRF-Token", token),
+            None => request,
+        }
+    }
+
+    // Picks the token up from the dashboard, like a browser following the redirect
+    // after a successful login would.
+    async fn remember_csrf_token(&self, login_response: &reqwest::Response) {
+        if login_response.headers().get("Location").map_or(true, |location| location != "/admin/dashboard") {
+            return;
+        }
+        let html = self.get_admin_dashboard().await.text().await.unwrap();
+        let token = html
+            .split(r#"name="csrf_token" value=""#)
+            .nth(1)
+            .and_then(|rest| rest.split('"').next())
+            .map(|token| token.to_string());
+        *self.csrf_token.lock().unwrap() = token;
+    }
+
+    pub fn csrf_token(&self) -> Option<String> {
+        self.csrf_token.lock().unwrap().clone()
     }
 
     pub async fn get_login(&self) -> reqwest::Response {
@@ -123,12 +227,22 @@ impl TestApp {
     }
 
     pub async fn login_with_test_user(&self) -> reqwest::Response {
+        self.login_as(&self.test_user).await
+    }
+
+    pub async fn login_as(&self, user: &TestUser) -> reqwest::Response {
         self.post_login(&serde_json::json!({
-            "username": self.test_user.username,
-            "password": self.test_user.password
+            "username": user.username,
+            "password": user.password
         })).await
     }
 
+    pub async fn add_user(&self, role: &str) -> TestUser {
+        let user = TestUser::generate_with_role(role);
+        user.store(&self.connection).await;
+        user
+    }
+
     pub async fn get_admin_dashboard(&self) -> reqwest::Response {
         self.api_client
             .get(format!("{}/admin/dashboard", &self.address))
@@ -149,8 +263,7 @@ impl TestApp {
         where
             Body: serde::Serialize
     {
-        self.api_client
-            .post(format!("{}/admin/password", &self.address))
+        self.post_admin(format!("{}/admin/password", &self.address))
             .form(body)
             .send()
             .await
@@ -158,26 +271,255 @@ impl TestApp {
     }
 
     pub async fn post_logout(&self) -> reqwest::Response {
-        self.api_client
-            .post(format!("{}/admin/logout", &self.address))
+        self.post_admin(format!("{}/admin/logout", &self.address))
             .send()
             .await
             .expect("Failed to POST /admin/logout endpoint")
     }
+
+    pub async fn get_attributes(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/attributes", &self.address))
+            .send()
+            .await
+            .expect("Failed to GET /admin/attributes endpoint")
+    }
+
+    pub async fn post_attributes<Body>(&self, body: &Body) -> reqwest::Response
+        where
+            Body: serde::Serialize
+    {
+        self.post_admin(format!("{}/admin/attributes", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to POST /admin/attributes endpoint")
+    }
+
+    pub async fn get_subscribers(&self, query: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/subscribers?{}", &self.address, query))
+            .send()
+            .await
+            .expect("Failed to GET /admin/subscribers endpoint")
+    }
+
+    pub async fn post_subscriber_action<Body>(&self, action: &str, body: &Body) -> reqwest::Response
+        where
+            Body: serde::Serialize
+    {
+        self.post_admin(format!("{}/admin/subscribers/{}", &self.address, action))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to POST an /admin/subscribers action")
+    }
+
+    pub async fn post_import<Body>(&self, body: &Body) -> reqwest::Response
+        where
+            Body: serde::Serialize
+    {
+        self.post_admin(format!("{}/admin/subscribers/import", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to POST /admin/subscribers/import endpoint")
+    }
+
+    pub async fn get_export(&self, query: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/subscribers/export?{}", &self.address, query))
+            .send()
+            .await
+            .expect("Failed to GET /admin/subscribers/export endpoint")
+    }
+
+    pub async fn get_welcome_sequence(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/welcome-sequence", &self.address))
+            .send()
+            .await
+            .expect("Failed to GET /admin/welcome-sequence endpoint")
+    }
+
+    pub async fn post_welcome_step<Body>(&self, body: &Body) -> reqwest::Response
+        where
+            Body: serde::Serialize
+    {
+        self.post_admin(format!("{}/admin/welcome-sequence", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to POST /admin/welcome-sequence endpoint")
+    }
+
+    pub async fn send_due_welcome_emails(&self) -> Result<u64, anyhow::Error> {
+        zero2prod::welcome_sequence::send_due_welcome_emails(
+            &self.connection,
+            &self.email_client,
+            &self.base_url,
+            &self.hmac_secret,
+        ).await
+    }
+
+    pub async fn get_users(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/users", &self.address))
+            .send()
+            .await
+            .expect("Failed to GET /admin/users endpoint")
+    }
+
+    pub async fn post_user_action<Body>(&self, action: &str, body: &Body) -> reqwest::Response
+        where
+            Body: serde::Serialize
+    {
+        self.post_admin(format!("{}/admin/users/{}", &self.address, action))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to POST /admin/users endpoint")
+    }
+
+    pub async fn post_password_reset_request(&self, email: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/password-reset", &self.address))
+            .form(&serde_json::json!({ "email": email }))
+            .send()
+            .await
+            .expect("Failed to POST /password-reset endpoint")
+    }
+
+    pub async fn post_password_reset<Body>(&self, body: &Body) -> reqwest::Response
+        where
+            Body: serde::Serialize
+    {
+        self.api_client
+            .post(format!("{}/password-reset/confirm", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to POST /password-reset/confirm endpoint")
+    }
+
+    pub async fn get_audit_log(&self, query: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/audit-log?{}", &self.address, query))
+            .send()
+            .await
+            .expect("Failed to GET /admin/audit-log endpoint")
+    }
+
+    pub async fn get_two_factor(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/two-factor", &self.address))
+            .send()
+            .await
+            .expect("Failed to GET /admin/two-factor endpoint")
+    }
+
+    pub async fn post_two_factor_action<Body>(&self, action: &str, body: &Body) -> reqwest::Response
+        where
+            Body: serde::Serialize
+    {
+        self.post_admin(format!("{}/admin/two-factor/{}", &self.address, action))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to POST /admin/two-factor endpoint")
+    }
+
+    pub async fn post_login_two_factor(&self, code: &str) -> reqwest::Response {
+        let response = self.api_client
+            .post(format!("{}/login/two-factor", &self.address))
+            .form(&serde_json::json!({ "code": code }))
+            .send()
+            .await
+            .expect("Failed to POST /login/two-factor endpoint");
+        self.remember_csrf_token(&response).await;
+        response
+    }
+
+    pub async fn get_api_tokens(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/api-tokens", &self.address))
+            .send()
+            .await
+            .expect("Failed to GET /admin/api-tokens endpoint")
+    }
+
+    // Takes an encoded form, since scopes are sent as a repeated field.
+    pub async fn post_api_tokens(&self, body: &str) -> reqwest::Response {
+        self.post_admin(format!("{}/admin/api-tokens", &self.address))
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(body.to_string())
+            .send()
+            .await
+            .expect("Failed to POST /admin/api-tokens endpoint")
+    }
+
+    pub async fn post_revoke_api_token(&self, token_id: Uuid) -> reqwest::Response {
+        self.post_admin(format!("{}/admin/api-tokens/revoke", &self.address))
+            .form(&serde_json::json!({ "token_id": token_id }))
+            .send()
+            .await
+            .expect("Failed to POST /admin/api-tokens/revoke endpoint")
+    }
+
+    pub async fn get_sessions(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/sessions", &self.address))
+            .send()
+            .await
+            .expect("Failed to GET /admin/sessions endpoint")
+    }
+
+    pub async fn post_revoke_session(&self, session_id: Uuid) -> reqwest::Response {
+        self.post_admin(format!("{}/admin/sessions/revoke", &self.address))
+            .form(&serde_json::json!({ "session_id": session_id }))
+            .send()
+            .await
+            .expect("Failed to POST /admin/sessions/revoke endpoint")
+    }
+
+    pub async fn post_revoke_other_sessions(&self) -> reqwest::Response {
+        self.post_admin(format!("{}/admin/sessions/revoke-others", &self.address))
+            .send()
+            .await
+            .expect("Failed to POST /admin/sessions/revoke-others endpoint")
+    }
+
+    pub async fn post_accept_invitation<Body>(&self, body: &Body) -> reqwest::Response
+        where
+            Body: serde::Serialize
+    {
+        self.api_client
+            .post(format!("{}/invitations/accept", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to POST /invitations/accept endpoint")
+    }
 }
 
 pub struct TestUser {
     pub user_id: Uuid,
     pub username: String,
     pub password: String,
+    pub role: String,
 }
 
 impl TestUser {
     pub fn generate() -> Self {
+        Self::generate_with_role("owner")
+    }
+
+    pub fn generate_with_role(role: &str) -> Self {
         TestUser {
             user_id: Uuid::new_v4(),
             username: Uuid::new_v4().to_string(),
             password: Uuid::new_v4().to_string(),
+            role: role.to_string(),
         }
     }
 
@@ -190,12 +532,13 @@ impl TestUser {
 
         sqlx::query!(
             r#"
-            INSERT INTO users (user_id, username, password_hash)
-            VALUES ($1, $2, $3)
+            INSERT INTO users (user_id, username, password_hash, role)
+            VALUES ($1, $2, $3, $4)
             "#,
             self.user_id,
             self.username,
-            password_hash
+            password_hash,
+            self.role
         )
             .execute(database)
             .await
@@ -205,6 +548,10 @@ impl TestUser {
 
 
 pub async fn spawn_app() -> TestApp {
+    spawn_app_with_configuration(|_| {}).await
+}
+
+pub async fn spawn_app_with_configuration(customise: impl FnOnce(&mut Settings)) -> TestApp {
     Lazy::force(&TRACING);
 
     let email_server = MockServer::start().await;
@@ -215,6 +562,9 @@ pub async fn spawn_app() -> TestApp {
         config.database.database_name = Uuid::new_v4().to_string();
         config.application.port = 0;
         config.email_client.base_url = email_server.uri();
+        // Most tests post to /subscriptions directly rather than through the home page form
+        config.subscription_protection.minimum_form_fill_seconds = 0;
+        customise(&mut config);
         config
     };
 
@@ -242,6 +592,10 @@ pub async fn spawn_app() -> TestApp {
         email_server,
         test_user,
         api_client: client,
+        email_client: configuration.email_client.client(),
+        base_url: configuration.application.base_url.clone(),
+        hmac_secret: configuration.application.hmac_secret.clone(),
+        csrf_token: Mutex::new(None),
     };
     test_app.test_user.store(&test_app.connection).await;
     test_app
@@ -0,0 +1,85 @@
+use crate::helpers::spawn_app;
+use wiremock::{Mock, ResponseTemplate};
+use wiremock::matchers::{path, method};
+
+async fn subject_of_confirmation_email(app: &crate::helpers::TestApp) -> String {
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    body["Subject"].as_str().unwrap().to_owned()
+}
+
+#[tokio::test]
+async fn the_locale_chosen_in_the_form_is_used_for_the_confirmation_email() {
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    let response = app.post_subscriptions("name=Dione&email=dione%40email.com&locale=fr".into()).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.text().await.unwrap().contains("Consultez votre boîte de réception"));
+    assert_eq!(subject_of_confirmation_email(&app).await, "Bienvenue !");
+    let saved = sqlx::query!("SELECT locale FROM subscriptions")
+        .fetch_one(&app.connection)
+        .await
+        .expect("Failed to retrieve subscriber");
+    assert_eq!(saved.locale, "fr");
+}
+
+#[tokio::test]
+async fn the_accept_language_header_is_used_when_no_locale_is_chosen() {
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    app.api_client
+        .post(&format!("{}/subscriptions", &app.address))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .header("Accept-Language", "de-DE,es;q=0.8,en;q=0.5")
+        .body("name=Dione&email=dione%40email.com")
+        .send()
+        .await
+        .expect("Failed to submit subscription information");
+
+    assert_eq!(subject_of_confirmation_email(&app).await, "¡Bienvenido!");
+}
+
+#[tokio::test]
+async fn unsupported_locales_fall_back_to_english() {
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscriptions("name=Dione&email=dione%40email.com&locale=xx".into()).await;
+
+    assert_eq!(subject_of_confirmation_email(&app).await, "Welcome!");
+}
+
+#[tokio::test]
+async fn the_confirmation_page_is_shown_in_the_subscribers_locale() {
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriptions("name=Dione&email=dione%40email.com&locale=es".into()).await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_link = app.get_confirmation_links(email_request);
+
+    let response = reqwest::get(confirmation_link.html).await.unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains(r#"<html lang="es">"#));
+    assert!(html_page.contains("Suscripción confirmada"));
+}
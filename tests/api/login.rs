@@ -1,4 +1,4 @@
-use crate::helpers::spawn_app;
+use crate::helpers::{spawn_app, spawn_app_with_configuration, TestApp};
 use std::collections::HashSet;
 use reqwest::header::HeaderValue;
 
@@ -52,4 +52,186 @@ async fn redirect_to_login_after_login_failure() {
 
     assert_eq!(response.status().as_u16(), 303);
     assert_eq!(response.headers().get("Location").unwrap(), "/login")
-}
\ No newline at end of file
+}
+async fn spawn_app_with_login_limits(username_failures: u32, ip_failures: u32) -> TestApp {
+    spawn_app_with_configuration(|config| {
+        config.login_protection.username_failure_limit.max_requests = username_failures;
+        config.login_protection.ip_failure_limit.max_requests = ip_failures;
+        config.login_protection.failure_delay_milliseconds = 0;
+    }).await
+}
+
+async fn fail_login(app: &TestApp, username: &str) {
+    let response = app.post_login(&serde_json::json!({
+        "username": username,
+        "password": "wrong-password",
+    })).await;
+    assert_eq!(response.headers().get("Location").unwrap(), "/login");
+}
+
+#[tokio::test]
+async fn a_username_is_locked_out_after_too_many_failures() {
+    let app = spawn_app_with_login_limits(3, 100).await;
+    for _ in 0..3 {
+        fail_login(&app, &app.test_user.username).await;
+    }
+
+    // Even the right password is refused during the lockout.
+    let response = app.login_with_test_user().await;
+    assert_eq!(response.headers().get("Location").unwrap(), "/login");
+    let html_page = app.get_login().await.text().await.unwrap();
+    assert!(html_page.contains("Too many failed login attempts"));
+
+    // Other users are not affected.
+    let other_user = app.add_user("editor").await;
+    let response = app.login_as(&other_user).await;
+    assert_eq!(response.headers().get("Location").unwrap(), "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn unknown_usernames_are_locked_out_like_real_ones() {
+    let app = spawn_app_with_login_limits(2, 100).await;
+    for _ in 0..2 {
+        fail_login(&app, "nobody").await;
+    }
+
+    fail_login(&app, "nobody").await;
+    let html_page = app.get_login().await.text().await.unwrap();
+    assert!(html_page.contains("Too many failed login attempts"));
+}
+
+#[tokio::test]
+async fn a_client_ip_is_locked_out_after_too_many_failures_across_usernames() {
+    let app = spawn_app_with_login_limits(100, 3).await;
+    for username in ["alice", "bob", "carol"] {
+        fail_login(&app, username).await;
+    }
+
+    let response = app.login_with_test_user().await;
+    assert_eq!(response.headers().get("Location").unwrap(), "/login");
+    let html_page = app.get_login().await.text().await.unwrap();
+    assert!(html_page.contains("Too many failed login attempts"));
+}
+
+#[tokio::test]
+async fn a_successful_login_resets_the_failures_of_the_username() {
+    let app = spawn_app_with_login_limits(3, 100).await;
+    for _ in 0..2 {
+        fail_login(&app, &app.test_user.username).await;
+    }
+    app.login_with_test_user().await;
+    app.post_logout().await;
+
+    for _ in 0..2 {
+        fail_login(&app, &app.test_user.username).await;
+    }
+    let response = app.login_with_test_user().await;
+    assert_eq!(response.headers().get("Location").unwrap(), "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn repeated_failures_are_answered_more_slowly() {
+    let app = spawn_app_with_configuration(|config| {
+        config.login_protection.free_failures = 1;
+        config.login_protection.failure_delay_milliseconds = 300;
+    }).await;
+    fail_login(&app, &app.test_user.username).await;
+
+    let started_at = std::time::Instant::now();
+    fail_login(&app, &app.test_user.username).await;
+
+    assert!(started_at.elapsed() >= std::time::Duration::from_millis(300));
+}
+
+#[tokio::test]
+async fn wrong_passwords_sent_in_parallel_cannot_get_past_the_lockout() {
+    let app = spawn_app_with_login_limits(3, 100).await;
+    // A client of their own for every guess, so that each keeps its own flash message.
+    let clients: Vec<reqwest::Client> = (0..10)
+        .map(|_| {
+            reqwest::Client::builder()
+                .redirect(reqwest::redirect::Policy::none())
+                .cookie_store(true)
+                .build()
+                .unwrap()
+        })
+        .collect();
+
+    futures_util::future::join_all(clients.iter().map(|client| {
+        client
+            .post(format!("{}/login", &app.address))
+            .form(&serde_json::json!({
+                "username": &app.test_user.username,
+                "password": "wrong-password",
+            }))
+            .send()
+    }))
+        .await;
+
+    let mut locked_out = 0;
+    for client in &clients {
+        let html_page = client
+            .get(format!("{}/login", &app.address))
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        if html_page.contains("Too many failed login attempts") {
+            locked_out += 1;
+        }
+    }
+    assert_eq!(locked_out, 10 - 3);
+}
+
+async fn stored_password_hash(app: &TestApp) -> String {
+    sqlx::query!("SELECT password_hash FROM users WHERE user_id = $1", app.test_user.user_id)
+        .fetch_one(&app.connection)
+        .await
+        .expect("Failed to retrieve the password hash")
+        .password_hash
+}
+
+#[tokio::test]
+async fn an_outdated_password_hash_is_upgraded_on_login() {
+    let app = spawn_app_with_configuration(|config| {
+        config.password_hashing.memory_kib = 8192;
+        config.password_hashing.iterations = 1;
+    }).await;
+    let old_hash = stored_password_hash(&app).await;
+    assert!(!old_hash.contains("m=8192,t=1"));
+
+    let response = app.login_with_test_user().await;
+    assert_eq!(response.headers().get("Location").unwrap(), "/admin/dashboard");
+
+    let new_hash = stored_password_hash(&app).await;
+    assert!(new_hash.starts_with("$argon2id$v=19$m=8192,t=1,p=1$"));
+    app.post_logout().await;
+    let response = app.login_with_test_user().await;
+    assert_eq!(response.headers().get("Location").unwrap(), "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn a_current_password_hash_is_left_alone() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+    let upgraded_hash = stored_password_hash(&app).await;
+    app.post_logout().await;
+
+    app.login_with_test_user().await;
+    assert_eq!(stored_password_hash(&app).await, upgraded_hash);
+}
+
+#[tokio::test]
+async fn a_wrong_password_does_not_upgrade_the_hash() {
+    let app = spawn_app().await;
+    let old_hash = stored_password_hash(&app).await;
+
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": "wrong password",
+    })).await;
+
+    assert_eq!(stored_password_hash(&app).await, old_hash);
+}
@@ -2,6 +2,25 @@ mod helpers;
 mod health_check;
 mod subscriptions;
 mod subscriptions_confirm;
+mod subscriptions_data;
+mod subscription_protection;
+mod subscriptions_preferences;
+mod subscriber_attributes;
+mod subscriber_import;
+mod subscriber_export;
+mod admin_subscribers;
+mod admin_users;
+mod admin_authorization;
+mod pending_subscription_cleanup;
+mod subscription_status;
+mod localization;
+mod welcome_sequence;
 mod newsletter;
 mod login;
-mod change_password;
\ No newline at end of file
+mod change_password;
+mod two_factor;
+mod password_reset;
+mod audit_log;
+mod api_tokens;
+mod csrf;
+mod sessions;
\ No newline at end of file
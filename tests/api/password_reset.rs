@@ -0,0 +1,216 @@
+use uuid::Uuid;
+use wiremock::{Mock, ResponseTemplate};
+use wiremock::matchers::{any, path, method};
+
+use crate::helpers::{spawn_app, spawn_app_with_configuration, TestApp};
+
+const EMAIL: &str = "rhea@email.com";
+
+async fn give_test_user_an_email(app: &TestApp) {
+    sqlx::query!("UPDATE users SET email = $1 WHERE user_id = $2", EMAIL, app.test_user.user_id)
+        .execute(&app.connection)
+        .await
+        .unwrap();
+}
+
+async fn request_reset_link(app: &TestApp) -> reqwest::Url {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let already_sent = app.email_server.received_requests().await.unwrap().len();
+    let response = app.post_password_reset_request(EMAIL).await;
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(response.headers().get("Location").unwrap(), "/login");
+
+    let email_request = app.wait_for_emails(already_sent + 1).await.pop().unwrap();
+    app.get_email_link(&email_request, "/password-reset/confirm")
+}
+
+fn reset_form(reset_link: &reqwest::Url, password: &str) -> serde_json::Value {
+    let token = reset_link
+        .query_pairs()
+        .find(|(key, _)| key == "token")
+        .unwrap()
+        .1
+        .into_owned();
+    serde_json::json!({
+        "token": token,
+        "new_password": password,
+        "new_password_check": password,
+    })
+}
+
+#[tokio::test]
+async fn the_login_page_links_to_the_password_reset() {
+    let app = spawn_app().await;
+
+    let html_page = app.get_login().await.text().await.unwrap();
+
+    assert!(html_page.contains(r#"href="/password-reset""#));
+}
+
+#[tokio::test]
+async fn an_admin_can_reset_their_password_and_log_in_with_it() {
+    let app = spawn_app().await;
+    give_test_user_an_email(&app).await;
+    let reset_link = request_reset_link(&app).await;
+    let new_password = Uuid::new_v4().to_string();
+
+    assert_eq!(reqwest::get(reset_link.clone()).await.unwrap().status().as_u16(), 200);
+    let response = app.post_password_reset(&reset_form(&reset_link, &new_password)).await;
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(response.headers().get("Location").unwrap(), "/login");
+
+    let response = app.login_with_test_user().await;
+    assert_eq!(response.headers().get("Location").unwrap(), "/login");
+    let response = app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &new_password,
+    })).await;
+    assert_eq!(response.headers().get("Location").unwrap(), "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn a_reset_link_can_only_be_used_once() {
+    let app = spawn_app().await;
+    give_test_user_an_email(&app).await;
+    let reset_link = request_reset_link(&app).await;
+    app.post_password_reset(&reset_form(&reset_link, &Uuid::new_v4().to_string())).await;
+
+    let response = app.post_password_reset(&reset_form(&reset_link, &Uuid::new_v4().to_string())).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(reqwest::get(reset_link).await.unwrap().status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn using_a_reset_link_invalidates_the_older_ones() {
+    let app = spawn_app().await;
+    give_test_user_an_email(&app).await;
+    let first_link = request_reset_link(&app).await;
+    let second_link = request_reset_link(&app).await;
+
+    app.post_password_reset(&reset_form(&second_link, &Uuid::new_v4().to_string())).await;
+
+    let response = app.post_password_reset(&reset_form(&first_link, &Uuid::new_v4().to_string())).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn a_tampered_reset_link_is_rejected() {
+    let app = spawn_app().await;
+    let reset_link = format!("{}/password-reset/confirm?token={}.9999999999.00", app.address, Uuid::new_v4());
+
+    let response = reqwest::get(reset_link).await.unwrap();
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn the_new_password_must_follow_the_same_rules_as_a_password_change() {
+    let app = spawn_app().await;
+    give_test_user_an_email(&app).await;
+    let reset_link = request_reset_link(&app).await;
+
+    let mut form = reset_form(&reset_link, "a-long-enough-password");
+    form["new_password_check"] = "a-different-password".into();
+    let response = app.post_password_reset(&form).await;
+    assert_eq!(response.status().as_u16(), 303);
+    assert_ne!(response.headers().get("Location").unwrap(), "/login");
+
+    let response = app.post_password_reset(&reset_form(&reset_link, "short")).await;
+    assert_ne!(response.headers().get("Location").unwrap(), "/login");
+
+    let response = app.post_password_reset(&reset_form(&reset_link, "password1234")).await;
+    assert_ne!(response.headers().get("Location").unwrap(), "/login");
+
+    // The link has not been used up.
+    assert_eq!(reqwest::get(reset_link).await.unwrap().status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn unknown_emails_get_the_same_answer_but_no_email() {
+    let app = spawn_app().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app.post_password_reset_request("nobody@email.com").await;
+
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(response.headers().get("Location").unwrap(), "/login");
+    let html_page = app.get_login().await.text().await.unwrap();
+    assert!(html_page.contains("If there is an account with that email"));
+}
+
+#[tokio::test]
+async fn resetting_the_password_logs_the_user_out_everywhere() {
+    let app = spawn_app().await;
+    give_test_user_an_email(&app).await;
+    let other_device = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .cookie_store(true)
+        .build()
+        .unwrap();
+    other_device
+        .post(format!("{}/login", &app.address))
+        .form(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .send()
+        .await
+        .unwrap();
+    let dashboard = format!("{}/admin/dashboard", &app.address);
+    assert_eq!(other_device.get(&dashboard).send().await.unwrap().status().as_u16(), 200);
+
+    let reset_link = request_reset_link(&app).await;
+    app.post_password_reset(&reset_form(&reset_link, &Uuid::new_v4().to_string())).await;
+
+    let response = other_device.get(&dashboard).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(response.headers().get("Location").unwrap(), "/login");
+}
+
+#[tokio::test]
+async fn reset_emails_are_rate_limited_per_email() {
+    let app = spawn_app_with_configuration(|config| {
+        config.password_reset_protection.email_rate_limit.max_requests = 2;
+    }).await;
+    give_test_user_an_email(&app).await;
+    request_reset_link(&app).await;
+    request_reset_link(&app).await;
+
+    let response = app.post_password_reset_request(&EMAIL.to_uppercase()).await;
+
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(response.headers().get("Location").unwrap(), "/login");
+    let html_page = app.get_login().await.text().await.unwrap();
+    assert!(html_page.contains("If there is an account with that email"));
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    assert_eq!(app.email_server.received_requests().await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn reset_emails_are_rate_limited_per_ip() {
+    let app = spawn_app_with_configuration(|config| {
+        config.password_reset_protection.ip_rate_limit.max_requests = 2;
+    }).await;
+    give_test_user_an_email(&app).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    app.post_password_reset_request("nobody@email.com").await;
+    app.post_password_reset_request("nobody-else@email.com").await;
+    let response = app.post_password_reset_request(EMAIL).await;
+
+    assert_eq!(response.status().as_u16(), 303);
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+}
@@ -0,0 +1,160 @@
+use crate::helpers::{spawn_app, TestApp};
+use wiremock::{Mock, ResponseTemplate};
+use wiremock::matchers::{path, method};
+use zero2prod::configuration::{CleanupAction, PendingSubscriptionCleanupSettings};
+use zero2prod::pending_subscription_cleanup::{clean_up_pending_subscriptions, CleanupReport};
+
+fn settings(action: CleanupAction) -> PendingSubscriptionCleanupSettings {
+    PendingSubscriptionCleanupSettings {
+        retention_hours: 24,
+        interval_seconds: 3600,
+        action,
+    }
+}
+
+// Dione signed up two days ago and never confirmed, Titan signed up just now.
+async fn create_pending_subscribers(app: &TestApp) {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriptions("name=Dione&email=dione%40email.com".into()).await;
+    app.post_subscriptions("name=Titan&email=titan%40email.com".into()).await;
+    sqlx::query!(
+        "UPDATE subscriptions SET subscribed_at = now() - interval '2 days' WHERE email = 'dione@email.com'"
+    )
+        .execute(&app.connection)
+        .await
+        .unwrap();
+}
+
+async fn remaining_emails(app: &TestApp) -> Vec<String> {
+    sqlx::query!("SELECT email FROM subscriptions ORDER BY email")
+        .fetch_all(&app.connection)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|row| row.email)
+        .collect()
+}
+
+#[tokio::test]
+async fn stale_pending_subscriptions_and_their_tokens_are_deleted() {
+    let app = spawn_app().await;
+    create_pending_subscribers(&app).await;
+
+    let report = clean_up_pending_subscriptions(&app.connection, &settings(CleanupAction::Delete))
+        .await
+        .unwrap();
+
+    assert_eq!(report, CleanupReport { subscriptions: 1, tokens: 1 });
+    assert_eq!(remaining_emails(&app).await, vec!["titan@email.com"]);
+    let archived = sqlx::query!("SELECT COUNT(*) AS count FROM archived_pending_subscriptions")
+        .fetch_one(&app.connection)
+        .await
+        .unwrap();
+    assert_eq!(archived.count, Some(0));
+}
+
+#[tokio::test]
+async fn stale_pending_subscriptions_can_be_archived() {
+    let app = spawn_app().await;
+    create_pending_subscribers(&app).await;
+
+    clean_up_pending_subscriptions(&app.connection, &settings(CleanupAction::Archive))
+        .await
+        .unwrap();
+
+    assert_eq!(remaining_emails(&app).await, vec!["titan@email.com"]);
+    let archived = sqlx::query!("SELECT email FROM archived_pending_subscriptions")
+        .fetch_one(&app.connection)
+        .await
+        .unwrap();
+    assert_eq!(archived.email, "dione@email.com");
+}
+
+#[tokio::test]
+async fn confirmed_subscribers_are_never_cleaned_up() {
+    let app = spawn_app().await;
+    create_pending_subscribers(&app).await;
+    sqlx::query!("UPDATE subscriptions SET status = 'confirmed'")
+        .execute(&app.connection)
+        .await
+        .unwrap();
+
+    let report = clean_up_pending_subscriptions(&app.connection, &settings(CleanupAction::Delete))
+        .await
+        .unwrap();
+
+    assert_eq!(report.subscriptions, 0);
+    assert_eq!(remaining_emails(&app).await.len(), 2);
+}
+
+#[tokio::test]
+async fn a_cleaned_up_email_can_subscribe_again() {
+    let app = spawn_app().await;
+    create_pending_subscribers(&app).await;
+    clean_up_pending_subscriptions(&app.connection, &settings(CleanupAction::Archive))
+        .await
+        .unwrap();
+
+    let response = app.post_subscriptions("name=Dione&email=dione%40email.com".into()).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(remaining_emails(&app).await.len(), 2);
+}
+
+#[tokio::test]
+async fn a_returning_subscriber_gets_the_full_time_to_confirm() {
+    let app = spawn_app().await;
+    create_pending_subscribers(&app).await;
+    sqlx::query!(
+        "UPDATE subscriptions SET status = 'unsubscribed', subscribed_at = now() - interval '30 days' WHERE email = 'dione@email.com'"
+    )
+        .execute(&app.connection)
+        .await
+        .unwrap();
+
+    let response = app.post_subscriptions("name=Dione&email=dione%40email.com".into()).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let report = clean_up_pending_subscriptions(&app.connection, &settings(CleanupAction::Archive))
+        .await
+        .unwrap();
+
+    assert_eq!(report.subscriptions, 0);
+    assert_eq!(remaining_emails(&app).await, vec!["dione@email.com", "titan@email.com"]);
+}
+
+#[tokio::test]
+async fn pending_subscribers_that_were_confirmed_before_are_cleaned_up_too() {
+    let app = spawn_app().await;
+    create_pending_subscribers(&app).await;
+    let dione = sqlx::query!("SELECT id FROM subscriptions WHERE email = 'dione@email.com'")
+        .fetch_one(&app.connection)
+        .await
+        .unwrap()
+        .id;
+    // Left over from before they unsubscribed and came back.
+    sqlx::query!(
+        "INSERT INTO newsletter_deliveries (subscriber_id, title, delivered_at) VALUES ($1, 'Issue 1', now())",
+        dione
+    )
+        .execute(&app.connection)
+        .await
+        .unwrap();
+    sqlx::query!(
+        "INSERT INTO welcome_sequence_enrollments (subscriber_id, enrolled_at) VALUES ($1, now())",
+        dione
+    )
+        .execute(&app.connection)
+        .await
+        .unwrap();
+
+    let report = clean_up_pending_subscriptions(&app.connection, &settings(CleanupAction::Delete))
+        .await
+        .unwrap();
+
+    assert_eq!(report, CleanupReport { subscriptions: 1, tokens: 1 });
+    assert_eq!(remaining_emails(&app).await, vec!["titan@email.com"]);
+}
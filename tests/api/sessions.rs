@@ -0,0 +1,157 @@
+use uuid::Uuid;
+
+use crate::helpers::{spawn_app, TestApp};
+
+// Logs the test user in with a client of its own, as if from another browser.
+async fn login_on_another_device(app: &TestApp) -> reqwest::Client {
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .cookie_store(true)
+        .user_agent("Another device")
+        .build()
+        .unwrap();
+    let response = client
+        .post(format!("{}/login", &app.address))
+        .form(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.headers().get("Location").unwrap(), "/admin/dashboard");
+    client
+}
+
+async fn is_logged_in(app: &TestApp, client: &reqwest::Client) -> bool {
+    let response = client
+        .get(format!("{}/admin/dashboard", &app.address))
+        .send()
+        .await
+        .unwrap();
+    response.status().as_u16() == 200
+}
+
+async fn session_ids(app: &TestApp) -> Vec<Uuid> {
+    sqlx::query!(
+        "SELECT id FROM user_sessions WHERE user_id = $1 AND revoked_at IS NULL ORDER BY created_at",
+        app.test_user.user_id
+    )
+        .fetch_all(&app.connection)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|row| row.id)
+        .collect()
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_your_sessions() {
+    let app = spawn_app().await;
+
+    let response = app.get_sessions().await;
+
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(response.headers().get("Location").unwrap(), "/login");
+}
+
+#[tokio::test]
+async fn every_login_is_listed_with_its_device() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+    login_on_another_device(&app).await;
+
+    let html_page = app.get_sessions().await.text().await.unwrap();
+
+    assert!(html_page.contains("Another device"));
+    assert!(html_page.contains("127.0.0.1"));
+    assert!(html_page.contains("This session"));
+    assert_eq!(session_ids(&app).await.len(), 2);
+}
+
+#[tokio::test]
+async fn another_session_can_be_revoked() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+    let other_device = login_on_another_device(&app).await;
+    let other_session = session_ids(&app).await[1];
+
+    let response = app.post_revoke_session(other_session).await;
+    assert_eq!(response.headers().get("Location").unwrap(), "/admin/sessions");
+    let html_page = app.get_sessions().await.text().await.unwrap();
+    assert!(html_page.contains("<p><i>The session has been logged out.</i></p>"));
+    assert!(!html_page.contains("Another device"));
+
+    assert!(!is_logged_in(&app, &other_device).await);
+    assert_eq!(app.get_admin_dashboard().await.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn the_current_session_cannot_be_revoked_from_the_list() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+    let current_session = session_ids(&app).await[0];
+
+    app.post_revoke_session(current_session).await;
+
+    let html_page = app.get_sessions().await.text().await.unwrap();
+    assert!(html_page.contains("The session does not exist or has already ended."));
+}
+
+#[tokio::test]
+async fn sessions_of_other_users_cannot_be_revoked() {
+    let app = spawn_app().await;
+    login_on_another_device(&app).await;
+    let test_user_session = session_ids(&app).await[0];
+    let viewer = app.add_user("viewer").await;
+    app.login_as(&viewer).await;
+
+    app.post_revoke_session(test_user_session).await;
+
+    assert_eq!(session_ids(&app).await, vec![test_user_session]);
+}
+
+#[tokio::test]
+async fn all_other_sessions_can_be_revoked_at_once() {
+    let app = spawn_app().await;
+    let first_device = login_on_another_device(&app).await;
+    let second_device = login_on_another_device(&app).await;
+    app.login_with_test_user().await;
+
+    let response = app.post_revoke_other_sessions().await;
+    assert_eq!(response.headers().get("Location").unwrap(), "/admin/sessions");
+
+    assert!(!is_logged_in(&app, &first_device).await);
+    assert!(!is_logged_in(&app, &second_device).await);
+    assert_eq!(app.get_admin_dashboard().await.status().as_u16(), 200);
+    assert_eq!(session_ids(&app).await.len(), 1);
+}
+
+#[tokio::test]
+async fn changing_the_password_logs_out_other_sessions() {
+    let app = spawn_app().await;
+    let other_device = login_on_another_device(&app).await;
+    app.login_with_test_user().await;
+    let new_password = Uuid::new_v4().to_string();
+
+    app.post_change_password(&serde_json::json!({
+        "current_password": &app.test_user.password,
+        "new_password": &new_password,
+        "new_password_check": &new_password,
+    })).await;
+
+    let html_page = app.get_change_password().await.text().await.unwrap();
+    assert!(html_page.contains("<p><i>Your other sessions have been logged out.</i></p>"));
+    assert!(!is_logged_in(&app, &other_device).await);
+    assert_eq!(app.get_admin_dashboard().await.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn logging_out_ends_the_listed_session() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+
+    app.post_logout().await;
+
+    assert!(session_ids(&app).await.is_empty());
+}
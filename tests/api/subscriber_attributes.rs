@@ -0,0 +1,167 @@
+use crate::helpers::{spawn_app, TestApp};
+use wiremock::{Mock, ResponseTemplate};
+use wiremock::matchers::{any, path, method};
+
+async fn define_attribute(app: &TestApp, key: &str, value_type: &str) {
+    let response = app.post_attributes(&serde_json::json!({
+        "key": key,
+        "value_type": value_type
+    })).await;
+    assert_eq!(response.status().as_u16(), 303);
+}
+
+// Subscribes with the given body and confirms the subscription.
+async fn create_confirmed_subscriber(app: &TestApp, body: &str) {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+
+    let response = app.post_subscriptions(body.into()).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let email_request = app.email_server.received_requests().await.unwrap().pop().unwrap();
+    let confirmation_links = app.get_confirmation_links(&email_request);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_attributes() {
+    let app = spawn_app().await;
+
+    let response = app.get_attributes().await;
+
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(response.headers().get("Location").unwrap(), "/login");
+}
+
+#[tokio::test]
+async fn defined_attributes_are_listed_on_the_admin_page() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+
+    define_attribute(&app, "company", "string").await;
+
+    let html_page = app.get_attributes().await.text().await.unwrap();
+    assert!(html_page.contains("The company attribute has been added."));
+    assert!(html_page.contains("<td>company</td>"));
+}
+
+#[tokio::test]
+async fn reserved_attribute_keys_are_rejected() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+
+    define_attribute(&app, "email", "string").await;
+
+    let html_page = app.get_attributes().await.text().await.unwrap();
+    assert!(html_page.contains("email is reserved"));
+}
+
+#[tokio::test]
+async fn subscribe_stores_defined_attributes() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+    define_attribute(&app, "company", "string").await;
+    define_attribute(&app, "seats", "number").await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_subscriptions("name=Dione&email=dione%40email.com&company=Acme&seats=12".into())
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let saved = sqlx::query!("SELECT attributes FROM subscriptions")
+        .fetch_one(&app.connection)
+        .await
+        .unwrap();
+    assert_eq!(saved.attributes, serde_json::json!({ "company": "Acme", "seats": 12 }));
+}
+
+#[tokio::test]
+async fn subscribe_rejects_undefined_attributes() {
+    let app = spawn_app().await;
+
+    let response = app.api_client
+        .post(format!("{}/subscriptions", &app.address))
+        .json(&serde_json::json!({
+            "name": "Dione",
+            "email": "dione@email.com",
+            "role": "CTO"
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 400);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["field"], "role");
+}
+
+#[tokio::test]
+async fn newsletters_are_personalised_with_subscriber_attributes() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+    define_attribute(&app, "company", "string").await;
+    create_confirmed_subscriber(&app, "name=Dione&email=dione%40email.com&company=Acme").await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app.post_newsletters(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Hi {{name}} from {{attributes.company}}",
+        "html_content": "<p>Hi {{name}} from {{attributes.company}}</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    })).await;
+    assert_eq!(response.status().as_u16(), 303);
+
+    let email_request = app.email_server.received_requests().await.unwrap().pop().unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert!(body["TextBody"].as_str().unwrap().starts_with("Hi Dione from Acme"));
+    assert!(body["HtmlBody"].as_str().unwrap().starts_with("<p>Hi Dione from Acme</p>"));
+}
+
+#[tokio::test]
+async fn newsletters_can_be_restricted_to_an_attribute_value() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+    define_attribute(&app, "company", "string").await;
+    create_confirmed_subscriber(&app, "name=Dione&email=dione%40email.com&company=Acme").await;
+    create_confirmed_subscriber(&app, "name=Titan&email=titan%40email.com&company=Initech").await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app.post_newsletters(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body in plain text",
+        "html_content": "<p>Newsletter body in HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        "audience_attribute": "company",
+        "audience_value": "Acme"
+    })).await;
+    assert_eq!(response.status().as_u16(), 303);
+
+    let email_request = app.email_server.received_requests().await.unwrap().pop().unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["To"], "dione@email.com");
+}
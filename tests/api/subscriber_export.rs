@@ -0,0 +1,95 @@
+use crate::helpers::{spawn_app, TestApp};
+use wiremock::{Mock, ResponseTemplate};
+use wiremock::matchers::{path, method};
+
+// Two confirmed subscribers, imported, and one pending confirmation.
+async fn create_subscribers(app: &TestApp) {
+    app.post_import(&serde_json::json!({
+        "csv": "email,name\ndione@email.com,Dione\ntitan@email.com,Titan\n",
+        "mode": "confirmed",
+        "consent_note": "Opted in through our previous provider",
+        "action": "import"
+    })).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriptions("name=Rhea&email=rhea%40email.com".into()).await;
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_export_subscribers() {
+    let app = spawn_app().await;
+
+    let response = app.get_export("format=csv").await;
+
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(response.headers().get("Location").unwrap(), "/login");
+}
+
+#[tokio::test]
+async fn subscribers_are_exported_as_csv_by_default() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+    create_subscribers(&app).await;
+
+    let response = app.get_export("").await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers().get("Content-Type").unwrap(), "text/csv; charset=utf-8");
+
+    let csv = response.text().await.unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines[0], "id,email,name,status,subscribed_at,paused_until,attributes");
+    assert_eq!(lines.len(), 4);
+}
+
+#[tokio::test]
+async fn subscribers_can_be_exported_as_json_filtered_by_status() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+    create_subscribers(&app).await;
+
+    let response = app.get_export("format=json&status=pending_confirmation").await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let subscribers: Vec<serde_json::Value> = response.json().await.unwrap();
+    assert_eq!(subscribers.len(), 1);
+    assert_eq!(subscribers[0]["email"], "rhea@email.com");
+}
+
+#[tokio::test]
+async fn subscribers_can_be_filtered_by_sign_up_date() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+    create_subscribers(&app).await;
+    let today = chrono::Utc::now().date().naive_utc();
+
+    let subscribers: Vec<serde_json::Value> = app
+        .get_export(&format!("format=json&subscribed_from={}&subscribed_to={}", today, today))
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(subscribers.len(), 3);
+
+    let subscribers: Vec<serde_json::Value> = app
+        .get_export(&format!("format=json&subscribed_to={}", today.pred()))
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert!(subscribers.is_empty());
+}
+
+#[tokio::test]
+async fn invalid_filters_are_rejected_with_400() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+
+    for query in ["status=deleted", "subscribed_from=yesterday", "format=xml"] {
+        let response = app.get_export(query).await;
+        assert_eq!(response.status().as_u16(), 400, "The export did not fail for {}", query);
+    }
+}
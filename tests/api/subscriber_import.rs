@@ -0,0 +1,233 @@
+use crate::helpers::spawn_app;
+use wiremock::{Mock, ResponseTemplate};
+use wiremock::matchers::{any, path, method};
+
+const CSV: &str = "email,name\ndione@email.com,Dione\nnot-an-email,Titan\ntitan@email.com,Titan\n";
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_import_subscribers() {
+    let app = spawn_app().await;
+
+    let response = app.post_import(&serde_json::json!({
+        "csv": CSV,
+        "mode": "send_confirmation",
+        "action": "import"
+    })).await;
+
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(response.headers().get("Location").unwrap(), "/login");
+}
+
+#[tokio::test]
+async fn a_preview_reports_each_row_without_saving_anything() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app.post_import(&serde_json::json!({
+        "csv": CSV,
+        "mode": "send_confirmation",
+        "action": "preview"
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("Preview: 2 of 3 rows can be imported."));
+    assert!(html_page.contains("not-an-email is not a valid subscriber email"));
+    let saved = sqlx::query!("SELECT COUNT(*) AS count FROM subscriptions")
+        .fetch_one(&app.connection)
+        .await
+        .unwrap();
+    assert_eq!(saved.count, Some(0));
+}
+
+#[tokio::test]
+async fn importing_with_confirmation_sends_an_email_to_each_valid_row() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app.post_import(&serde_json::json!({
+        "csv": CSV,
+        "mode": "send_confirmation",
+        "action": "import"
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.text().await.unwrap().contains("Imported 2 of 3 rows."));
+
+    let email_request = &app.wait_for_emails(2).await[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn imports_larger_than_the_default_form_limit_are_accepted() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+    let mut csv = "email,name\n".to_string();
+    for i in 0..2000 {
+        csv.push_str(&format!("subscriber-{}@email.com,Subscriber {}\n", i, i));
+    }
+    assert!(csv.len() > 16 * 1024);
+
+    let response = app.post_import(&serde_json::json!({
+        "csv": csv,
+        "mode": "confirmed",
+        "consent_note": "Opted in through our previous provider",
+        "action": "import"
+    })).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.text().await.unwrap().contains("Imported 2000 of 2000 rows."));
+    let saved = sqlx::query!("SELECT COUNT(*) AS count FROM subscriptions")
+        .fetch_one(&app.connection)
+        .await
+        .unwrap();
+    assert_eq!(saved.count, Some(2000));
+}
+
+#[tokio::test]
+async fn importing_as_confirmed_requires_a_consent_note() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+
+    let response = app.post_import(&serde_json::json!({
+        "csv": CSV,
+        "mode": "confirmed",
+        "action": "import"
+    })).await;
+
+    assert!(response.text().await.unwrap().contains("Describe how consent was obtained"));
+    let saved = sqlx::query!("SELECT COUNT(*) AS count FROM subscriptions")
+        .fetch_one(&app.connection)
+        .await
+        .unwrap();
+    assert_eq!(saved.count, Some(0));
+}
+
+#[tokio::test]
+async fn importing_as_confirmed_records_the_consent_note_without_sending_emails() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    app.post_import(&serde_json::json!({
+        "csv": CSV,
+        "mode": "confirmed",
+        "consent_note": "Opted in through our previous provider",
+        "action": "import"
+    })).await;
+
+    let saved = sqlx::query!(
+        r#"
+        SELECT s.status, i.consent_note
+        FROM subscriptions s
+        JOIN subscriber_imports i ON i.id = s.import_id
+        WHERE s.email = 'dione@email.com'
+        "#
+    )
+        .fetch_one(&app.connection)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "confirmed");
+    assert_eq!(saved.consent_note.as_deref(), Some("Opted in through our previous provider"));
+}
+
+#[tokio::test]
+async fn existing_subscribers_are_reported_and_left_untouched() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriptions("name=Dione&email=dione%40email.com".into()).await;
+
+    let response = app.post_import(&serde_json::json!({
+        "csv": "email,name\nDIONE@email.com,Someone else\n",
+        "mode": "confirmed",
+        "consent_note": "Opted in through our previous provider",
+        "action": "import"
+    })).await;
+
+    assert!(response.text().await.unwrap().contains("Already subscribed"));
+    let saved = sqlx::query!("SELECT name, status FROM subscriptions")
+        .fetch_one(&app.connection)
+        .await
+        .unwrap();
+    assert_eq!(saved.name, "Dione");
+    assert_eq!(saved.status, "pending_confirmation");
+}
+
+#[tokio::test]
+async fn blocked_email_domains_are_reported_and_not_imported() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+
+    let response = app.post_import(&serde_json::json!({
+        "csv": "email,name\ndione@email.com,Dione\nspam@maildrop.cc,Spam\n",
+        "mode": "confirmed",
+        "consent_note": "Opted in through our previous provider",
+        "action": "import"
+    })).await;
+
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("Imported 1 of 2 rows."));
+    assert!(html_page.contains("Blocked by the email domain policy"));
+    let saved = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_all(&app.connection)
+        .await
+        .unwrap();
+    assert_eq!(saved.len(), 1);
+    assert_eq!(saved[0].email, "dione@email.com");
+}
+
+#[tokio::test]
+async fn subscribers_imported_as_confirmed_start_the_welcome_sequence() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+
+    app.post_import(&serde_json::json!({
+        "csv": CSV,
+        "mode": "confirmed",
+        "consent_note": "Opted in through our previous provider",
+        "action": "import"
+    })).await;
+
+    let enrolled = sqlx::query!(
+        r#"
+        SELECT s.email
+        FROM welcome_sequence_enrollments e
+        JOIN subscriptions s ON s.id = e.subscriber_id
+        ORDER BY s.email
+        "#
+    )
+        .fetch_all(&app.connection)
+        .await
+        .unwrap();
+    let enrolled: Vec<_> = enrolled.into_iter().map(|row| row.email).collect();
+    assert_eq!(enrolled, ["dione@email.com", "titan@email.com"]);
+}
@@ -0,0 +1,288 @@
+use crate::helpers::{spawn_app, spawn_app_with_configuration, TestApp};
+use wiremock::{Mock, ResponseTemplate};
+use wiremock::matchers::{any, path, method};
+
+async fn get_form_token(app: &TestApp) -> String {
+    let html_page = app.get_home().await.text().await.unwrap();
+    let start = html_page.find(r#"name="form_token" value=""#).unwrap() + r#"name="form_token" value=""#.len();
+    let end = start + html_page[start..].find('"').unwrap();
+    html_page[start..end].to_string()
+}
+
+#[tokio::test]
+async fn filling_in_the_honeypot_does_not_create_a_subscriber() {
+    let app = spawn_app().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_subscriptions("name=Dione&email=dione%40email.com&website=spam.com".into())
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_optional(&app.connection)
+        .await
+        .unwrap();
+    assert!(saved.is_none());
+}
+
+#[tokio::test]
+async fn form_submissions_without_a_form_token_are_rejected() {
+    let app = spawn_app_with_configuration(|config| {
+        config.subscription_protection.minimum_form_fill_seconds = 3;
+    }).await;
+
+    let response = app
+        .post_subscriptions("name=Dione&email=dione%40email.com".into())
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn forms_submitted_faster_than_the_minimum_fill_time_are_rejected() {
+    let app = spawn_app_with_configuration(|config| {
+        config.subscription_protection.minimum_form_fill_seconds = 60;
+    }).await;
+    let form_token = get_form_token(&app).await;
+
+    let response = app
+        .post_subscriptions(format!("name=Dione&email=dione%40email.com&form_token={}", form_token))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn forms_submitted_after_the_minimum_fill_time_are_accepted() {
+    let app = spawn_app_with_configuration(|config| {
+        config.subscription_protection.minimum_form_fill_seconds = 1;
+    }).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let form_token = get_form_token(&app).await;
+    tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+
+    let response = app
+        .post_subscriptions(format!("name=Dione&email=dione%40email.com&form_token={}", form_token))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn a_form_token_can_only_be_used_for_one_subscription() {
+    let app = spawn_app_with_configuration(|config| {
+        config.subscription_protection.minimum_form_fill_seconds = 1;
+    }).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let form_token = get_form_token(&app).await;
+    tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+
+    let response = app
+        .post_subscriptions(format!("name=Dione&email=dione%40email.com&form_token={}", form_token))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_subscriptions(format!("name=Ursula&email=ursula%40email.com&form_token={}", form_token))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn a_form_token_is_not_spent_by_an_invalid_submission() {
+    let app = spawn_app_with_configuration(|config| {
+        config.subscription_protection.minimum_form_fill_seconds = 1;
+    }).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let form_token = get_form_token(&app).await;
+    tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+
+    let response = app
+        .post_subscriptions(format!("name=Dione&email=not-an-email&form_token={}", form_token))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app
+        .post_subscriptions(format!("name=Dione&email=dione%40email.com&form_token={}", form_token))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn subscriptions_over_the_ip_rate_limit_are_rejected_with_429() {
+    let app = spawn_app_with_configuration(|config| {
+        config.subscription_protection.ip_rate_limit.max_requests = 1;
+    }).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_subscriptions("name=Dione&email=dione%40email.com".into())
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_subscriptions("name=Ursula&email=ursula%40another.com".into())
+        .await;
+    assert_eq!(response.status().as_u16(), 429);
+}
+
+async fn post_subscriptions_json(app: &TestApp, body: serde_json::Value) -> reqwest::Response {
+    app.api_client
+        .post(format!("{}/subscriptions", &app.address))
+        .json(&body)
+        .send()
+        .await
+        .expect("Failed to POST /subscriptions endpoint")
+}
+
+#[tokio::test]
+async fn json_submissions_without_a_form_token_are_rejected() {
+    let app = spawn_app_with_configuration(|config| {
+        config.subscription_protection.minimum_form_fill_seconds = 3;
+    }).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = post_subscriptions_json(&app, serde_json::json!({
+        "name": "Dione",
+        "email": "dione@email.com",
+    })).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn json_submissions_need_the_minimum_fill_time_too() {
+    let app = spawn_app_with_configuration(|config| {
+        config.subscription_protection.minimum_form_fill_seconds = 1;
+    }).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let token: serde_json::Value = app.api_client
+        .get(format!("{}/subscriptions/form-token", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let form_token = token["form_token"].as_str().unwrap();
+
+    let response = post_subscriptions_json(&app, serde_json::json!({
+        "name": "Dione",
+        "email": "dione@email.com",
+        "form_token": form_token,
+    })).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+    let response = post_subscriptions_json(&app, serde_json::json!({
+        "name": "Dione",
+        "email": "dione@email.com",
+        "form_token": form_token,
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+async fn post_subscriptions_forwarded_for(app: &TestApp, body: &str, forwarded_for: &str) -> reqwest::Response {
+    app.api_client
+        .post(format!("{}/subscriptions", &app.address))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .header("X-Forwarded-For", forwarded_for)
+        .body(body.to_string())
+        .send()
+        .await
+        .expect("Failed to POST /subscriptions endpoint")
+}
+
+#[tokio::test]
+async fn forwarded_headers_do_not_reset_the_ip_rate_limit() {
+    let app = spawn_app_with_configuration(|config| {
+        config.subscription_protection.ip_rate_limit.max_requests = 1;
+    }).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = post_subscriptions_forwarded_for(&app, "name=Dione&email=dione%40email.com", "198.51.100.1").await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = post_subscriptions_forwarded_for(&app, "name=Ursula&email=ursula%40another.com", "198.51.100.2").await;
+    assert_eq!(response.status().as_u16(), 429);
+}
+
+#[tokio::test]
+async fn subscriptions_over_the_email_domain_rate_limit_are_rejected_with_429() {
+    let app = spawn_app_with_configuration(|config| {
+        config.subscription_protection.email_domain_rate_limit.max_requests = 1;
+    }).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_subscriptions("name=Dione&email=dione%40email.com".into())
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_subscriptions("name=Ursula&email=ursula%40EMAIL.com".into())
+        .await;
+    assert_eq!(response.status().as_u16(), 429);
+
+    let response = app
+        .post_subscriptions("name=Ursula&email=ursula%40another.com".into())
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
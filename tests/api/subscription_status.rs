@@ -0,0 +1,151 @@
+use crate::helpers::{spawn_app, TestApp};
+use wiremock::{Mock, ResponseTemplate};
+use wiremock::matchers::{path, method};
+
+// Subscribes a new subscriber, returning the link from their confirmation email.
+async fn subscribe(app: &TestApp) -> reqwest::Url {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriptions("name=Dione&email=dione%40email.com".into()).await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    app.get_confirmation_links(email_request).html
+}
+
+async fn status_history(app: &TestApp) -> Vec<(Option<String>, String)> {
+    sqlx::query!("SELECT from_status, to_status FROM subscription_status_changes ORDER BY changed_at")
+        .fetch_all(&app.connection)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|row| (row.from_status, row.to_status))
+        .collect()
+}
+
+#[tokio::test]
+async fn every_status_change_is_recorded() {
+    let app = spawn_app().await;
+    let confirmation_link = subscribe(&app).await;
+    reqwest::get(confirmation_link).await.unwrap().error_for_status().unwrap();
+
+    assert_eq!(
+        status_history(&app).await,
+        vec![
+            (None, "pending_confirmation".to_string()),
+            (Some("pending_confirmation".to_string()), "confirmed".to_string()),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn confirming_twice_does_not_record_a_second_change() {
+    let app = spawn_app().await;
+    let confirmation_link = subscribe(&app).await;
+
+    for _ in 0..2 {
+        let response = reqwest::get(confirmation_link.clone()).await.unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    assert_eq!(status_history(&app).await.len(), 2);
+}
+
+#[tokio::test]
+async fn an_old_confirmation_link_cannot_resubscribe_an_unsubscribed_subscriber() {
+    let app = spawn_app().await;
+    let confirmation_link = subscribe(&app).await;
+    let preferences_link = {
+        let email_request = &app.email_server.received_requests().await.unwrap()[0];
+        app.get_email_link(email_request, "/subscriptions/preferences")
+    };
+    let token = preferences_link.query_pairs().next().unwrap().1.into_owned();
+    app.api_client
+        .post(format!("{}/subscriptions/preferences/unsubscribe", &app.address))
+        .form(&serde_json::json!({ "token": token }))
+        .send()
+        .await
+        .unwrap();
+
+    let response = reqwest::get(confirmation_link).await.unwrap();
+
+    assert_eq!(response.status().as_u16(), 400);
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.connection)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "unsubscribed");
+}
+
+#[tokio::test]
+async fn unknown_statuses_are_rejected_by_the_database() {
+    let app = spawn_app().await;
+    subscribe(&app).await;
+
+    let result = sqlx::query!("UPDATE subscriptions SET status = 'deleted'")
+        .execute(&app.connection)
+        .await;
+
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn an_unsubscribed_subscriber_can_sign_up_again() {
+    let app = spawn_app().await;
+    let old_confirmation_link = subscribe(&app).await;
+    reqwest::get(old_confirmation_link.clone()).await.unwrap().error_for_status().unwrap();
+    let preferences_link = {
+        let email_request = &app.email_server.received_requests().await.unwrap()[0];
+        app.get_email_link(email_request, "/subscriptions/preferences")
+    };
+    let token = preferences_link.query_pairs().next().unwrap().1.into_owned();
+    app.api_client
+        .post(format!("{}/subscriptions/preferences/unsubscribe", &app.address))
+        .form(&serde_json::json!({ "token": token }))
+        .send()
+        .await
+        .unwrap();
+
+    let response = app.post_subscriptions("name=Dione%20Again&email=Dione%40email.com".into()).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let saved = sqlx::query!("SELECT name, status FROM subscriptions")
+        .fetch_one(&app.connection)
+        .await
+        .unwrap();
+    assert_eq!(saved.name, "Dione Again");
+    assert_eq!(saved.status, "pending_confirmation");
+    assert_eq!(
+        status_history(&app).await.last().unwrap(),
+        &(Some("unsubscribed".to_string()), "pending_confirmation".to_string())
+    );
+
+    assert_eq!(reqwest::get(old_confirmation_link).await.unwrap().status().as_u16(), 401);
+    let email_request = &app.email_server.received_requests().await.unwrap()[1];
+    let new_confirmation_link = app.get_confirmation_links(email_request).html;
+    reqwest::get(new_confirmation_link).await.unwrap().error_for_status().unwrap();
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.connection)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "confirmed");
+}
+
+#[tokio::test]
+async fn signing_up_again_while_confirmed_changes_nothing_and_sends_nothing() {
+    let app = spawn_app().await;
+    let confirmation_link = subscribe(&app).await;
+    reqwest::get(confirmation_link).await.unwrap().error_for_status().unwrap();
+
+    let response = app.post_subscriptions("name=Someone%20Else&email=dione%40email.com".into()).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(app.email_server.received_requests().await.unwrap().len(), 1);
+    let saved = sqlx::query!("SELECT name, status FROM subscriptions")
+        .fetch_one(&app.connection)
+        .await
+        .unwrap();
+    assert_eq!(saved.name, "Dione");
+    assert_eq!(saved.status, "confirmed");
+}
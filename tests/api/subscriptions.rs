@@ -1,4 +1,4 @@
-use crate::helpers::spawn_app;
+use crate::helpers::{spawn_app, spawn_app_with_configuration};
 use wiremock::{Mock, ResponseTemplate};
 use wiremock::matchers::{path, method};
 
@@ -227,4 +227,160 @@ async fn subscribe_returns_a_400_when_fields_are_present_but_invalid() {
             description
         );
     }
-}
\ No newline at end of file
+}
+#[tokio::test]
+async fn subscribe_accepts_json_bodies() {
+    let app = spawn_app().await;
+
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app.api_client
+        .post(&format!("{}/subscriptions", &app.address))
+        .json(&serde_json::json!({
+            "name": "Dione",
+            "email": "dione@email.com"
+        }))
+        .send()
+        .await
+        .expect("Failed to submit subscription information");
+
+    assert_eq!(200, response.status().as_u16());
+
+    let saved = sqlx::query!("SELECT email, name FROM subscriptions")
+        .fetch_one(&app.connection)
+        .await
+        .expect("Failed to fetch saved subscription");
+
+    assert_eq!(saved.name, "Dione");
+    assert_eq!(saved.email, "dione@email.com");
+}
+
+#[tokio::test]
+async fn subscribe_returns_the_invalid_field_in_a_json_error_body() {
+    let app = spawn_app().await;
+    let test_cases = vec![
+        (serde_json::json!({ "name": "", "email": "dione@email.com" }), "name"),
+        (serde_json::json!({ "name": "Dione", "email": "definitely-not-an-email" }), "email"),
+        (serde_json::json!({ "name": "Dione" }), "email"),
+    ];
+
+    for (body, field) in test_cases {
+        let response = app.api_client
+            .post(&format!("{}/subscriptions", &app.address))
+            .json(&body)
+            .send()
+            .await
+            .expect("Failed to execute request.");
+
+        assert_eq!(400, response.status().as_u16());
+        let error: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(error["error"]["field"], field, "Unexpected error body for payload {}", body);
+        assert!(error["error"]["message"].is_string());
+    }
+}
+
+#[tokio::test]
+async fn subscribe_returns_a_json_error_body_for_malformed_json() {
+    let app = spawn_app().await;
+
+    let response = app.api_client
+        .post(&format!("{}/subscriptions", &app.address))
+        .header("Content-Type", "application/json")
+        .body("{ not json")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(400, response.status().as_u16());
+    let error: serde_json::Value = response.json().await.unwrap();
+    assert!(error["error"]["message"].is_string());
+}
+
+#[tokio::test]
+async fn subscribe_rejects_blocked_email_domains() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_subscriptions("name=Dione&email=dione%40mailinator.com".into())
+        .await;
+
+    assert_eq!(400, response.status().as_u16());
+    let error: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(error["error"]["field"], "email");
+}
+
+#[tokio::test]
+async fn the_email_domain_blocklist_is_reloaded_when_the_file_changes() {
+    let blocklist_path = std::env::temp_dir().join(format!("{}.txt", uuid::Uuid::new_v4()));
+    std::fs::write(&blocklist_path, "mailinator.com\n").unwrap();
+    let app = spawn_app_with_configuration(|config| {
+        config.email_domain_policy.blocklist_path = blocklist_path.to_str().unwrap().to_string();
+    }).await;
+
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_subscriptions("name=Dione&email=dione%40throwaway.com".into())
+        .await;
+    assert_eq!(200, response.status().as_u16());
+
+    std::fs::write(&blocklist_path, "mailinator.com\nthrowaway.com\n").unwrap();
+
+    let response = app
+        .post_subscriptions("name=Ursula&email=ursula%40throwaway.com".into())
+        .await;
+    assert_eq!(400, response.status().as_u16());
+
+    std::fs::remove_file(&blocklist_path).unwrap();
+}
+
+#[tokio::test]
+async fn allowlisted_domains_are_accepted_even_when_blocked() {
+    let allowlist_path = std::env::temp_dir().join(format!("{}.txt", uuid::Uuid::new_v4()));
+    std::fs::write(&allowlist_path, "mailinator.com\n").unwrap();
+    let app = spawn_app_with_configuration(|config| {
+        config.email_domain_policy.allowlist_path = Some(allowlist_path.to_str().unwrap().to_string());
+    }).await;
+
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_subscriptions("name=Dione&email=dione%40mailinator.com".into())
+        .await;
+    assert_eq!(200, response.status().as_u16());
+
+    std::fs::remove_file(&allowlist_path).unwrap();
+}
+
+#[tokio::test]
+async fn emails_differing_only_by_case_are_the_same_subscriber() {
+    let app = spawn_app().await;
+
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscriptions("name=Dione&email=Dione%40Email.COM".into()).await;
+    let response = app.post_subscriptions("name=Dione&email=dione%40email.com".into()).await;
+    assert!(!response.status().is_success());
+
+    let saved = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_all(&app.connection)
+        .await
+        .expect("Failed to fetch saved subscriptions");
+    assert_eq!(saved.len(), 1);
+    assert_eq!(saved[0].email, "Dione@email.com");
+}
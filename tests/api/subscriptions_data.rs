@@ -0,0 +1,191 @@
+use crate::helpers::{spawn_app, spawn_app_with_configuration, TestApp};
+use wiremock::{Mock, ResponseTemplate};
+use wiremock::matchers::{any, path, method};
+
+async fn create_subscriber_and_request_data_links(app: &TestApp) -> (reqwest::Url, reqwest::Url) {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscriptions("name=Dione&email=dione%40email.com".into()).await;
+    let response = app.post_subscriber_data_request(&serde_json::json!({
+        "email": "dione@email.com"
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let email_request = &app.wait_for_emails(2).await[1];
+    (
+        app.get_email_link(email_request, "/subscriptions/data/export"),
+        app.get_email_link(email_request, "/subscriptions/data/erase"),
+    )
+}
+
+#[tokio::test]
+async fn requesting_data_for_an_unknown_email_does_not_send_an_email() {
+    let app = spawn_app().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app.post_subscriber_data_request(&serde_json::json!({
+        "email": "nobody@email.com"
+    })).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+}
+
+#[tokio::test]
+async fn failing_to_send_the_data_links_is_not_reported_to_the_requester() {
+    let app = spawn_app().await;
+    app.post_subscriptions("name=Dione&email=dione%40email.com".into()).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&app.email_server)
+        .await;
+
+    let response = app.post_subscriber_data_request(&serde_json::json!({
+        "email": "dione@email.com"
+    })).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn data_links_are_rate_limited_per_email() {
+    let app = spawn_app_with_configuration(|config| {
+        config.subscriber_data_protection.email_rate_limit.max_requests = 1;
+    }).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriptions("name=Dione&email=dione%40email.com".into()).await;
+    let body = serde_json::json!({ "email": "dione@email.com" });
+    app.post_subscriber_data_request(&body).await;
+    app.wait_for_emails(2).await;
+
+    let response = app.post_subscriber_data_request(&serde_json::json!({
+        "email": "DIONE@email.com"
+    })).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    assert_eq!(app.email_server.received_requests().await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn data_links_are_rate_limited_per_ip() {
+    let app = spawn_app_with_configuration(|config| {
+        config.subscriber_data_protection.ip_rate_limit.max_requests = 2;
+    }).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriptions("name=Dione&email=dione%40email.com".into()).await;
+    app.wait_for_emails(1).await;
+
+    for email in ["nobody@email.com", "nobody-else@email.com", "dione@email.com"] {
+        let response = app.post_subscriber_data_request(&serde_json::json!({ "email": email })).await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    assert_eq!(app.email_server.received_requests().await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn the_export_link_returns_the_subscriber_data_as_json() {
+    let app = spawn_app().await;
+    let (export_link, _) = create_subscriber_and_request_data_links(&app).await;
+
+    let response = reqwest::get(export_link).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let export: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(export["subscription"]["email"], "dione@email.com");
+    assert_eq!(export["subscription"]["name"], "Dione");
+    assert_eq!(export["subscription_tokens"].as_array().unwrap().len(), 1);
+    assert!(export["deliveries"].as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn a_tampered_export_link_is_rejected_with_401() {
+    let app = spawn_app().await;
+    let (mut export_link, _) = create_subscriber_and_request_data_links(&app).await;
+    let token = export_link.query_pairs().next().unwrap().1.into_owned();
+    export_link.set_query(Some(&format!("token={}0", token)));
+
+    let response = reqwest::get(export_link).await.unwrap();
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn the_export_link_cannot_be_used_to_erase_data() {
+    let app = spawn_app().await;
+    let (export_link, _) = create_subscriber_and_request_data_links(&app).await;
+    let token = export_link.query_pairs().next().unwrap().1.into_owned();
+
+    let response = app.api_client
+        .post(format!("{}/subscriptions/data/erase", &app.address))
+        .form(&serde_json::json!({ "token": token }))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn confirming_erasure_removes_the_subscriber_and_their_tokens() {
+    let app = spawn_app().await;
+    let (_, erasure_link) = create_subscriber_and_request_data_links(&app).await;
+    sqlx::query!(
+        r#"
+        INSERT INTO archived_pending_subscriptions (id, email, name, subscribed_at, attributes, archived_at)
+        VALUES ($1, 'Dione@email.com', 'Dione', now(), '{}', now())
+        "#,
+        uuid::Uuid::new_v4()
+    )
+        .execute(&app.connection)
+        .await
+        .unwrap();
+
+    let confirmation_page = reqwest::get(erasure_link.clone()).await.unwrap();
+    assert_eq!(confirmation_page.status().as_u16(), 200);
+
+    let token = erasure_link.query_pairs().next().unwrap().1.into_owned();
+    let response = app.api_client
+        .post(format!("{}/subscriptions/data/erase", &app.address))
+        .form(&serde_json::json!({ "token": token }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let subscriptions = sqlx::query!("SELECT COUNT(*) AS count FROM subscriptions")
+        .fetch_one(&app.connection)
+        .await
+        .unwrap();
+    assert_eq!(subscriptions.count, Some(0));
+    let tokens = sqlx::query!("SELECT COUNT(*) AS count FROM subscription_tokens")
+        .fetch_one(&app.connection)
+        .await
+        .unwrap();
+    assert_eq!(tokens.count, Some(0));
+    let archived = sqlx::query!("SELECT COUNT(*) AS count FROM archived_pending_subscriptions")
+        .fetch_one(&app.connection)
+        .await
+        .unwrap();
+    assert_eq!(archived.count, Some(0));
+}
@@ -0,0 +1,174 @@
+use crate::helpers::{spawn_app, TestApp};
+use wiremock::{Mock, ResponseTemplate};
+use wiremock::matchers::{any, path, method};
+
+// Subscribes and confirms a new subscriber, returning the preferences link from
+// their confirmation email.
+async fn create_confirmed_subscriber(app: &TestApp) -> reqwest::Url {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+
+    app.post_subscriptions("name=Dione&email=dione%40email.com".into()).await;
+
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    app.get_email_link(email_request, "/subscriptions/preferences")
+}
+
+fn token(preferences_link: &reqwest::Url) -> String {
+    preferences_link.query_pairs().next().unwrap().1.into_owned()
+}
+
+async fn publish_newsletter(app: &TestApp) {
+    app.login_with_test_user().await;
+    let response = app.post_newsletters(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body in plain text",
+        "html_content": "<p>Newsletter body in HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    })).await;
+    assert_eq!(response.status().as_u16(), 303);
+}
+
+#[tokio::test]
+async fn the_preferences_page_is_reachable_from_the_confirmation_email() {
+    let app = spawn_app().await;
+    let preferences_link = create_confirmed_subscriber(&app).await;
+
+    let response = app.api_client.get(preferences_link).send().await.unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.text().await.unwrap().contains(r#"value="Dione""#));
+}
+
+#[tokio::test]
+async fn newsletter_issues_contain_a_preferences_link() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    publish_newsletter(&app).await;
+
+    let email_request = app.email_server.received_requests().await.unwrap().pop().unwrap();
+    let preferences_link = app.get_email_link(&email_request, "/subscriptions/preferences");
+    let response = reqwest::get(preferences_link).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn an_invalid_preferences_link_is_rejected_with_401() {
+    let app = spawn_app().await;
+
+    let response = app.api_client
+        .get(format!("{}/subscriptions/preferences?token=not-a-token", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn subscribers_can_update_their_name() {
+    let app = spawn_app().await;
+    let preferences_link = create_confirmed_subscriber(&app).await;
+
+    let response = app.api_client
+        .post(format!("{}/subscriptions/preferences/name", &app.address))
+        .form(&serde_json::json!({ "token": token(&preferences_link), "name": "Ursula" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 303);
+
+    let saved = sqlx::query!("SELECT name FROM subscriptions")
+        .fetch_one(&app.connection)
+        .await
+        .unwrap();
+    assert_eq!(saved.name, "Ursula");
+}
+
+#[tokio::test]
+async fn invalid_names_are_not_saved() {
+    let app = spawn_app().await;
+    let preferences_link = create_confirmed_subscriber(&app).await;
+
+    app.api_client
+        .post(format!("{}/subscriptions/preferences/name", &app.address))
+        .form(&serde_json::json!({ "token": token(&preferences_link), "name": "<script>" }))
+        .send()
+        .await
+        .unwrap();
+
+    let html_page = app.api_client.get(preferences_link).send().await.unwrap().text().await.unwrap();
+    assert!(html_page.contains("is not a valid name"));
+    let saved = sqlx::query!("SELECT name FROM subscriptions")
+        .fetch_one(&app.connection)
+        .await
+        .unwrap();
+    assert_eq!(saved.name, "Dione");
+}
+
+#[tokio::test]
+async fn paused_subscribers_do_not_receive_newsletters() {
+    let app = spawn_app().await;
+    let preferences_link = create_confirmed_subscriber(&app).await;
+
+    app.api_client
+        .post(format!("{}/subscriptions/preferences/pause", &app.address))
+        .form(&serde_json::json!({ "token": token(&preferences_link), "days": 30 }))
+        .send()
+        .await
+        .unwrap();
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    publish_newsletter(&app).await;
+}
+
+#[tokio::test]
+async fn unsubscribed_subscribers_do_not_receive_newsletters() {
+    let app = spawn_app().await;
+    let preferences_link = create_confirmed_subscriber(&app).await;
+
+    let response = app.api_client
+        .post(format!("{}/subscriptions/preferences/unsubscribe", &app.address))
+        .form(&serde_json::json!({ "token": token(&preferences_link) }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 303);
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    publish_newsletter(&app).await;
+
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.connection)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "unsubscribed");
+}
@@ -0,0 +1,177 @@
+use chrono::Utc;
+use uuid::Uuid;
+use zero2prod::authentication::totp;
+
+use crate::helpers::{spawn_app, spawn_app_with_configuration, TestApp};
+
+async fn stored_secret(app: &TestApp, user_id: Uuid) -> String {
+    sqlx::query!("SELECT secret FROM user_totp WHERE user_id = $1", user_id)
+        .fetch_one(&app.connection)
+        .await
+        .expect("Failed to retrieve the two-factor secret")
+        .secret
+}
+
+// The code for the next time step, which has not been used by an earlier request
+// in the same test.
+fn next_code(secret: &str) -> String {
+    totp::code_at(secret, Utc::now().timestamp() + 30).unwrap()
+}
+
+// Enrolls the test user and returns their secret and recovery codes.
+async fn enable_two_factor(app: &TestApp) -> (String, Vec<String>) {
+    app.login_with_test_user().await;
+    app.post_two_factor_action("enroll", &serde_json::json!({})).await;
+    let secret = stored_secret(app, app.test_user.user_id).await;
+    let code = totp::code_at(&secret, Utc::now().timestamp()).unwrap();
+
+    let response = app.post_two_factor_action("confirm", &serde_json::json!({ "code": code })).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let html = response.text().await.unwrap();
+    let recovery_codes = html
+        .split("<li><code>")
+        .skip(1)
+        .map(|item| item.split("</code>").next().unwrap().to_string())
+        .collect();
+    app.post_logout().await;
+    (secret, recovery_codes)
+}
+
+#[tokio::test]
+async fn enrolling_shows_a_qr_code_and_then_ten_recovery_codes() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+
+    app.post_two_factor_action("enroll", &serde_json::json!({})).await;
+    let html = app.get_two_factor().await.text().await.unwrap();
+    assert!(html.contains("<svg"));
+    assert!(html.contains(&stored_secret(&app, app.test_user.user_id).await));
+    app.post_logout().await;
+
+    let (_, recovery_codes) = enable_two_factor(&app).await;
+    assert_eq!(recovery_codes.len(), 10);
+}
+
+#[tokio::test]
+async fn a_wrong_code_does_not_turn_on_two_factor_authentication() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+    app.post_two_factor_action("enroll", &serde_json::json!({})).await;
+
+    let response = app.post_two_factor_action("confirm", &serde_json::json!({ "code": "000000x" })).await;
+    assert_eq!(response.status().as_u16(), 303);
+    let html = app.get_two_factor().await.text().await.unwrap();
+    assert!(html.contains("The code is incorrect"));
+    app.post_logout().await;
+
+    let response = app.login_with_test_user().await;
+    assert_eq!(response.headers().get("Location").unwrap(), "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn users_with_two_factor_authentication_need_a_code_to_log_in() {
+    let app = spawn_app().await;
+    let (secret, _) = enable_two_factor(&app).await;
+
+    let response = app.login_with_test_user().await;
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(response.headers().get("Location").unwrap(), "/login/two-factor");
+    // The password alone does not log the user in.
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(response.headers().get("Location").unwrap(), "/login");
+
+    let response = app.post_login_two_factor(&next_code(&secret)).await;
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(response.headers().get("Location").unwrap(), "/admin/dashboard");
+    assert_eq!(app.get_admin_dashboard().await.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn a_wrong_code_is_rejected_at_login() {
+    let app = spawn_app().await;
+    enable_two_factor(&app).await;
+    app.login_with_test_user().await;
+
+    let response = app.post_login_two_factor("000000").await;
+    assert_eq!(response.headers().get("Location").unwrap(), "/login/two-factor");
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(response.headers().get("Location").unwrap(), "/login");
+}
+
+#[tokio::test]
+async fn a_code_cannot_be_used_twice() {
+    let app = spawn_app().await;
+    let (secret, _) = enable_two_factor(&app).await;
+    let code = next_code(&secret);
+    app.login_with_test_user().await;
+    app.post_login_two_factor(&code).await;
+    app.post_logout().await;
+
+    app.login_with_test_user().await;
+    let response = app.post_login_two_factor(&code).await;
+    assert_eq!(response.headers().get("Location").unwrap(), "/login/two-factor");
+}
+
+#[tokio::test]
+async fn a_recovery_code_works_only_once() {
+    let app = spawn_app().await;
+    let (_, recovery_codes) = enable_two_factor(&app).await;
+
+    app.login_with_test_user().await;
+    let response = app.post_login_two_factor(&recovery_codes[0].to_uppercase()).await;
+    assert_eq!(response.headers().get("Location").unwrap(), "/admin/dashboard");
+    app.post_logout().await;
+
+    app.login_with_test_user().await;
+    let response = app.post_login_two_factor(&recovery_codes[0]).await;
+    assert_eq!(response.headers().get("Location").unwrap(), "/login/two-factor");
+}
+
+#[tokio::test]
+async fn the_second_step_needs_a_password_first() {
+    let app = spawn_app().await;
+    let (secret, _) = enable_two_factor(&app).await;
+
+    let response = app.post_login_two_factor(&next_code(&secret)).await;
+    assert_eq!(response.headers().get("Location").unwrap(), "/login");
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(response.headers().get("Location").unwrap(), "/login");
+}
+
+#[tokio::test]
+async fn turning_off_two_factor_authentication_needs_a_code() {
+    let app = spawn_app().await;
+    let (secret, recovery_codes) = enable_two_factor(&app).await;
+    app.login_with_test_user().await;
+    app.post_login_two_factor(&recovery_codes[0]).await;
+
+    app.post_two_factor_action("disable", &serde_json::json!({ "code": "000000" })).await;
+    assert!(app.get_two_factor().await.text().await.unwrap().contains("is on"));
+
+    app.post_two_factor_action("disable", &serde_json::json!({ "code": next_code(&secret) })).await;
+    assert!(app.get_two_factor().await.text().await.unwrap().contains("is off"));
+    app.post_logout().await;
+
+    let response = app.login_with_test_user().await;
+    assert_eq!(response.headers().get("Location").unwrap(), "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn guessing_the_code_to_turn_off_two_factor_authentication_is_throttled() {
+    let app = spawn_app_with_configuration(|config| {
+        config.login_protection.username_failure_limit.max_requests = 3;
+        config.login_protection.failure_delay_milliseconds = 0;
+    }).await;
+    let (secret, recovery_codes) = enable_two_factor(&app).await;
+    app.login_with_test_user().await;
+    app.post_login_two_factor(&recovery_codes[0]).await;
+
+    for _ in 0..3 {
+        app.post_two_factor_action("disable", &serde_json::json!({ "code": "000000" })).await;
+    }
+    app.post_two_factor_action("disable", &serde_json::json!({ "code": next_code(&secret) })).await;
+
+    let html_page = app.get_two_factor().await.text().await.unwrap();
+    assert!(html_page.contains("Too many incorrect codes. Please try again later."));
+    assert!(html_page.contains("is on"));
+}
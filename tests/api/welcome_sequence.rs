@@ -0,0 +1,201 @@
+use crate::helpers::{spawn_app, TestApp};
+use wiremock::{Mock, ResponseTemplate};
+use wiremock::matchers::{path, method};
+
+async fn add_welcome_step(app: &TestApp, delay_days: i32, subject: &str) {
+    let response = app.post_welcome_step(&serde_json::json!({
+        "delay_days": delay_days,
+        "subject": subject,
+        "html_content": "<p>Hello</p>",
+        "text_content": "Hello",
+    })).await;
+    assert_eq!(response.status().as_u16(), 303);
+}
+
+async fn create_confirmed_subscriber(app: &TestApp) {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriptions("name=Dione&email=dione%40email.com".into()).await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
+
+// Everything after the confirmation email.
+async fn welcome_subjects(app: &TestApp) -> Vec<String> {
+    app.email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .skip(1)
+        .map(|request| {
+            let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+            body["Subject"].as_str().unwrap().to_owned()
+        })
+        .collect()
+}
+
+async fn move_enrollment_back(app: &TestApp, days: i32) {
+    sqlx::query!(
+        "UPDATE welcome_sequence_enrollments SET enrolled_at = enrolled_at - make_interval(days => $1)",
+        days
+    )
+        .execute(&app.connection)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_the_welcome_sequence() {
+    let app = spawn_app().await;
+
+    let response = app.get_welcome_sequence().await;
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(response.headers().get("Location").unwrap(), "/login");
+
+    let response = app.post_welcome_step(&serde_json::json!({
+        "delay_days": 0,
+        "subject": "Welcome aboard",
+        "html_content": "<p>Hello</p>",
+        "text_content": "Hello",
+    })).await;
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(response.headers().get("Location").unwrap(), "/login");
+}
+
+#[tokio::test]
+async fn welcome_steps_are_listed_in_order() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+
+    add_welcome_step(&app, 7, "One week in").await;
+    add_welcome_step(&app, 0, "Welcome aboard").await;
+
+    let html_page = app.get_welcome_sequence().await.text().await.unwrap();
+    let first = html_page.find("Welcome aboard").unwrap();
+    let second = html_page.find("One week in").unwrap();
+    assert!(first < second);
+}
+
+#[tokio::test]
+async fn negative_delays_are_rejected() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+
+    add_welcome_step(&app, -1, "Too early").await;
+
+    let html_page = app.get_welcome_sequence().await.text().await.unwrap();
+    assert!(html_page.contains("Welcome emails cannot be sent before confirmation."));
+    assert!(!html_page.contains("Too early"));
+}
+
+#[tokio::test]
+async fn each_step_is_sent_once_its_delay_has_passed() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+    add_welcome_step(&app, 0, "Welcome aboard").await;
+    add_welcome_step(&app, 3, "Getting started").await;
+    create_confirmed_subscriber(&app).await;
+
+    let sent = app.send_due_welcome_emails().await.unwrap();
+    assert_eq!(sent, 1);
+    assert_eq!(welcome_subjects(&app).await, vec!["Welcome aboard"]);
+
+    move_enrollment_back(&app, 3).await;
+    let sent = app.send_due_welcome_emails().await.unwrap();
+    assert_eq!(sent, 1);
+    assert_eq!(welcome_subjects(&app).await, vec!["Welcome aboard", "Getting started"]);
+
+    // Nothing is sent twice.
+    let sent = app.send_due_welcome_emails().await.unwrap();
+    assert_eq!(sent, 0);
+}
+
+#[tokio::test]
+async fn pending_subscribers_are_not_sent_the_welcome_sequence() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+    add_welcome_step(&app, 0, "Welcome aboard").await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriptions("name=Dione&email=dione%40email.com".into()).await;
+
+    let sent = app.send_due_welcome_emails().await.unwrap();
+
+    assert_eq!(sent, 0);
+    assert!(welcome_subjects(&app).await.is_empty());
+}
+
+#[tokio::test]
+async fn unsubscribing_stops_the_welcome_sequence() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+    add_welcome_step(&app, 0, "Welcome aboard").await;
+    create_confirmed_subscriber(&app).await;
+    let subscriber_id = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.connection)
+        .await
+        .unwrap()
+        .id;
+
+    app.post_subscriber_action("unsubscribe", &serde_json::json!({
+        "subscriber_id": subscriber_id
+    })).await;
+    let sent = app.send_due_welcome_emails().await.unwrap();
+
+    assert_eq!(sent, 0);
+    let enrollments = sqlx::query!("SELECT COUNT(*) AS count FROM welcome_sequence_enrollments")
+        .fetch_one(&app.connection)
+        .await
+        .unwrap();
+    assert_eq!(enrollments.count, Some(0));
+}
+
+#[tokio::test]
+async fn steps_added_later_are_not_sent_to_subscribers_already_past_them() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+    create_confirmed_subscriber(&app).await;
+    move_enrollment_back(&app, 10).await;
+
+    add_welcome_step(&app, 7, "One week in").await;
+    add_welcome_step(&app, 14, "Two weeks in").await;
+    app.send_due_welcome_emails().await.unwrap();
+    move_enrollment_back(&app, 4).await;
+    app.send_due_welcome_emails().await.unwrap();
+
+    assert_eq!(welcome_subjects(&app).await, vec!["Two weeks in"]);
+}
+
+#[tokio::test]
+async fn welcome_emails_link_to_the_subscriber_preferences_in_their_language() {
+    let app = spawn_app().await;
+    app.login_with_test_user().await;
+    add_welcome_step(&app, 0, "Welcome aboard").await;
+    create_confirmed_subscriber(&app).await;
+    sqlx::query!("UPDATE subscriptions SET locale = 'es'")
+        .execute(&app.connection)
+        .await
+        .unwrap();
+
+    app.send_due_welcome_emails().await.unwrap();
+
+    let email_request = &app.email_server.received_requests().await.unwrap()[1];
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    let html_body = body["HtmlBody"].as_str().unwrap();
+    let text_body = body["TextBody"].as_str().unwrap();
+    assert!(html_body.contains(&format!("{}/subscriptions/preferences?token=", app.base_url)));
+    assert!(html_body.contains("Gestiona tus preferencias"));
+    assert!(text_body.contains(&format!("{}/subscriptions/preferences?token=", app.base_url)));
+}